@@ -0,0 +1,51 @@
+//! Compares the throughput of `Parser::from_async_read` with a byte stream reading one byte at a
+//! time.
+//!
+//! Run with `cargo bench --features futures-io`.
+
+use std::{
+    pin::pin,
+    time::{Duration, Instant},
+};
+
+use async_gcode::{io::ReadError, Parser};
+use futures::{io::Cursor, stream, AsyncReadExt, Stream, StreamExt};
+
+const LINE: &str = "N120 G1 X12.5 Y-3.25 Z0.3 E0.0451 F1800 (perimeter)\n";
+
+/// Parses the whole input, returns the number of events.
+fn parse<S>(parser: Parser<S, ReadError<std::io::Error>>) -> usize
+where
+    S: Stream<Item = Result<u8, ReadError<std::io::Error>>> + Unpin,
+{
+    futures_executor::block_on(parser.into_stream().count())
+}
+
+fn measure(name: &str, len: usize, mut f: impl FnMut() -> usize) {
+    let mut best = Duration::MAX;
+    for _ in 0..10 {
+        let start = Instant::now();
+        std::hint::black_box(f());
+        best = best.min(start.elapsed());
+    }
+    let throughput = len as f64 / best.as_secs_f64() / 1e6;
+    println!("{:<12} {:>8.1?} {:>8.1} MB/s", name, best, throughput);
+}
+
+fn main() {
+    let input = LINE.repeat(20_000).into_bytes();
+
+    measure("bytes", input.len(), || {
+        let bytes = stream::unfold(Cursor::new(&input), |mut reader| async move {
+            let mut byte = [0];
+            match reader.read(&mut byte).await {
+                Ok(0) => None,
+                res => Some((res.map(|_| byte[0]).map_err(ReadError::Io), reader)),
+            }
+        });
+        parse(Parser::new(pin!(bytes)))
+    });
+    measure("async_read", input.len(), || {
+        parse(Parser::from_async_read(Cursor::new(&input)))
+    });
+}
@@ -1,8 +1,7 @@
-use futures::stream;
-use futures_executor::block_on;
-use std::io::Read;
+use std::io::{BufReader, Read};
 
 #[derive(Debug)]
+#[allow(dead_code)] // fields are only read through `Debug`
 enum Error {
     Io(std::io::Error),
     Parse(async_gcode::Error),
@@ -13,13 +12,13 @@ impl From<async_gcode::Error> for Error {
     }
 }
 fn main() {
-    block_on(async {
-        let mut parser = async_gcode::Parser::new(stream::iter(
-            std::io::stdin().bytes().map(|res| res.map_err(Error::Io)),
-        ));
+    let parser = async_gcode::SyncParser::new(
+        BufReader::new(std::io::stdin())
+            .bytes()
+            .map(|res| res.map_err(Error::Io)),
+    );
 
-        while let Some(res) = parser.next().await {
-            println!("{:?}", res);
-        }
-    });
+    for res in parser {
+        println!("{:?}", res);
+    }
 }
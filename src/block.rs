@@ -0,0 +1,218 @@
+//! Line level assembly of the `GCode` events.
+//!
+//! A [`Block`] gathers all the events of a line up to its `GCode::Execute`. Its content is stored
+//! in buffers selected by a [`BlockStorage`]: [`Unbounded`] relies on `Vec`s while [`Bounded`]
+//! uses fixed capacity arrays and does not require any allocation.
+
+use core::ops::Deref;
+
+#[cfg(all(
+    not(feature = "std"),
+    any(
+        feature = "parse-expressions",
+        feature = "parse-parameters",
+        feature = "parse-comments",
+        feature = "string-value"
+    )
+))]
+use alloc::vec::Vec;
+
+#[cfg(feature = "parse-comments")]
+use crate::types::Comment;
+use crate::RealValue;
+#[cfg(feature = "parse-o-words")]
+use crate::{OCode, OWord};
+
+/// A buffer holding the elements of a block.
+pub trait Buffer<T>: Default + Deref<Target = [T]> {
+    /// Appends `value` to the buffer or gives it back if the buffer is full.
+    fn try_push(&mut self, value: T) -> Result<(), T>;
+    fn clear(&mut self);
+}
+
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+impl<T> Buffer<T> for Vec<T> {
+    fn try_push(&mut self, value: T) -> Result<(), T> {
+        self.push(value);
+        Ok(())
+    }
+    fn clear(&mut self) {
+        Vec::clear(self)
+    }
+}
+
+/// A buffer holding at most `N` elements.
+#[derive(Debug, Clone)]
+pub struct BoundedBuffer<T, const N: usize> {
+    len: usize,
+    items: [T; N],
+}
+impl<T: Default, const N: usize> Default for BoundedBuffer<T, N> {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            items: core::array::from_fn(|_| T::default()),
+        }
+    }
+}
+impl<T, const N: usize> Deref for BoundedBuffer<T, N> {
+    type Target = [T];
+    fn deref(&self) -> &[T] {
+        &self.items[..self.len]
+    }
+}
+impl<T: Default, const N: usize> Buffer<T> for BoundedBuffer<T, N> {
+    fn try_push(&mut self, value: T) -> Result<(), T> {
+        match self.items.get_mut(self.len) {
+            Some(slot) => {
+                *slot = value;
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(value),
+        }
+    }
+    fn clear(&mut self) {
+        // drop the previous values to release any resource they may hold.
+        self.items[..self.len].fill_with(T::default);
+        self.len = 0;
+    }
+}
+impl<T: PartialEq, const N: usize> PartialEq for BoundedBuffer<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.deref() == other.deref()
+    }
+}
+
+/// Selects the buffers used by a [`Block`].
+pub trait BlockStorage {
+    type Words: Buffer<(char, RealValue)>;
+    #[cfg(feature = "parse-parameters")]
+    type ParameterSets: Buffer<(RealValue, RealValue)>;
+    #[cfg(feature = "parse-comments")]
+    type Comments: Buffer<Comment>;
+}
+
+/// Storage backed by `Vec`s.
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+#[derive(Debug)]
+pub struct Unbounded;
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+impl BlockStorage for Unbounded {
+    type Words = Vec<(char, RealValue)>;
+    #[cfg(feature = "parse-parameters")]
+    type ParameterSets = Vec<(RealValue, RealValue)>;
+    #[cfg(feature = "parse-comments")]
+    type Comments = Vec<Comment>;
+}
+
+/// Storage holding up to `N` elements of each kind without allocation.
+#[derive(Debug)]
+pub struct Bounded<const N: usize>;
+impl<const N: usize> BlockStorage for Bounded<N> {
+    type Words = BoundedBuffer<(char, RealValue), N>;
+    #[cfg(feature = "parse-parameters")]
+    type ParameterSets = BoundedBuffer<(RealValue, RealValue), N>;
+    #[cfg(feature = "parse-comments")]
+    type Comments = BoundedBuffer<Comment, N>;
+}
+
+/// All the content of a line.
+pub struct Block<S: BlockStorage> {
+    pub block_delete: bool,
+    pub line_number: Option<u32>,
+    pub words: S::Words,
+    #[cfg(feature = "parse-parameters")]
+    pub parameter_sets: S::ParameterSets,
+    #[cfg(feature = "parse-comments")]
+    pub comments: S::Comments,
+    #[cfg(feature = "parse-o-words")]
+    pub o_code: Option<(OWord, OCode)>,
+    #[cfg(feature = "parse-checksum")]
+    /// The checksum received for this line, if any. It has already been verified by the parser.
+    pub checksum: Option<u16>,
+}
+
+impl<S: BlockStorage> Block<S> {
+    pub fn new() -> Self {
+        Self {
+            block_delete: false,
+            line_number: None,
+            words: Default::default(),
+            #[cfg(feature = "parse-parameters")]
+            parameter_sets: Default::default(),
+            #[cfg(feature = "parse-comments")]
+            comments: Default::default(),
+            #[cfg(feature = "parse-o-words")]
+            o_code: None,
+            #[cfg(feature = "parse-checksum")]
+            checksum: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.block_delete = false;
+        self.line_number = None;
+        self.words.clear();
+        #[cfg(feature = "parse-parameters")]
+        self.parameter_sets.clear();
+        #[cfg(feature = "parse-comments")]
+        self.comments.clear();
+        #[cfg(feature = "parse-o-words")]
+        {
+            self.o_code = None;
+        }
+        #[cfg(feature = "parse-checksum")]
+        {
+            self.checksum = None;
+        }
+    }
+
+    /// Returns the value of the first word with the given letter (lowercase).
+    pub fn word(&self, letter: char) -> Option<&RealValue> {
+        self.words
+            .iter()
+            .find_map(|(l, value)| (*l == letter).then_some(value))
+    }
+}
+impl<S: BlockStorage> Default for Block<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: BlockStorage> core::fmt::Debug for Block<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut dbg = f.debug_struct("Block");
+        dbg.field("block_delete", &self.block_delete)
+            .field("line_number", &self.line_number)
+            .field("words", &self.words.deref());
+        #[cfg(feature = "parse-parameters")]
+        dbg.field("parameter_sets", &self.parameter_sets.deref());
+        #[cfg(feature = "parse-comments")]
+        dbg.field("comments", &self.comments.deref());
+        #[cfg(feature = "parse-o-words")]
+        dbg.field("o_code", &self.o_code);
+        #[cfg(feature = "parse-checksum")]
+        dbg.field("checksum", &self.checksum);
+        dbg.finish()
+    }
+}
@@ -0,0 +1,111 @@
+//! Checksums covering a line up to its `*`.
+//!
+//! [`XorSum`] is the one used by RepRap firmwares and the default of the [`Parser`]. CRCs detect
+//! more errors over noisy links, they are selected with [`Parser::with_checksum`] and
+//! [`Serializer::with_checksum`].
+//!
+//! [`Parser`]: crate::Parser
+//! [`Parser::with_checksum`]: crate::Parser::with_checksum
+//! [`Serializer::with_checksum`]: crate::Serializer::with_checksum
+
+/// Accumulates the checksum of the bytes of a line.
+///
+/// The default value is the one of an empty line.
+pub trait Checksum: Default + Copy {
+    /// The greatest value of the checksum, it bounds the number following `*`.
+    const MAX: u16;
+
+    /// Adds the next byte of the line.
+    fn update(&mut self, byte: u8);
+
+    /// The checksum of the bytes added so far, as written after `*`.
+    fn value(&self) -> u16;
+}
+
+/// Xor of all the bytes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct XorSum(u8);
+
+impl Checksum for XorSum {
+    const MAX: u16 = u8::MAX as u16;
+
+    fn update(&mut self, byte: u8) {
+        self.0 ^= byte;
+    }
+
+    fn value(&self) -> u16 {
+        self.0.into()
+    }
+}
+
+/// CRC-8/SMBUS: polynomial `0x07`, no reflection, initial value and final xor of 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Crc8(u8);
+
+impl Checksum for Crc8 {
+    const MAX: u16 = u8::MAX as u16;
+
+    fn update(&mut self, byte: u8) {
+        // computed bitwise rather than with a table to save 256 bytes of ROM.
+        self.0 ^= byte;
+        for _ in 0..8 {
+            self.0 = if self.0 & 0x80 != 0 {
+                (self.0 << 1) ^ 0x07
+            } else {
+                self.0 << 1
+            };
+        }
+    }
+
+    fn value(&self) -> u16 {
+        self.0.into()
+    }
+}
+
+/// CRC-16/CCITT-FALSE: polynomial `0x1021`, no reflection, initial value `0xFFFF` and final xor
+/// of 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Crc16(u16);
+
+impl Default for Crc16 {
+    fn default() -> Self {
+        Self(0xFFFF)
+    }
+}
+
+impl Checksum for Crc16 {
+    const MAX: u16 = u16::MAX;
+
+    fn update(&mut self, byte: u8) {
+        self.0 ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            self.0 = if self.0 & 0x8000 != 0 {
+                (self.0 << 1) ^ 0x1021
+            } else {
+                self.0 << 1
+            };
+        }
+    }
+
+    fn value(&self) -> u16 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Checksum, Crc16, Crc8, XorSum};
+
+    fn checksum<C: Checksum>(bytes: &[u8]) -> u16 {
+        let mut sum = C::default();
+        bytes.iter().for_each(|&b| sum.update(b));
+        sum.value()
+    }
+
+    #[test]
+    fn checksums_match_their_check_values() {
+        assert_eq!(checksum::<XorSum>(b"N1 G28"), 18);
+        assert_eq!(checksum::<Crc8>(b"123456789"), 0xF4);
+        assert_eq!(checksum::<Crc16>(b"123456789"), 0x29B1);
+    }
+}
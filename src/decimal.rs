@@ -0,0 +1,240 @@
+//! Exact decimal numbers, as written in the source.
+
+use core::fmt;
+
+use crate::{utils::math, Limits};
+
+const POW10: [i64; 19] = [
+    1,
+    10,
+    100,
+    1_000,
+    10_000,
+    100_000,
+    1_000_000,
+    10_000_000,
+    100_000_000,
+    1_000_000_000,
+    10_000_000_000,
+    100_000_000_000,
+    1_000_000_000_000,
+    10_000_000_000_000,
+    100_000_000_000_000,
+    1_000_000_000_000_000,
+    10_000_000_000_000_000,
+    100_000_000_000_000_000,
+    1_000_000_000_000_000_000,
+];
+
+/// A decimal number worth `mantissa * 10^-scale`.
+///
+/// Parsed numbers keep the digits they were written with: `1.50` has a mantissa of `150` and a
+/// scale of `2`. Comparisons are made on the value, `1.50` is equal to `1.5`.
+///
+/// ```
+/// use async_gcode::Decimal;
+///
+/// let x = Decimal::new(12_3456, 4);
+/// // in micrometres, rounded half away from zero.
+/// assert_eq!(x.rescale(3), Some(12_346));
+/// assert_eq!(f64::from(x), 12.3456);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u8,
+}
+
+impl Decimal {
+    /// The largest supported scale.
+    pub const MAX_SCALE: u8 = 18;
+
+    /// Builds `mantissa * 10^-scale`.
+    ///
+    /// # Panics
+    ///
+    /// If `scale` is greater than [`Decimal::MAX_SCALE`].
+    pub const fn new(mantissa: i64, scale: u8) -> Self {
+        assert!(scale <= Self::MAX_SCALE, "scale out of range");
+        Self { mantissa, scale }
+    }
+
+    pub fn mantissa(&self) -> i64 {
+        self.mantissa
+    }
+
+    /// Number of digits after the decimal point.
+    pub fn scale(&self) -> u8 {
+        self.scale
+    }
+
+    /// Returns the value as a multiple of `10^-scale`, rounded half away from zero.
+    ///
+    /// Returns `None` if the result does not fit in an `i64`.
+    pub fn rescale(&self, scale: u8) -> Option<i64> {
+        if scale >= self.scale {
+            10i64
+                .checked_pow(u32::from(scale - self.scale))
+                .and_then(|factor| self.mantissa.checked_mul(factor))
+        } else {
+            let divisor = POW10[usize::from(self.scale - scale)];
+            let (quotient, remainder) = (self.mantissa / divisor, self.mantissa % divisor);
+            // cannot overflow: the remainder is smaller than 10^18.
+            if remainder.abs() * 2 >= divisor {
+                Some(quotient + remainder.signum())
+            } else {
+                Some(quotient)
+            }
+        }
+    }
+
+    /// Rounds to at most `scale` digits after the decimal point.
+    ///
+    /// The digits are kept as is if there are not more than `scale` of them.
+    pub fn round(&self, scale: u8) -> Self {
+        match self.rescale(scale) {
+            Some(mantissa) if scale < self.scale => Self { mantissa, scale }.normalized(),
+            _ => *self,
+        }
+    }
+
+    /// The value as an `f64`.
+    ///
+    /// This is the nearest `f64` as long as the mantissa fits in 53 bits. Larger mantissas are
+    /// rounded before the division, the result may then be off by one unit in the last place.
+    pub fn to_f64(&self) -> f64 {
+        // powers of ten are exact up to 10^22, the division of exact operands is correctly rounded.
+        self.mantissa as f64 / POW10[usize::from(self.scale)] as f64
+    }
+
+    /// The value as an `f32`, rounded through an `f64`.
+    ///
+    /// Rounding twice may differ from the nearest `f32` by one unit in the last place.
+    pub fn to_f32(&self) -> f32 {
+        self.to_f64() as f32
+    }
+
+    /// Removes the trailing zeros of the fractional part.
+    fn normalized(self) -> Self {
+        let Self {
+            mut mantissa,
+            mut scale,
+        } = self;
+        while scale != 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        Self { mantissa, scale }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        let (lhs, rhs) = (self.normalized(), other.normalized());
+        lhs.mantissa == rhs.mantissa && lhs.scale == rhs.scale
+    }
+}
+impl Eq for Decimal {}
+
+impl From<i32> for Decimal {
+    fn from(from: i32) -> Self {
+        Self::new(from.into(), 0)
+    }
+}
+impl From<u32> for Decimal {
+    fn from(from: u32) -> Self {
+        Self::new(from.into(), 0)
+    }
+}
+
+/// Keeps as many of the first 9 fractional digits as the mantissa can hold. Values out of range
+/// saturate and NaN converts to 0.
+impl From<f64> for Decimal {
+    fn from(from: f64) -> Self {
+        let mut scale = Limits::MAX_FRACTION_DIGITS;
+        while scale != 0 && math::abs(from) * POW10[usize::from(scale)] as f64 >= i64::MAX as f64 {
+            scale -= 1;
+        }
+        let mantissa = math::round(from * POW10[usize::from(scale)] as f64) as i64;
+        Self { mantissa, scale }.normalized()
+    }
+}
+
+/// See the conversion from `f64`.
+impl From<f32> for Decimal {
+    fn from(from: f32) -> Self {
+        Self::from(f64::from(from))
+    }
+}
+
+impl From<Decimal> for f64 {
+    fn from(from: Decimal) -> Self {
+        from.to_f64()
+    }
+}
+impl From<Decimal> for f32 {
+    fn from(from: Decimal) -> Self {
+        from.to_f32()
+    }
+}
+
+/// Writes all the digits of the number, trailing zeros included.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let divisor = POW10[usize::from(self.scale)].unsigned_abs();
+        let abs = self.mantissa.unsigned_abs();
+        if self.mantissa < 0 {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", abs / divisor)?;
+        if self.scale != 0 {
+            write!(f, ".{:01$}", abs % divisor, usize::from(self.scale))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Decimal;
+
+    #[test]
+    fn values_are_compared_regardless_of_trailing_zeros() {
+        assert_eq!(Decimal::new(150, 2), Decimal::new(15, 1));
+        assert_eq!(Decimal::new(0, 4), Decimal::from(0));
+        assert_ne!(Decimal::new(15, 2), Decimal::new(15, 1));
+    }
+
+    #[test]
+    fn rescaling_rounds_half_away_from_zero() {
+        assert_eq!(Decimal::new(12_3455, 4).rescale(3), Some(12_346));
+        assert_eq!(Decimal::new(-12_3455, 4).rescale(3), Some(-12_346));
+        assert_eq!(Decimal::new(-12_3454, 4).rescale(3), Some(-12_345));
+        assert_eq!(Decimal::new(12, 0).rescale(3), Some(12_000));
+        assert_eq!(Decimal::new(i64::MAX, 0).rescale(1), None);
+    }
+
+    #[test]
+    fn conversions_to_floats_are_correctly_rounded() {
+        assert_eq!(Decimal::new(1, 1).to_f64(), 0.1);
+        assert_eq!(Decimal::new(-21_098, 3).to_f64(), -21.098);
+        assert_eq!(Decimal::new(123_456_789, 9).to_f32(), 0.12345679f32);
+    }
+
+    #[test]
+    fn conversions_from_floats_keep_9_fractional_digits() {
+        assert_eq!(Decimal::from(0.1), Decimal::new(1, 1));
+        assert_eq!(Decimal::from(-2.0000000004), Decimal::new(-2, 0));
+        assert_eq!(Decimal::from(1e12).rescale(0), Some(1_000_000_000_000));
+        assert_eq!(Decimal::from(f64::NAN), Decimal::from(0));
+    }
+
+    #[test]
+    fn digits_are_displayed_as_is() {
+        assert_eq!(Decimal::new(150, 2).to_string(), "1.50");
+        assert_eq!(Decimal::new(-5, 3).to_string(), "-0.005");
+        assert_eq!(Decimal::new(-5, 0).to_string(), "-5");
+        assert_eq!(Decimal::new(12_3456, 4).round(2).to_string(), "12.35");
+        assert_eq!(Decimal::new(10_001, 4).round(2).to_string(), "1");
+    }
+}
@@ -0,0 +1,20 @@
+//! Interpretation of the parsed blocks following RS274/NGC.
+//!
+//! The words of a block are first decoded into a [`DecodedBlock`] which checks that no two codes
+//! of the same modal group are used on the same line. The [`ModalState`] then keeps track of the
+//! active modes from one block to the next.
+//!
+//! The [`Executor`] finally turns the blocks into calls to a [`CanonicalMachine`] following the
+//! RS274/NGC order of execution.
+
+mod canonical;
+mod executor;
+mod modal;
+
+pub use canonical::{ArcFeed, CanonicalMachine, Position};
+pub use executor::Executor;
+pub use modal::{
+    Coolant, CoordinateSystem, CutterCompensation, DecodedBlock, DistanceMode, FeedRateMode,
+    ModalGroup, ModalState, Motion, NonModal, PathControl, Plane, ProbeMode, ReturnMode, Spindle,
+    Stop, ToolLengthOffset, Units,
+};
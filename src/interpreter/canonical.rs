@@ -0,0 +1,132 @@
+use super::{PathControl, Plane, ProbeMode, Units};
+use crate::Real;
+
+/// A position on all 6 axes.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Position {
+    pub x: Real,
+    pub y: Real,
+    pub z: Real,
+    pub a: Real,
+    pub b: Real,
+    pub c: Real,
+}
+
+impl Position {
+    pub(crate) const AXES: [char; 6] = ['x', 'y', 'z', 'a', 'b', 'c'];
+
+    pub(crate) fn axis(&self, letter: char) -> Real {
+        let mut copy = *self;
+        *copy.axis_mut(letter)
+    }
+
+    pub(crate) fn axis_mut(&mut self, letter: char) -> &mut Real {
+        match letter {
+            'x' => &mut self.x,
+            'y' => &mut self.y,
+            'z' => &mut self.z,
+            'a' => &mut self.a,
+            'b' => &mut self.b,
+            _ => &mut self.c,
+        }
+    }
+
+    pub(crate) fn map2(self, other: Self, f: impl Fn(Real, Real) -> Real) -> Self {
+        Self {
+            x: f(self.x, other.x),
+            y: f(self.y, other.y),
+            z: f(self.z, other.z),
+            a: f(self.a, other.a),
+            b: f(self.b, other.b),
+            c: f(self.c, other.c),
+        }
+    }
+}
+impl core::ops::Add for Position {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        self.map2(rhs, |a, b| a + b)
+    }
+}
+impl core::ops::Sub for Position {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        self.map2(rhs, |a, b| a - b)
+    }
+}
+
+/// Arguments of [`CanonicalMachine::arc_feed`].
+///
+/// `first` and `second` refer to the axes of the selected plane (X & Y for `Plane::XY`, Z & X
+/// for `Plane::ZX`, Y & Z for `Plane::YZ`), the remaining linear axis is the plane's normal.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ArcFeed {
+    pub first_end: Real,
+    pub second_end: Real,
+    pub first_center: Real,
+    pub second_center: Real,
+    /// Positive for counter clockwise turns, negative for clockwise turns.
+    pub rotation: i32,
+    /// End point on the axis normal to the plane, for helical motions.
+    pub axis_end_point: Real,
+    pub a: Real,
+    pub b: Real,
+    pub c: Real,
+}
+
+/// Canonical machining functions as defined by NIST's RS274/NGC interpreter.
+///
+/// Positions are given in the program's coordinate system and length units, the origin offsets
+/// being reported through [`CanonicalMachine::set_origin_offsets`].
+///
+/// Only the motions are mandatory, all other functions default to doing nothing.
+pub trait CanonicalMachine {
+    // Representation
+    fn set_origin_offsets(&mut self, _offsets: Position) {}
+    fn use_length_units(&mut self, _units: Units) {}
+    fn select_plane(&mut self, _plane: Plane) {}
+
+    // Free space motion
+    fn straight_traverse(&mut self, end: Position);
+
+    // Machining attributes
+    fn set_feed_rate(&mut self, _rate: Real) {}
+    fn set_motion_control_mode(&mut self, _mode: PathControl) {}
+    /// `left` is true for G41 and false for G42.
+    fn start_cutter_radius_compensation(&mut self, _left: bool) {}
+    fn stop_cutter_radius_compensation(&mut self) {}
+
+    // Machining functions
+    fn arc_feed(&mut self, arc: ArcFeed);
+    fn dwell(&mut self, _seconds: Real) {}
+    fn straight_feed(&mut self, end: Position);
+    fn straight_probe(&mut self, _end: Position, _mode: ProbeMode) {}
+
+    // Spindle functions
+    fn set_spindle_speed(&mut self, _rpm: Real) {}
+    fn start_spindle_clockwise(&mut self) {}
+    fn start_spindle_counterclockwise(&mut self) {}
+    fn stop_spindle_turning(&mut self) {}
+
+    // Tool functions
+    /// Selects the offset `H` to use (G43) or cancels it (G49).
+    fn use_tool_length_offset(&mut self, _offset: Option<u32>) {}
+    fn select_tool(&mut self, _slot: u32) {}
+    fn change_tool(&mut self, _slot: u32) {}
+
+    // Miscellaneous functions
+    fn comment(&mut self, _text: &str) {}
+    fn message(&mut self, _text: &str) {}
+    fn enable_overrides(&mut self) {}
+    fn disable_overrides(&mut self) {}
+    fn flood_on(&mut self) {}
+    fn flood_off(&mut self) {}
+    fn mist_on(&mut self) {}
+    fn mist_off(&mut self) {}
+
+    // Program functions
+    fn program_stop(&mut self) {}
+    fn optional_program_stop(&mut self) {}
+    fn program_end(&mut self) {}
+    fn pallet_shuttle(&mut self) {}
+}
@@ -0,0 +1,1094 @@
+use super::{
+    canonical::{ArcFeed, CanonicalMachine, Position},
+    Coolant, CoordinateSystem, CutterCompensation, DecodedBlock, DistanceMode, FeedRateMode,
+    ModalGroup, ModalState, Motion, NonModal, Plane, ProbeMode, ReturnMode, Spindle, Stop,
+    ToolLengthOffset, Units,
+};
+use crate::{
+    block::{Block, BlockStorage},
+    Error, Real, RealValue,
+};
+
+#[cfg(not(feature = "f32"))]
+use crate::utils::math;
+#[cfg(feature = "f32")]
+use crate::utils::math::single as math;
+#[cfg(feature = "f32")]
+use core::f32::consts::{FRAC_PI_2, PI};
+#[cfg(not(feature = "f32"))]
+use core::f64::consts::{FRAC_PI_2, PI};
+
+/// A motion computed before its block is executed, with the feed rate to set first in inverse time
+/// mode.
+#[derive(Debug)]
+enum Move {
+    Traverse(Position),
+    Feed(Position, Option<Real>),
+    Arc(ArcFeed, Position, Option<Real>),
+    Probe(Position, ProbeMode),
+    Drill(Drill, Option<Real>),
+}
+
+/// A G81 or, with a dwell, G82 drilling cycle.
+#[derive(Debug)]
+struct Drill {
+    target: Position,
+    r: Real,
+    z: Real,
+    dwell: Option<Real>,
+}
+
+/// Sticky parameters of the canned cycles.
+#[derive(Debug, Clone, Copy, Default)]
+struct CannedCycle {
+    r: Option<Real>,
+    z: Option<Real>,
+    p: Option<Real>,
+}
+
+/// Turns decoded blocks into calls to a [`CanonicalMachine`].
+///
+/// The calls are made following RS274/NGC's order of execution:
+/// 1. comments & messages,
+/// 2. feed rate mode (G93, G94),
+/// 3. feed rate (F),
+/// 4. spindle speed (S),
+/// 5. tool selection (T),
+/// 6. tool change (M6),
+/// 7. spindle on or off (M3, M4, M5),
+/// 8. coolant on or off (M7, M8, M9),
+/// 9. enable or disable overrides (M48, M49),
+/// 10. dwell (G4),
+/// 11. active plane (G17, G18, G19),
+/// 12. length units (G20, G21),
+/// 13. cutter radius compensation on or off (G40, G41, G42),
+/// 14. tool length offset on or off (G43, G49),
+/// 15. coordinate system selection (G54 to G59.3),
+/// 16. path control mode (G61, G61.1, G64),
+/// 17. distance mode (G90, G91),
+/// 18. retract mode (G98, G99),
+/// 19. home (G28, G30), coordinate system data (G10) or axis offsets (G92 to G92.3),
+/// 20. motion (G0 to G3, G38.x, G80 to G89), possibly in machine coordinates (G53),
+/// 21. stop (M0, M1, M2, M30, M60).
+///
+/// Cutter radius compensation is only reported to the machine, the paths are not offset. Of the
+/// canned cycles only G81 and G82 are supported, in the XY plane.
+#[derive(Debug, Clone)]
+pub struct Executor {
+    state: ModalState,
+    position: Position,
+    coordinate_systems: [Position; 9],
+    axis_offset: Position,
+    suspended_axis_offset: Position,
+    home: [Position; 2],
+    feed_rate: Real,
+    tool: u32,
+    cycle: CannedCycle,
+    block_delete: bool,
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self {
+            state: ModalState::default(),
+            position: Position::default(),
+            coordinate_systems: [Position::default(); 9],
+            axis_offset: Position::default(),
+            suspended_axis_offset: Position::default(),
+            home: [Position::default(); 2],
+            feed_rate: 0.,
+            tool: 0,
+            cycle: CannedCycle::default(),
+            block_delete: false,
+        }
+    }
+
+    pub fn state(&self) -> &ModalState {
+        &self.state
+    }
+
+    /// The current position in the program's coordinate system.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Sets the positions, in machine coordinates, reached by G28 (`secondary == false`) and G30.
+    pub fn set_home(&mut self, secondary: bool, position: Position) {
+        self.home[usize::from(secondary)] = position;
+    }
+
+    /// When enabled, blocks starting with `/` are skipped by [`Executor::execute_block`].
+    pub fn set_block_delete(&mut self, enabled: bool) {
+        self.block_delete = enabled;
+    }
+
+    fn origin_offset(&self) -> Position {
+        self.coordinate_systems[usize::from(self.state.coordinate_system.0 - 1)] + self.axis_offset
+    }
+
+    /// Updates the origin while keeping the machine still.
+    fn move_origin<M: CanonicalMachine>(&mut self, machine: &mut M, f: impl FnOnce(&mut Self)) {
+        let machine_position = self.position + self.origin_offset();
+        f(self);
+        let offset = self.origin_offset();
+        self.position = machine_position - offset;
+        machine.set_origin_offsets(offset);
+    }
+
+    /// Evaluates, decodes and executes a `Block`.
+    ///
+    /// Comments starting with `MSG,` are reported as messages, once the block has been checked: a
+    /// rejected block does not report its comments either.
+    pub fn execute_block<S, M, F>(
+        &mut self,
+        block: &Block<S>,
+        machine: &mut M,
+        eval: F,
+    ) -> Result<(), Error>
+    where
+        S: BlockStorage,
+        M: CanonicalMachine,
+        F: FnMut(&RealValue) -> Result<Real, Error>,
+    {
+        if block.block_delete && self.block_delete {
+            return Ok(());
+        }
+        let decoded = DecodedBlock::from_block(block, eval)?;
+        let planned = self.check(&decoded)?;
+        #[cfg(feature = "parse-comments")]
+        for comment in block.comments.iter() {
+            match comment.get(..4) {
+                Some(prefix) if prefix.eq_ignore_ascii_case("msg,") => {
+                    machine.message(&comment[4..])
+                }
+                _ => machine.comment(comment),
+            }
+        }
+        self.apply(&decoded, planned, machine)
+    }
+
+    /// Checks the block and computes its motion, if any, before the state is changed or any call
+    /// is made to the machine. A rejected block therefore has no effect.
+    fn check(&self, block: &DecodedBlock) -> Result<Option<Move>, Error> {
+        let uses_axes = Position::AXES.iter().any(|&l| block.value(l).is_some());
+        let non_modal_uses_axes = matches!(
+            block.non_modal(),
+            Some(
+                NonModal::SetCoordinateSystemData
+                    | NonModal::Home
+                    | NonModal::SecondaryHome
+                    | NonModal::SetAxisOffsets
+            )
+        );
+        if non_modal_uses_axes && uses_axes && block.motion().is_some() {
+            return Err(Error::ModalGroupConflict(ModalGroup::Motion));
+        }
+        if !non_modal_uses_axes
+            && uses_axes
+            && block.motion().unwrap_or(self.state.motion) == Motion::Cancel
+        {
+            return Err(Error::MissingWord('g'));
+        }
+        if block.value('t').is_some() {
+            to_integer(block, 't')?;
+        }
+        match block.non_modal() {
+            Some(NonModal::Dwell) => match block.value('p') {
+                None => return Err(Error::MissingWord('p')),
+                Some(p) if p < 0. => return Err(Error::InvalidValue('p')),
+                Some(_) => {}
+            },
+            Some(NonModal::SetCoordinateSystemData) => {
+                if to_integer(block, 'l')? != 2 {
+                    return Err(Error::InvalidValue('l'));
+                }
+                if !(1..=9).contains(&to_integer(block, 'p')?) {
+                    return Err(Error::InvalidValue('p'));
+                }
+            }
+            _ => {}
+        }
+        if block.tool_length_offset() == Some(ToolLengthOffset::On) {
+            to_integer(block, 'h')?;
+        }
+        match block.non_modal() {
+            Some(NonModal::Dwell | NonModal::MachineCoordinates) | None if uses_axes => {
+                self.plan_motion(block).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Computes the motion of a block.
+    fn plan_motion(&self, block: &DecodedBlock) -> Result<Move, Error> {
+        // the modes and the origin in effect once the modal codes of the block are applied.
+        let mut state = self.state.clone();
+        state.update(block);
+        let offset =
+            self.coordinate_systems[usize::from(state.coordinate_system.0 - 1)] + self.axis_offset;
+        let position = self.position + self.origin_offset() - offset;
+        let target = target(
+            &state,
+            position,
+            offset,
+            block,
+            block.non_modal() == Some(NonModal::MachineCoordinates),
+        );
+
+        Ok(match state.motion {
+            Motion::Rapid => Move::Traverse(target),
+            Motion::Linear => {
+                let delta = target - position;
+                let mut length = math::hypot(math::hypot(delta.x, delta.y), delta.z);
+                if length == 0. {
+                    length = math::hypot(math::hypot(delta.a, delta.b), delta.c);
+                }
+                Move::Feed(target, self.feed_rate(&state, block, length)?)
+            }
+            Motion::ClockwiseArc | Motion::CounterClockwiseArc => {
+                let clockwise = state.motion == Motion::ClockwiseArc;
+                let (arc, length) = arc(&state, position, block, target, clockwise)?;
+                Move::Arc(arc, target, self.feed_rate(&state, block, length)?)
+            }
+            Motion::StraightProbe(mode) => Move::Probe(target, mode),
+            Motion::Cancel => return Err(Error::MissingWord('g')),
+            Motion::CannedCycle(cycle @ (81 | 82)) if state.plane == Plane::XY => {
+                let r = block
+                    .value('r')
+                    .or(self.cycle.r)
+                    .ok_or(Error::MissingWord('r'))?;
+                let z = block
+                    .value('z')
+                    .or(self.cycle.z)
+                    .ok_or(Error::MissingWord('z'))?;
+                let dwell = if cycle == 82 {
+                    Some(
+                        block
+                            .value('p')
+                            .or(self.cycle.p)
+                            .ok_or(Error::MissingWord('p'))?,
+                    )
+                } else {
+                    None
+                };
+                let drill = Drill {
+                    target,
+                    r,
+                    z,
+                    dwell,
+                };
+                Move::Drill(drill, self.feed_rate(&state, block, 0.)?)
+            }
+            Motion::CannedCycle(_) => return Err(Error::UnknownCode('g')),
+        })
+    }
+
+    /// Checks that a feed motion of the given length has a feed rate and returns the one to set
+    /// in inverse time mode.
+    fn feed_rate(
+        &self,
+        state: &ModalState,
+        block: &DecodedBlock,
+        length: Real,
+    ) -> Result<Option<Real>, Error> {
+        if state.feed_rate_mode == FeedRateMode::InverseTime {
+            let f = block.value('f').ok_or(Error::MissingWord('f'))?;
+            Ok(Some(length * f))
+        } else if block.value('f').unwrap_or(self.feed_rate) <= 0. {
+            Err(Error::MissingWord('f'))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Executes a decoded block.
+    pub fn execute<M: CanonicalMachine>(
+        &mut self,
+        block: &DecodedBlock,
+        machine: &mut M,
+    ) -> Result<(), Error> {
+        let planned = self.check(block)?;
+        self.apply(block, planned, machine)
+    }
+
+    /// Executes a checked block along with the motion planned for it.
+    fn apply<M: CanonicalMachine>(
+        &mut self,
+        block: &DecodedBlock,
+        planned: Option<Move>,
+        machine: &mut M,
+    ) -> Result<(), Error> {
+        let previous = self.state.clone();
+        self.state.update(block);
+
+        if let Some(f) = block.value('f') {
+            self.feed_rate = f;
+            if self.state.feed_rate_mode == FeedRateMode::UnitsPerMinute {
+                machine.set_feed_rate(f);
+            }
+        }
+        if let Some(s) = block.value('s') {
+            machine.set_spindle_speed(s);
+        }
+        if block.value('t').is_some() {
+            self.tool = to_integer(block, 't')?;
+            machine.select_tool(self.tool);
+        }
+        if block.tool_change() {
+            machine.change_tool(self.tool);
+        }
+        match block.spindle() {
+            Some(Spindle::Clockwise) => machine.start_spindle_clockwise(),
+            Some(Spindle::CounterClockwise) => machine.start_spindle_counterclockwise(),
+            Some(Spindle::Stopped) => machine.stop_spindle_turning(),
+            None => {}
+        }
+        match block.coolant() {
+            Some(Coolant { mist, flood }) if mist || flood => {
+                if mist {
+                    machine.mist_on();
+                }
+                if flood {
+                    machine.flood_on();
+                }
+            }
+            Some(_) => {
+                machine.mist_off();
+                machine.flood_off();
+            }
+            None => {}
+        }
+        match block.overrides() {
+            Some(true) => machine.enable_overrides(),
+            Some(false) => machine.disable_overrides(),
+            None => {}
+        }
+        if block.non_modal() == Some(NonModal::Dwell) {
+            machine.dwell(block.value('p').unwrap_or_default());
+        }
+        if let Some(plane) = block.plane() {
+            machine.select_plane(plane);
+        }
+        if let Some(units) = block.units() {
+            machine.use_length_units(units);
+        }
+        match block.cutter_compensation() {
+            Some(CutterCompensation::Off) => machine.stop_cutter_radius_compensation(),
+            Some(CutterCompensation::Left) => machine.start_cutter_radius_compensation(true),
+            Some(CutterCompensation::Right) => machine.start_cutter_radius_compensation(false),
+            None => {}
+        }
+        match block.tool_length_offset() {
+            Some(ToolLengthOffset::On) => {
+                machine.use_tool_length_offset(Some(to_integer(block, 'h')?))
+            }
+            Some(ToolLengthOffset::Off) => machine.use_tool_length_offset(None),
+            None => {}
+        }
+        if let Some(cs) = block.coordinate_system() {
+            self.state.coordinate_system = previous.coordinate_system;
+            self.move_origin(machine, |this| this.state.coordinate_system = cs);
+        }
+        if let Some(mode) = block.path_control() {
+            machine.set_motion_control_mode(mode);
+        }
+
+        let uses_axes = Position::AXES.iter().any(|&l| block.value(l).is_some());
+        match block.non_modal() {
+            Some(NonModal::Home | NonModal::SecondaryHome) => {
+                if uses_axes {
+                    let intermediate = target(
+                        &self.state,
+                        self.position,
+                        self.origin_offset(),
+                        block,
+                        false,
+                    );
+                    machine.straight_traverse(intermediate);
+                    self.position = intermediate;
+                }
+                let secondary = block.non_modal() == Some(NonModal::SecondaryHome);
+                let home = self.home[usize::from(secondary)] - self.origin_offset();
+                machine.straight_traverse(home);
+                self.position = home;
+            }
+            Some(NonModal::SetCoordinateSystemData) => {
+                let index = to_integer(block, 'p')? as usize - 1;
+                self.move_origin(machine, |this| {
+                    for &l in Position::AXES.iter() {
+                        if let Some(v) = block.value(l) {
+                            *this.coordinate_systems[index].axis_mut(l) = v;
+                        }
+                    }
+                });
+            }
+            Some(NonModal::SetAxisOffsets) => {
+                let cs_offset =
+                    self.coordinate_systems[usize::from(self.state.coordinate_system.0 - 1)];
+                let machine_position = self.position + self.origin_offset();
+                self.move_origin(machine, |this| {
+                    for &l in Position::AXES.iter() {
+                        if let Some(v) = block.value(l) {
+                            *this.axis_offset.axis_mut(l) =
+                                machine_position.axis(l) - cs_offset.axis(l) - v;
+                        }
+                    }
+                });
+            }
+            Some(NonModal::ResetAxisOffsets) => self.move_origin(machine, |this| {
+                this.axis_offset = Position::default();
+                this.suspended_axis_offset = Position::default();
+            }),
+            Some(NonModal::SuspendAxisOffsets) => self.move_origin(machine, |this| {
+                this.suspended_axis_offset = this.axis_offset;
+                this.axis_offset = Position::default();
+            }),
+            Some(NonModal::RestoreAxisOffsets) => self.move_origin(machine, |this| {
+                this.axis_offset = this.suspended_axis_offset
+            }),
+            Some(NonModal::Dwell | NonModal::MachineCoordinates) | None => {
+                if let Some(planned) = planned {
+                    self.perform(planned, machine);
+                }
+            }
+        }
+
+        match block.stop() {
+            Some(Stop::ProgramStop) => machine.program_stop(),
+            Some(Stop::OptionalStop) => machine.optional_program_stop(),
+            Some(Stop::PalletShuttle) => {
+                machine.pallet_shuttle();
+                machine.program_stop();
+            }
+            Some(Stop::ProgramEnd | Stop::ProgramEndAndRewind) => {
+                machine.program_end();
+                self.end_program(machine);
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Resets the modes as required at the end of a program.
+    fn end_program<M: CanonicalMachine>(&mut self, machine: &mut M) {
+        self.move_origin(machine, |this| {
+            this.state.coordinate_system = CoordinateSystem(1);
+            this.suspended_axis_offset = this.axis_offset;
+            this.axis_offset = Position::default();
+        });
+        self.state.plane = Plane::XY;
+        machine.select_plane(Plane::XY);
+        self.state.distance = DistanceMode::Absolute;
+        self.state.feed_rate_mode = FeedRateMode::UnitsPerMinute;
+        self.state.overrides_enabled = true;
+        machine.enable_overrides();
+        self.state.cutter_compensation = CutterCompensation::Off;
+        machine.stop_cutter_radius_compensation();
+        self.state.spindle = Spindle::Stopped;
+        machine.stop_spindle_turning();
+        self.state.motion = Motion::Linear;
+        self.state.coolant = Default::default();
+        machine.mist_off();
+        machine.flood_off();
+    }
+
+    fn perform<M: CanonicalMachine>(&mut self, planned: Move, machine: &mut M) {
+        self.position = match planned {
+            Move::Traverse(target) => {
+                machine.straight_traverse(target);
+                target
+            }
+            Move::Feed(target, feed_rate) => {
+                if let Some(f) = feed_rate {
+                    machine.set_feed_rate(f);
+                }
+                machine.straight_feed(target);
+                target
+            }
+            Move::Arc(arc, target, feed_rate) => {
+                if let Some(f) = feed_rate {
+                    machine.set_feed_rate(f);
+                }
+                machine.arc_feed(arc);
+                target
+            }
+            Move::Probe(target, mode) => {
+                machine.straight_probe(target, mode);
+                target
+            }
+            Move::Drill(drill, feed_rate) => return self.drill(drill, feed_rate, machine),
+        };
+    }
+
+    /// Executes G81 or G82.
+    fn drill<M: CanonicalMachine>(
+        &mut self,
+        drill: Drill,
+        feed_rate: Option<Real>,
+        machine: &mut M,
+    ) {
+        let Drill {
+            target,
+            r,
+            z,
+            dwell,
+        } = drill;
+        if let Some(f) = feed_rate {
+            machine.set_feed_rate(f);
+        }
+        self.cycle = CannedCycle {
+            r: Some(r),
+            z: Some(z),
+            p: dwell.or(self.cycle.p),
+        };
+        let initial_z = self.position.z;
+        let (r, z) = match self.state.distance {
+            DistanceMode::Absolute => (r, z),
+            DistanceMode::Incremental => (initial_z + r, initial_z + r + z),
+        };
+        let clear_z = match self.state.return_mode {
+            ReturnMode::InitialLevel => r.max(initial_z),
+            ReturnMode::RLevel => r,
+        };
+
+        let mut position = self.position;
+        if position.z < r {
+            position.z = r;
+            machine.straight_traverse(position);
+        }
+        position = Position {
+            z: position.z,
+            ..target
+        };
+        machine.straight_traverse(position);
+        position.z = r;
+        machine.straight_traverse(position);
+        position.z = z;
+        machine.straight_feed(position);
+        if let Some(p) = dwell {
+            machine.dwell(p);
+        }
+        position.z = clear_z;
+        machine.straight_traverse(position);
+        self.position = position;
+    }
+}
+
+/// Computes the target of a motion in the program's coordinate system.
+fn target(
+    state: &ModalState,
+    position: Position,
+    offset: Position,
+    block: &DecodedBlock,
+    machine_coordinates: bool,
+) -> Position {
+    let mut target = position;
+    for &l in Position::AXES.iter() {
+        if let Some(v) = block.value(l) {
+            *target.axis_mut(l) = if machine_coordinates {
+                v - offset.axis(l)
+            } else if state.distance == DistanceMode::Incremental {
+                position.axis(l) + v
+            } else {
+                v
+            };
+        }
+    }
+    target
+}
+
+/// Computes an arc and its length.
+fn arc(
+    state: &ModalState,
+    position: Position,
+    block: &DecodedBlock,
+    target: Position,
+    clockwise: bool,
+) -> Result<(ArcFeed, Real), Error> {
+    let (first, second, normal, i, j) = match state.plane {
+        Plane::XY => ('x', 'y', 'z', 'i', 'j'),
+        Plane::ZX => ('z', 'x', 'y', 'k', 'i'),
+        Plane::YZ => ('y', 'z', 'x', 'j', 'k'),
+    };
+    let tolerance = match state.units {
+        Units::Inches => 0.0002,
+        Units::Millimeters => 0.002,
+    };
+    let (start_first, start_second) = (position.axis(first), position.axis(second));
+    let (end_first, end_second) = (target.axis(first), target.axis(second));
+
+    let (center_first, center_second, radius) = if let Some(r) = block.value('r') {
+        // radius format: positive radius for arcs of less than half a turn.
+        let abs_radius = math::abs(r);
+        let (mid_first, mid_second) = (
+            (start_first + end_first) / 2.,
+            (start_second + end_second) / 2.,
+        );
+        let half_length = math::hypot(end_first - mid_first, end_second - mid_second);
+        if abs_radius == 0. || half_length == 0. || half_length > abs_radius + tolerance {
+            return Err(Error::InvalidArc);
+        }
+        let half_length = half_length.min(abs_radius);
+        let theta = math::atan2(end_second - start_second, end_first - start_first)
+            + if clockwise == (r > 0.) {
+                -FRAC_PI_2
+            } else {
+                FRAC_PI_2
+            };
+        let offset = abs_radius * math::cos(math::asin(half_length / abs_radius));
+        let (center_first, center_second) = (
+            mid_first + offset * math::cos(theta),
+            mid_second + offset * math::sin(theta),
+        );
+        if center_first.is_nan() || center_second.is_nan() {
+            return Err(Error::InvalidArc);
+        }
+        (center_first, center_second, abs_radius)
+    } else {
+        // center format: offsets are always relative to the start point.
+        if block.value(i).is_none() && block.value(j).is_none() {
+            return Err(Error::MissingWord('r'));
+        }
+        let center_first = start_first + block.value(i).unwrap_or_default();
+        let center_second = start_second + block.value(j).unwrap_or_default();
+        let radius = math::hypot(start_first - center_first, start_second - center_second);
+        let end_radius = math::hypot(end_first - center_first, end_second - center_second);
+        if radius == 0. || math::abs(radius - end_radius) > tolerance {
+            return Err(Error::InvalidArc);
+        }
+        (center_first, center_second, radius)
+    };
+
+    let start_angle = math::atan2(start_second - center_second, start_first - center_first);
+    let end_angle = math::atan2(end_second - center_second, end_first - center_first);
+    let mut sweep = if clockwise {
+        start_angle - end_angle
+    } else {
+        end_angle - start_angle
+    };
+    if sweep <= 0. {
+        sweep += 2. * PI;
+    }
+    let length = math::hypot(radius * sweep, target.axis(normal) - position.axis(normal));
+
+    Ok((
+        ArcFeed {
+            first_end: end_first,
+            second_end: end_second,
+            first_center: center_first,
+            second_center: center_second,
+            rotation: if clockwise { -1 } else { 1 },
+            axis_end_point: target.axis(normal),
+            a: target.a,
+            b: target.b,
+            c: target.c,
+        },
+        length,
+    ))
+}
+
+/// Reads the value of a word that must be a positive integer.
+fn to_integer(block: &DecodedBlock, letter: char) -> Result<u32, Error> {
+    let value = block.value(letter).ok_or(Error::MissingWord(letter))?;
+    let integer = (value + 0.5) as u32;
+    if value < 0. || math::abs(value - integer as Real) > 1e-4 {
+        Err(Error::InvalidValue(letter))
+    } else {
+        Ok(integer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Executor;
+    use crate::interpreter::{
+        ArcFeed, CanonicalMachine, DecodedBlock, ModalGroup, Position, ProbeMode,
+    };
+    use crate::{Error, Real};
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Offsets(Position),
+        Traverse(Position),
+        Feed(Position),
+        Arc(ArcFeed),
+        FeedRate(Real),
+        Speed(Real),
+        SelectTool(u32),
+        ChangeTool(u32),
+        SpindleClockwise,
+        Dwell(Real),
+        Probe(Position, ProbeMode),
+        FloodOn,
+        ProgramStop,
+        Message(String),
+    }
+
+    #[derive(Default)]
+    struct Recorder(Vec<Call>);
+    impl CanonicalMachine for Recorder {
+        fn set_origin_offsets(&mut self, offsets: Position) {
+            self.0.push(Call::Offsets(offsets))
+        }
+        fn straight_traverse(&mut self, end: Position) {
+            self.0.push(Call::Traverse(end))
+        }
+        fn straight_feed(&mut self, end: Position) {
+            self.0.push(Call::Feed(end))
+        }
+        fn arc_feed(&mut self, arc: ArcFeed) {
+            self.0.push(Call::Arc(arc))
+        }
+        fn set_feed_rate(&mut self, rate: Real) {
+            self.0.push(Call::FeedRate(rate))
+        }
+        fn set_spindle_speed(&mut self, rpm: Real) {
+            self.0.push(Call::Speed(rpm))
+        }
+        fn select_tool(&mut self, slot: u32) {
+            self.0.push(Call::SelectTool(slot))
+        }
+        fn change_tool(&mut self, slot: u32) {
+            self.0.push(Call::ChangeTool(slot))
+        }
+        fn start_spindle_clockwise(&mut self) {
+            self.0.push(Call::SpindleClockwise)
+        }
+        fn dwell(&mut self, seconds: Real) {
+            self.0.push(Call::Dwell(seconds))
+        }
+        fn straight_probe(&mut self, end: Position, mode: ProbeMode) {
+            self.0.push(Call::Probe(end, mode))
+        }
+        fn flood_on(&mut self) {
+            self.0.push(Call::FloodOn)
+        }
+        fn program_stop(&mut self) {
+            self.0.push(Call::ProgramStop)
+        }
+        fn message(&mut self, text: &str) {
+            self.0.push(Call::Message(text.into()))
+        }
+    }
+
+    fn run(executor: &mut Executor, words: &[(char, Real)]) -> Result<Vec<Call>, Error> {
+        let mut machine = Recorder::default();
+        executor.execute(&DecodedBlock::decode(words.iter().copied())?, &mut machine)?;
+        Ok(machine.0)
+    }
+
+    fn xyz(x: Real, y: Real, z: Real) -> Position {
+        Position {
+            x,
+            y,
+            z,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn calls_follow_the_order_of_execution() {
+        let mut executor = Executor::new();
+        assert_eq!(
+            run(
+                &mut executor,
+                &[
+                    ('m', 0.),
+                    ('g', 1.),
+                    ('x', 1.),
+                    ('m', 8.),
+                    ('m', 3.),
+                    ('m', 6.),
+                    ('t', 2.),
+                    ('s', 1000.),
+                    ('g', 4.),
+                    ('p', 0.5),
+                    ('f', 100.),
+                ]
+            ),
+            Ok(vec![
+                Call::FeedRate(100.),
+                Call::Speed(1000.),
+                Call::SelectTool(2),
+                Call::ChangeTool(2),
+                Call::SpindleClockwise,
+                Call::FloodOn,
+                Call::Dwell(0.5),
+                Call::Feed(xyz(1., 0., 0.)),
+                Call::ProgramStop,
+            ])
+        );
+    }
+
+    #[test]
+    fn motion_mode_and_distance_mode_are_modal() {
+        let mut executor = Executor::new();
+        run(&mut executor, &[('g', 0.), ('x', 1.), ('y', 2.)]).unwrap();
+        assert_eq!(
+            run(&mut executor, &[('g', 91.), ('x', 1.)]),
+            Ok(vec![Call::Traverse(xyz(2., 2., 0.))])
+        );
+        assert_eq!(executor.position(), xyz(2., 2., 0.));
+    }
+
+    #[test]
+    fn feed_motion_requires_a_feed_rate() {
+        let mut executor = Executor::new();
+        assert_eq!(
+            run(&mut executor, &[('g', 1.), ('x', 1.)]),
+            Err(Error::MissingWord('f'))
+        );
+        assert_eq!(
+            run(
+                &mut executor,
+                &[('g', 93.), ('g', 1.), ('x', 2.), ('f', 3.)]
+            ),
+            Ok(vec![Call::FeedRate(6.), Call::Feed(xyz(2., 0., 0.))])
+        );
+    }
+
+    #[test]
+    fn axis_words_require_a_motion() {
+        let mut executor = Executor::new();
+        assert_eq!(
+            run(&mut executor, &[('x', 1.)]),
+            Err(Error::MissingWord('g'))
+        );
+        assert_eq!(
+            run(&mut executor, &[('g', 0.), ('g', 92.), ('x', 1.)]),
+            Err(Error::ModalGroupConflict(ModalGroup::Motion))
+        );
+    }
+
+    #[test]
+    fn arcs_in_center_and_radius_format() {
+        let mut executor = Executor::new();
+        let expected = Call::Arc(ArcFeed {
+            first_end: 1.,
+            second_end: 1.,
+            first_center: 1.,
+            second_center: 0.,
+            rotation: -1,
+            axis_end_point: 0.,
+            a: 0.,
+            b: 0.,
+            c: 0.,
+        });
+        assert_eq!(
+            run(
+                &mut executor,
+                &[('g', 2.), ('x', 1.), ('y', 1.), ('i', 1.), ('f', 100.)]
+            ),
+            Ok(vec![Call::FeedRate(100.), expected])
+        );
+
+        let mut executor = Executor::new();
+        let calls = run(
+            &mut executor,
+            &[('g', 2.), ('x', 1.), ('y', 1.), ('r', 1.), ('f', 100.)],
+        )
+        .unwrap();
+        match &calls[1] {
+            Call::Arc(arc) => {
+                assert!((arc.first_center - 1.).abs() < 1e-9, "{:?}", arc);
+                assert!(arc.second_center.abs() < 1e-9, "{:?}", arc);
+                assert_eq!(arc.rotation, -1);
+            }
+            call => panic!("unexpected {:?}", call),
+        }
+
+        assert_eq!(
+            run(&mut executor, &[('g', 3.), ('x', 5.), ('r', 1.)]),
+            Err(Error::InvalidArc)
+        );
+        assert_eq!(
+            run(&mut executor, &[('g', 3.), ('x', 5.), ('i', 1.)]),
+            Err(Error::InvalidArc)
+        );
+        // a null radius, even for a chord within the tolerance.
+        assert_eq!(
+            run(&mut executor, &[('g', 3.), ('x', 0.001), ('r', 0.)]),
+            Err(Error::InvalidArc)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "parse-comments")]
+    fn rejected_blocks_do_not_report_their_messages() {
+        use crate::{Block, Parser, RealValue, Unbounded};
+
+        let input = b"(MSG,hi) X1\n(MSG,hi) G0 X1\n".iter();
+        let mut parser = Parser::new(futures::stream::iter(input.map(|&b| Ok::<_, Error>(b))));
+        let mut block = Block::<Unbounded>::default();
+        let mut executor = Executor::new();
+        let eval = |value: &RealValue| match value {
+            RealValue::Literal(literal) => literal.as_real_number().ok_or(Error::InvalidValue('x')),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::InvalidValue('x')),
+        };
+        let mut execute = |executor: &mut Executor| {
+            futures_executor::block_on(parser.next_block(&mut block))
+                .unwrap()
+                .unwrap();
+            let mut machine = Recorder::default();
+            executor
+                .execute_block(&block, &mut machine, eval)
+                .map(|_| machine.0)
+        };
+        assert_eq!(execute(&mut executor), Err(Error::MissingWord('g')));
+        assert_eq!(
+            execute(&mut executor),
+            Ok(vec![
+                Call::Message("hi".into()),
+                Call::Traverse(xyz(1., 0., 0.))
+            ])
+        );
+    }
+
+    #[test]
+    fn coordinate_systems_and_axis_offsets() {
+        let mut executor = Executor::new();
+        run(&mut executor, &[('g', 0.), ('x', 10.), ('y', 10.)]).unwrap();
+
+        // G10 L2 P2 X5: the current point is at X5 in G55
+        assert_eq!(
+            run(
+                &mut executor,
+                &[('g', 10.), ('l', 2.), ('p', 2.), ('x', 5.), ('g', 55.)]
+            ),
+            Ok(vec![
+                Call::Offsets(xyz(0., 0., 0.)),
+                Call::Offsets(xyz(5., 0., 0.))
+            ])
+        );
+        assert_eq!(executor.position(), xyz(5., 10., 0.));
+
+        // G92 Y0: the current point is now at Y0
+        assert_eq!(
+            run(&mut executor, &[('g', 92.), ('y', 0.)]),
+            Ok(vec![Call::Offsets(xyz(5., 10., 0.))])
+        );
+        assert_eq!(executor.position(), xyz(5., 0., 0.));
+
+        // G53 moves in machine coordinates
+        assert_eq!(
+            run(&mut executor, &[('g', 53.), ('x', 0.)]),
+            Ok(vec![Call::Traverse(xyz(-5., 0., 0.))])
+        );
+
+        run(&mut executor, &[('g', 92.1)]).unwrap();
+        assert_eq!(executor.position(), xyz(-5., 10., 0.));
+    }
+
+    #[test]
+    fn home_goes_through_the_intermediate_point() {
+        let mut executor = Executor::new();
+        executor.set_home(false, xyz(1., 1., 1.));
+        assert_eq!(
+            run(&mut executor, &[('g', 28.), ('z', 5.)]),
+            Ok(vec![
+                Call::Traverse(xyz(0., 0., 5.)),
+                Call::Traverse(xyz(1., 1., 1.))
+            ])
+        );
+    }
+
+    #[test]
+    fn drilling_cycle() {
+        let mut executor = Executor::new();
+        run(&mut executor, &[('g', 0.), ('z', 5.)]).unwrap();
+        assert_eq!(
+            run(
+                &mut executor,
+                &[
+                    ('g', 98.),
+                    ('g', 82.),
+                    ('x', 1.),
+                    ('z', -1.),
+                    ('r', 2.),
+                    ('p', 0.5),
+                    ('f', 10.)
+                ]
+            ),
+            Ok(vec![
+                Call::FeedRate(10.),
+                Call::Traverse(xyz(1., 0., 5.)),
+                Call::Traverse(xyz(1., 0., 2.)),
+                Call::Feed(xyz(1., 0., -1.)),
+                Call::Dwell(0.5),
+                Call::Traverse(xyz(1., 0., 5.)),
+            ])
+        );
+        // the cycle's parameters are sticky
+        assert_eq!(
+            run(&mut executor, &[('x', 2.)]).map(|calls| calls.len()),
+            Ok(5)
+        );
+        assert_eq!(
+            run(&mut executor, &[('g', 84.), ('x', 2.)]),
+            Err(Error::UnknownCode('g'))
+        );
+    }
+
+    #[test]
+    fn probing() {
+        let mut executor = Executor::new();
+        assert_eq!(
+            run(&mut executor, &[('g', 38.3), ('z', -10.)]),
+            Ok(vec![Call::Probe(xyz(0., 0., -10.), ProbeMode::Toward)])
+        );
+    }
+
+    #[test]
+    fn rejected_blocks_have_no_effect() {
+        let mut executor = Executor::new();
+        for words in [
+            &[('m', 3.), ('g', 1.), ('g', 55.), ('x', 1.)][..],
+            &[('m', 3.), ('g', 2.), ('x', 5.), ('r', 1.), ('f', 100.)],
+            &[
+                ('m', 3.),
+                ('g', 84.),
+                ('x', 1.),
+                ('z', -1.),
+                ('r', 1.),
+                ('f', 100.),
+            ],
+            &[
+                ('m', 3.),
+                ('g', 82.),
+                ('x', 1.),
+                ('z', -1.),
+                ('r', 1.),
+                ('f', 100.),
+            ],
+        ] {
+            let state = executor.state().clone();
+            assert!(run(&mut executor, words).is_err(), "{:?}", words);
+            assert_eq!(executor.state(), &state);
+            assert_eq!(executor.position(), xyz(0., 0., 0.));
+        }
+    }
+
+    #[test]
+    fn non_modal_codes_are_checked_before_any_call() {
+        let mut executor = Executor::new();
+        assert_eq!(
+            run(&mut executor, &[('s', 100.), ('g', 4.)]),
+            Err(Error::MissingWord('p'))
+        );
+        assert_eq!(
+            run(&mut executor, &[('g', 10.), ('l', 1.), ('p', 1.)]),
+            Err(Error::InvalidValue('l'))
+        );
+        assert_eq!(
+            run(&mut executor, &[('t', 1.5)]),
+            Err(Error::InvalidValue('t'))
+        );
+    }
+}
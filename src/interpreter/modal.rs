@@ -0,0 +1,683 @@
+use crate::{
+    block::{Block, BlockStorage},
+    Error, Real, RealValue,
+};
+
+#[cfg(not(feature = "f32"))]
+use crate::utils::math;
+#[cfg(feature = "f32")]
+use crate::utils::math::single as math;
+
+/// Modal groups as defined by RS274/NGC.
+///
+/// At most one code of each group may be used on a line.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ModalGroup {
+    /// G group 0: G4, G10, G28, G30, G53, G92, G92.1, G92.2, G92.3.
+    NonModal,
+    /// G group 1: G0, G1, G2, G3, G38.2-G38.5, G80-G89.
+    Motion,
+    /// G group 2: G17, G18, G19.
+    Plane,
+    /// G group 3: G90, G91.
+    Distance,
+    /// G group 5: G93, G94.
+    FeedRateMode,
+    /// G group 6: G20, G21.
+    Units,
+    /// G group 7: G40, G41, G42.
+    CutterCompensation,
+    /// G group 8: G43, G49.
+    ToolLengthOffset,
+    /// G group 10: G98, G99.
+    ReturnMode,
+    /// G group 12: G54-G59, G59.1-G59.3.
+    CoordinateSystem,
+    /// G group 13: G61, G61.1, G64.
+    PathControl,
+    /// M group 4: M0, M1, M2, M30, M60.
+    Stop,
+    /// M group 6: M6.
+    ToolChange,
+    /// M group 7: M3, M4, M5.
+    Spindle,
+    /// M group 8: M7, M8, M9.
+    Coolant,
+    /// M group 9: M48, M49.
+    Override,
+}
+const GROUP_COUNT: usize = 16;
+
+impl ModalGroup {
+    /// Finds the group of a code given in tenths (e.g. G38.2 is `382`, M3 is `30`).
+    fn of(letter: char, code: u16) -> Option<Self> {
+        Some(match (letter, code) {
+            ('g', 40 | 100 | 280 | 300 | 530 | 920 | 921 | 922 | 923) => Self::NonModal,
+            ('g', 0 | 10 | 20 | 30 | 382..=385 | 800 | 810 | 820 | 830 | 840 | 850 | 860)
+            | ('g', 870 | 880 | 890) => Self::Motion,
+            ('g', 170 | 180 | 190) => Self::Plane,
+            ('g', 900 | 910) => Self::Distance,
+            ('g', 930 | 940) => Self::FeedRateMode,
+            ('g', 200 | 210) => Self::Units,
+            ('g', 400 | 410 | 420) => Self::CutterCompensation,
+            ('g', 430 | 490) => Self::ToolLengthOffset,
+            ('g', 980 | 990) => Self::ReturnMode,
+            ('g', 540 | 550 | 560 | 570 | 580 | 590..=593) => Self::CoordinateSystem,
+            ('g', 610 | 611 | 640) => Self::PathControl,
+            ('m', 0 | 10 | 20 | 300 | 600) => Self::Stop,
+            ('m', 60) => Self::ToolChange,
+            ('m', 30 | 40 | 50) => Self::Spindle,
+            ('m', 70 | 80 | 90) => Self::Coolant,
+            ('m', 480 | 490) => Self::Override,
+            _ => return None,
+        })
+    }
+}
+
+/// G group 0.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NonModal {
+    /// G4
+    Dwell,
+    /// G10
+    SetCoordinateSystemData,
+    /// G28
+    Home,
+    /// G30
+    SecondaryHome,
+    /// G53
+    MachineCoordinates,
+    /// G92
+    SetAxisOffsets,
+    /// G92.1
+    ResetAxisOffsets,
+    /// G92.2
+    SuspendAxisOffsets,
+    /// G92.3
+    RestoreAxisOffsets,
+}
+
+/// Direction and failure behaviour of a straight probe.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ProbeMode {
+    /// G38.2
+    TowardWithError,
+    /// G38.3
+    Toward,
+    /// G38.4
+    AwayWithError,
+    /// G38.5
+    Away,
+}
+
+/// G group 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Motion {
+    /// G0
+    Rapid,
+    /// G1
+    Linear,
+    /// G2
+    ClockwiseArc,
+    /// G3
+    CounterClockwiseArc,
+    /// G38.2-G38.5
+    StraightProbe(ProbeMode),
+    /// G80
+    Cancel,
+    /// G81-G89, the canned cycle's number is kept (e.g. `81`).
+    CannedCycle(u8),
+}
+
+/// G group 2.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Plane {
+    /// G17
+    XY,
+    /// G18
+    ZX,
+    /// G19
+    YZ,
+}
+
+/// G group 3.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DistanceMode {
+    /// G90
+    Absolute,
+    /// G91
+    Incremental,
+}
+
+/// G group 5.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FeedRateMode {
+    /// G93
+    InverseTime,
+    /// G94
+    UnitsPerMinute,
+}
+
+/// G group 6.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Units {
+    /// G20
+    Inches,
+    /// G21
+    Millimeters,
+}
+
+/// G group 7.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CutterCompensation {
+    /// G40
+    Off,
+    /// G41
+    Left,
+    /// G42
+    Right,
+}
+
+/// G group 8.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ToolLengthOffset {
+    /// G43
+    On,
+    /// G49
+    Off,
+}
+
+/// G group 10.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReturnMode {
+    /// G98
+    InitialLevel,
+    /// G99
+    RLevel,
+}
+
+/// G group 12: the index of the coordinate system from 1 (G54) to 9 (G59.3).
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CoordinateSystem(pub u8);
+
+/// G group 13.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PathControl {
+    /// G61
+    ExactPath,
+    /// G61.1
+    ExactStop,
+    /// G64
+    Continuous,
+}
+
+/// M group 4.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    /// M0
+    ProgramStop,
+    /// M1
+    OptionalStop,
+    /// M2
+    ProgramEnd,
+    /// M30
+    ProgramEndAndRewind,
+    /// M60
+    PalletShuttle,
+}
+
+/// M group 7.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Spindle {
+    /// M3
+    Clockwise,
+    /// M4
+    CounterClockwise,
+    /// M5
+    Stopped,
+}
+
+/// M group 8.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Coolant {
+    /// Set by M7, cleared by M9.
+    pub mist: bool,
+    /// Set by M8, cleared by M9.
+    pub flood: bool,
+}
+
+/// The words of a block sorted by letter and modal group.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct DecodedBlock {
+    codes: [Option<u16>; GROUP_COUNT],
+    values: [Option<Real>; 26],
+    /// Whether both M7 and M8 were used, the only codes of a group allowed together.
+    mist_and_flood: bool,
+}
+
+impl DecodedBlock {
+    /// Decodes words with evaluated values.
+    pub fn decode<I>(words: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (char, Real)>,
+    {
+        let mut decoded = Self::default();
+        for (letter, value) in words {
+            decoded.push(letter, value)?;
+        }
+        Ok(decoded)
+    }
+
+    /// Decodes the words of a `Block`, `eval` being used to turn their values to numbers.
+    pub fn from_block<S, F>(block: &Block<S>, mut eval: F) -> Result<Self, Error>
+    where
+        S: BlockStorage,
+        F: FnMut(&RealValue) -> Result<Real, Error>,
+    {
+        let mut decoded = Self::default();
+        for (letter, value) in block.words.iter() {
+            decoded.push(*letter, eval(value)?)?;
+        }
+        Ok(decoded)
+    }
+
+    fn push(&mut self, letter: char, value: Real) -> Result<(), Error> {
+        let letter = letter.to_ascii_lowercase();
+        match letter {
+            'g' | 'm' => {
+                let (group, code) = to_tenths(value)
+                    .filter(|code| letter == 'g' || code % 10 == 0)
+                    .and_then(|code| Some((ModalGroup::of(letter, code)?, code)))
+                    .ok_or(Error::UnknownCode(letter))?;
+                let slot = &mut self.codes[group as usize];
+                match *slot {
+                    Some(70) if code == 80 && !self.mist_and_flood => self.mist_and_flood = true,
+                    Some(80) if code == 70 && !self.mist_and_flood => self.mist_and_flood = true,
+                    Some(_) => return Err(Error::ModalGroupConflict(group)),
+                    None => *slot = Some(code),
+                }
+            }
+            'a'..='z' => {
+                let slot = &mut self.values[usize::from(letter as u8 - b'a')];
+                if slot.is_some() {
+                    return Err(Error::RepeatedWord(letter));
+                }
+                *slot = Some(value);
+            }
+            _ => return Err(Error::UnexpectedByte(letter as u8)),
+        }
+        Ok(())
+    }
+
+    /// Returns the code used for a group in tenths (e.g. G38.2 is `382`, M3 is `30`).
+    pub fn code(&self, group: ModalGroup) -> Option<u16> {
+        self.codes[group as usize]
+    }
+
+    /// Returns the value of a word other than `G` and `M`.
+    pub fn value(&self, letter: char) -> Option<Real> {
+        match letter.to_ascii_lowercase() {
+            l @ 'a'..='z' if l != 'g' && l != 'm' => self.values[usize::from(l as u8 - b'a')],
+            _ => None,
+        }
+    }
+
+    pub fn non_modal(&self) -> Option<NonModal> {
+        Some(match self.code(ModalGroup::NonModal)? {
+            40 => NonModal::Dwell,
+            100 => NonModal::SetCoordinateSystemData,
+            280 => NonModal::Home,
+            300 => NonModal::SecondaryHome,
+            530 => NonModal::MachineCoordinates,
+            920 => NonModal::SetAxisOffsets,
+            921 => NonModal::ResetAxisOffsets,
+            922 => NonModal::SuspendAxisOffsets,
+            _ => NonModal::RestoreAxisOffsets,
+        })
+    }
+
+    pub fn motion(&self) -> Option<Motion> {
+        Some(match self.code(ModalGroup::Motion)? {
+            0 => Motion::Rapid,
+            10 => Motion::Linear,
+            20 => Motion::ClockwiseArc,
+            30 => Motion::CounterClockwiseArc,
+            382 => Motion::StraightProbe(ProbeMode::TowardWithError),
+            383 => Motion::StraightProbe(ProbeMode::Toward),
+            384 => Motion::StraightProbe(ProbeMode::AwayWithError),
+            385 => Motion::StraightProbe(ProbeMode::Away),
+            800 => Motion::Cancel,
+            code => Motion::CannedCycle((code / 10) as u8),
+        })
+    }
+
+    pub fn plane(&self) -> Option<Plane> {
+        Some(match self.code(ModalGroup::Plane)? {
+            170 => Plane::XY,
+            180 => Plane::ZX,
+            _ => Plane::YZ,
+        })
+    }
+
+    pub fn distance(&self) -> Option<DistanceMode> {
+        Some(match self.code(ModalGroup::Distance)? {
+            900 => DistanceMode::Absolute,
+            _ => DistanceMode::Incremental,
+        })
+    }
+
+    pub fn feed_rate_mode(&self) -> Option<FeedRateMode> {
+        Some(match self.code(ModalGroup::FeedRateMode)? {
+            930 => FeedRateMode::InverseTime,
+            _ => FeedRateMode::UnitsPerMinute,
+        })
+    }
+
+    pub fn units(&self) -> Option<Units> {
+        Some(match self.code(ModalGroup::Units)? {
+            200 => Units::Inches,
+            _ => Units::Millimeters,
+        })
+    }
+
+    pub fn cutter_compensation(&self) -> Option<CutterCompensation> {
+        Some(match self.code(ModalGroup::CutterCompensation)? {
+            400 => CutterCompensation::Off,
+            410 => CutterCompensation::Left,
+            _ => CutterCompensation::Right,
+        })
+    }
+
+    pub fn tool_length_offset(&self) -> Option<ToolLengthOffset> {
+        Some(match self.code(ModalGroup::ToolLengthOffset)? {
+            430 => ToolLengthOffset::On,
+            _ => ToolLengthOffset::Off,
+        })
+    }
+
+    pub fn return_mode(&self) -> Option<ReturnMode> {
+        Some(match self.code(ModalGroup::ReturnMode)? {
+            980 => ReturnMode::InitialLevel,
+            _ => ReturnMode::RLevel,
+        })
+    }
+
+    pub fn coordinate_system(&self) -> Option<CoordinateSystem> {
+        Some(CoordinateSystem(
+            match self.code(ModalGroup::CoordinateSystem)? {
+                code @ 540..=590 => ((code - 530) / 10) as u8,
+                code => (code - 584) as u8,
+            },
+        ))
+    }
+
+    pub fn path_control(&self) -> Option<PathControl> {
+        Some(match self.code(ModalGroup::PathControl)? {
+            610 => PathControl::ExactPath,
+            611 => PathControl::ExactStop,
+            _ => PathControl::Continuous,
+        })
+    }
+
+    pub fn stop(&self) -> Option<Stop> {
+        Some(match self.code(ModalGroup::Stop)? {
+            0 => Stop::ProgramStop,
+            10 => Stop::OptionalStop,
+            20 => Stop::ProgramEnd,
+            300 => Stop::ProgramEndAndRewind,
+            _ => Stop::PalletShuttle,
+        })
+    }
+
+    /// Returns true if the block contains an `M6`.
+    pub fn tool_change(&self) -> bool {
+        self.code(ModalGroup::ToolChange).is_some()
+    }
+
+    pub fn spindle(&self) -> Option<Spindle> {
+        Some(match self.code(ModalGroup::Spindle)? {
+            30 => Spindle::Clockwise,
+            40 => Spindle::CounterClockwise,
+            _ => Spindle::Stopped,
+        })
+    }
+
+    /// Returns the coolant turned on by M7 and M8, which may be used together, or none of it for
+    /// M9.
+    pub fn coolant(&self) -> Option<Coolant> {
+        Some(match self.code(ModalGroup::Coolant)? {
+            _ if self.mist_and_flood => Coolant {
+                mist: true,
+                flood: true,
+            },
+            70 => Coolant {
+                mist: true,
+                flood: false,
+            },
+            80 => Coolant {
+                mist: false,
+                flood: true,
+            },
+            _ => Coolant::default(),
+        })
+    }
+
+    /// Returns true if overrides are enabled (M48) or false if disabled (M49).
+    pub fn overrides(&self) -> Option<bool> {
+        self.code(ModalGroup::Override).map(|code| code == 480)
+    }
+}
+
+/// Converts a positive code to tenths if it is one.
+fn to_tenths(value: Real) -> Option<u16> {
+    let tenths = value * 10.;
+    if !(0. ..Real::from(u16::MAX)).contains(&tenths) {
+        return None;
+    }
+    let code = (tenths + 0.5) as u16;
+    (math::abs(tenths - Real::from(code)) < 1e-3).then_some(code)
+}
+
+/// The active modes.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ModalState {
+    pub motion: Motion,
+    pub plane: Plane,
+    pub distance: DistanceMode,
+    pub feed_rate_mode: FeedRateMode,
+    pub units: Units,
+    pub cutter_compensation: CutterCompensation,
+    pub tool_length_offset: ToolLengthOffset,
+    pub return_mode: ReturnMode,
+    pub coordinate_system: CoordinateSystem,
+    pub path_control: PathControl,
+    /// The stop requested by the last block, if any.
+    pub stop: Option<Stop>,
+    pub spindle: Spindle,
+    pub coolant: Coolant,
+    pub overrides_enabled: bool,
+}
+
+impl Default for ModalState {
+    /// The default state: `G80 G17 G90 G94 G21 G40 G49 G99 G54 G64 M5 M9 M48`.
+    fn default() -> Self {
+        Self {
+            motion: Motion::Cancel,
+            plane: Plane::XY,
+            distance: DistanceMode::Absolute,
+            feed_rate_mode: FeedRateMode::UnitsPerMinute,
+            units: Units::Millimeters,
+            cutter_compensation: CutterCompensation::Off,
+            tool_length_offset: ToolLengthOffset::Off,
+            return_mode: ReturnMode::RLevel,
+            coordinate_system: CoordinateSystem(1),
+            path_control: PathControl::Continuous,
+            stop: None,
+            spindle: Spindle::Stopped,
+            coolant: Coolant::default(),
+            overrides_enabled: true,
+        }
+    }
+}
+
+impl ModalState {
+    /// Applies the modal codes of a block.
+    pub fn update(&mut self, block: &DecodedBlock) {
+        macro_rules! update {
+            ($($field:ident),*) => {
+                $(
+                    if let Some(v) = block.$field() {
+                        self.$field = v;
+                    }
+                )*
+            };
+        }
+        update!(
+            motion,
+            plane,
+            distance,
+            feed_rate_mode,
+            units,
+            cutter_compensation,
+            tool_length_offset,
+            return_mode,
+            coordinate_system,
+            path_control,
+            spindle
+        );
+        self.stop = block.stop();
+        match block.coolant() {
+            Some(Coolant { mist, flood }) if mist || flood => {
+                self.coolant.mist |= mist;
+                self.coolant.flood |= flood;
+            }
+            Some(_) => self.coolant = Coolant::default(),
+            None => {}
+        }
+        if let Some(enabled) = block.overrides() {
+            self.overrides_enabled = enabled;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        Coolant, CoordinateSystem, DecodedBlock, ModalGroup, ModalState, Motion, PathControl,
+        ProbeMode, Spindle, Stop, Units,
+    };
+    use crate::Error;
+
+    #[test]
+    fn codes_are_sorted_by_modal_group() {
+        let block =
+            DecodedBlock::decode([('g', 1.), ('x', 2.), ('g', 20.), ('m', 3.), ('s', 1000.)])
+                .unwrap();
+        assert_eq!(block.code(ModalGroup::Motion), Some(10));
+        assert_eq!(block.code(ModalGroup::Units), Some(200));
+        assert_eq!(block.code(ModalGroup::Spindle), Some(30));
+        assert_eq!(block.code(ModalGroup::Plane), None);
+        assert_eq!(block.value('x'), Some(2.));
+        assert_eq!(block.value('S'), Some(1000.));
+        assert_eq!(block.value('g'), None);
+    }
+
+    #[test]
+    fn two_codes_of_the_same_modal_group_are_rejected() {
+        assert_eq!(
+            DecodedBlock::decode([('g', 0.), ('g', 1.)]),
+            Err(Error::ModalGroupConflict(ModalGroup::Motion))
+        );
+        assert_eq!(
+            DecodedBlock::decode([('g', 54.), ('g', 59.2)]),
+            Err(Error::ModalGroupConflict(ModalGroup::CoordinateSystem))
+        );
+        assert_eq!(
+            DecodedBlock::decode([('m', 7.), ('m', 9.)]),
+            Err(Error::ModalGroupConflict(ModalGroup::Coolant))
+        );
+        assert_eq!(
+            DecodedBlock::decode([('m', 7.), ('m', 8.), ('m', 7.)]),
+            Err(Error::ModalGroupConflict(ModalGroup::Coolant))
+        );
+        assert_eq!(
+            DecodedBlock::decode([('g', 4.), ('g', 92.)]),
+            Err(Error::ModalGroupConflict(ModalGroup::NonModal))
+        );
+    }
+
+    #[test]
+    fn mist_and_flood_may_be_turned_on_together() {
+        let both = Coolant {
+            mist: true,
+            flood: true,
+        };
+        for words in [[('m', 7.), ('m', 8.)], [('m', 8.), ('m', 7.)]] {
+            let block = DecodedBlock::decode(words).unwrap();
+            assert_eq!(block.coolant(), Some(both));
+
+            let mut state = ModalState::default();
+            state.update(&block);
+            assert_eq!(state.coolant, both);
+        }
+    }
+
+    #[test]
+    fn words_may_not_be_repeated() {
+        assert_eq!(
+            DecodedBlock::decode([('x', 0.), ('x', 1.)]),
+            Err(Error::RepeatedWord('x'))
+        );
+    }
+
+    #[test]
+    fn unknown_codes_are_rejected() {
+        for (letter, value) in [('g', 5.), ('g', 38.1), ('g', -1.), ('m', 3.5), ('m', 104.)] {
+            assert_eq!(
+                DecodedBlock::decode([(letter, value)]),
+                Err(Error::UnknownCode(letter)),
+                "{}{}",
+                letter,
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn decimal_codes_are_decoded() {
+        let block = DecodedBlock::decode([('g', 38.2), ('g', 59.3), ('g', 61.1)]).unwrap();
+        assert_eq!(
+            block.motion(),
+            Some(Motion::StraightProbe(ProbeMode::TowardWithError))
+        );
+        assert_eq!(block.coordinate_system(), Some(CoordinateSystem(9)));
+        assert_eq!(block.path_control(), Some(PathControl::ExactStop));
+
+        let block = DecodedBlock::decode([('g', 55.), ('g', 83.)]).unwrap();
+        assert_eq!(block.coordinate_system(), Some(CoordinateSystem(2)));
+        assert_eq!(block.motion(), Some(Motion::CannedCycle(83)));
+    }
+
+    #[test]
+    fn the_modal_state_is_kept_between_blocks() {
+        let mut state = ModalState::default();
+        state.update(&DecodedBlock::decode([('g', 20.), ('g', 1.), ('m', 4.), ('m', 7.)]).unwrap());
+        state.update(&DecodedBlock::decode([('x', 1.), ('m', 8.), ('m', 0.)]).unwrap());
+
+        assert_eq!(state.units, Units::Inches);
+        assert_eq!(state.motion, Motion::Linear);
+        assert_eq!(state.spindle, Spindle::CounterClockwise);
+        assert_eq!(
+            state.coolant,
+            Coolant {
+                mist: true,
+                flood: true
+            }
+        );
+        assert_eq!(state.stop, Some(Stop::ProgramStop));
+
+        state.update(&DecodedBlock::decode([('m', 9.)]).unwrap());
+        assert_eq!(state.coolant, Coolant::default());
+        assert_eq!(state.stop, None);
+    }
+}
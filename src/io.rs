@@ -0,0 +1,50 @@
+//! Adapters reading the parser's input from byte readers.
+//!
+//! - `embedded-io-async` readers are turned into streams by [`read_stream`] and
+//!   [`buf_read_stream`].
+//! - `futures` and `tokio` readers are buffered by [`AsyncReadStream`] and [`TokioReadStream`],
+//!   see [`Parser::from_async_read`](crate::Parser::from_async_read) and
+//!   [`Parser::from_tokio_read`](crate::Parser::from_tokio_read).
+
+use crate::Error;
+
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+mod async_read;
+#[cfg(feature = "embedded-io-async")]
+mod embedded;
+
+#[cfg(feature = "futures-io")]
+pub use async_read::AsyncReadStream;
+#[cfg(feature = "tokio")]
+pub use async_read::TokioReadStream;
+#[cfg(all(feature = "embedded-io-async", feature = "std"))]
+pub use embedded::BoxedReadStream;
+#[cfg(feature = "embedded-io-async")]
+pub use embedded::{buf_read_stream, read_stream};
+
+/// Errors of a parser reading from a reader.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ReadError<T> {
+    /// The reader failed.
+    Io(T),
+    /// The input is not valid.
+    Parsing(Error),
+}
+
+impl<T> From<Error> for ReadError<T> {
+    fn from(from: Error) -> Self {
+        Self::Parsing(from)
+    }
+}
+
+/// Gives the parsing error back, or the reader's failure.
+impl<T> TryFrom<ReadError<T>> for Error {
+    type Error = T;
+
+    fn try_from(from: ReadError<T>) -> Result<Self, T> {
+        match from {
+            ReadError::Io(error) => Err(error),
+            ReadError::Parsing(error) => Ok(error),
+        }
+    }
+}
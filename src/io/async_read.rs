@@ -0,0 +1,217 @@
+//! Buffered adapters for `futures` and `tokio` readers.
+//!
+//! Reading a byte at a time through `AsyncReadExt::bytes` costs a call to `poll_read` per byte.
+//! These adapters read chunks instead and hand the bytes out of their buffer.
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use std::io;
+
+use futures::{ready, Stream};
+
+use super::ReadError;
+use crate::Parser;
+
+/// Size of the buffer used by default.
+const BUFFER_LEN: usize = 8 * 1024;
+
+/// The bytes read and not yet handed out.
+struct Buffer {
+    bytes: Box<[u8]>,
+    start: usize,
+    end: usize,
+}
+
+impl Buffer {
+    fn new(len: usize) -> Self {
+        Self {
+            bytes: vec![0; len.max(1)].into_boxed_slice(),
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the next byte, refilling the buffer with `poll_read` when it is exhausted.
+    fn poll_next<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut poll_read: F,
+    ) -> Poll<Option<Result<u8, ReadError<io::Error>>>>
+    where
+        F: FnMut(&mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if let Some(&b) = self.bytes[..self.end].get(self.start) {
+                self.start += 1;
+                return Poll::Ready(Some(Ok(b)));
+            }
+            self.start = 0;
+            self.end = 0;
+            match ready!(poll_read(cx, &mut self.bytes)) {
+                Ok(0) => return Poll::Ready(None),
+                Ok(n) => self.end = n.min(self.bytes.len()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Poll::Ready(Some(Err(ReadError::Io(e)))),
+            }
+        }
+    }
+}
+
+/// A `Stream` of the bytes of a `futures::io::AsyncRead`, read in chunks.
+#[cfg(feature = "futures-io")]
+pub struct AsyncReadStream<R> {
+    reader: R,
+    buffer: Buffer,
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: futures::io::AsyncRead + Unpin> AsyncReadStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(reader, BUFFER_LEN)
+    }
+
+    /// Reads chunks of up to `len` bytes.
+    pub fn with_capacity(reader: R, len: usize) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::new(len),
+        }
+    }
+
+    /// Gives the reader back. The bytes buffered are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: futures::io::AsyncRead + Unpin> Stream for AsyncReadStream<R> {
+    type Item = Result<u8, ReadError<io::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self { reader, buffer } = self.get_mut();
+        buffer.poll_next(cx, |cx, bytes| Pin::new(&mut *reader).poll_read(cx, bytes))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl<R: futures::io::AsyncRead + Unpin> Parser<AsyncReadStream<R>, ReadError<io::Error>> {
+    /// Builds a parser reading from a `futures::io::AsyncRead` through an [`AsyncReadStream`].
+    ///
+    /// ```
+    /// use async_gcode::{GCode, Parser};
+    /// use futures::io::Cursor;
+    ///
+    /// let mut parser = Parser::from_async_read(Cursor::new(b"G1 X2\n"));
+    /// futures_executor::block_on(async {
+    ///     assert_eq!(parser.next().await.unwrap().unwrap(), GCode::Word('g', 1.into()));
+    /// });
+    /// ```
+    pub fn from_async_read(reader: R) -> Self {
+        Self::new(AsyncReadStream::new(reader))
+    }
+}
+
+/// A `Stream` of the bytes of a `tokio::io::AsyncRead`, read in chunks.
+#[cfg(feature = "tokio")]
+pub struct TokioReadStream<R> {
+    reader: R,
+    buffer: Buffer,
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> TokioReadStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_capacity(reader, BUFFER_LEN)
+    }
+
+    /// Reads chunks of up to `len` bytes.
+    pub fn with_capacity(reader: R, len: usize) -> Self {
+        Self {
+            reader,
+            buffer: Buffer::new(len),
+        }
+    }
+
+    /// Gives the reader back. The bytes buffered are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> Stream for TokioReadStream<R> {
+    type Item = Result<u8, ReadError<io::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let Self { reader, buffer } = self.get_mut();
+        buffer.poll_next(cx, |cx, bytes| {
+            let mut bytes = tokio::io::ReadBuf::new(bytes);
+            Pin::new(&mut *reader)
+                .poll_read(cx, &mut bytes)
+                .map_ok(|()| bytes.filled().len())
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> Parser<TokioReadStream<R>, ReadError<io::Error>> {
+    /// Builds a parser reading from a `tokio::io::AsyncRead` through a [`TokioReadStream`].
+    pub fn from_tokio_read(reader: R) -> Self {
+        Self::new(TokioReadStream::new(reader))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use futures::{Stream, StreamExt};
+
+    use crate::{io::ReadError, Error, GCode, Parser};
+
+    fn parse<S, E>(parser: Parser<S, E>) -> Vec<Result<GCode, E>>
+    where
+        S: Stream<Item = Result<u8, E>> + Unpin,
+        E: From<Error>,
+    {
+        futures_executor::block_on(parser.into_stream().collect())
+    }
+
+    fn expected() -> Vec<Result<GCode, ReadError<io::Error>>> {
+        vec![
+            Ok(GCode::Word('g', 1.into())),
+            Ok(GCode::Word('x', 2.5.into())),
+            Ok(GCode::Execute),
+        ]
+    }
+
+    /// Compares the results, `io::Error` not implementing `PartialEq`.
+    fn assert_same(actual: Vec<Result<GCode, ReadError<io::Error>>>) {
+        let debug = |results: Vec<_>| format!("{:?}", results);
+        assert_eq!(debug(actual), debug(expected()));
+    }
+
+    #[test]
+    #[cfg(feature = "futures-io")]
+    fn futures_readers_are_read_in_chunks() {
+        use super::AsyncReadStream;
+        use futures::io::Cursor;
+
+        assert_same(parse(Parser::from_async_read(Cursor::new(b"G1 X2.5\n"))));
+        let input = AsyncReadStream::with_capacity(Cursor::new(b"G1 X2.5\n"), 3);
+        assert_same(parse(Parser::new(input)));
+    }
+
+    #[test]
+    #[cfg(feature = "tokio")]
+    fn tokio_readers_are_read_in_chunks() {
+        use super::TokioReadStream;
+
+        assert_same(parse(Parser::from_tokio_read(&b"G1 X2.5\n"[..])));
+        let input = TokioReadStream::with_capacity(&b"G1 X2.5\n"[..], 3);
+        assert_same(parse(Parser::new(input)));
+    }
+}
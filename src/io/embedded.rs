@@ -0,0 +1,299 @@
+//! Adapters for [`embedded-io-async`] readers.
+//!
+//! [`read_stream`] reads chunks into a buffer provided by the caller, [`buf_read_stream`] takes
+//! the bytes one at a time from the reader's own buffer, consuming them as they are read. Either
+//! way the parser holds on to the byte ending an event until the next one, no byte is lost between
+//! two chunks.
+//!
+//! The streams hold the pending read, a future of the reader's `async fn` which cannot be named:
+//! they are returned as `impl Stream` and must be pinned before being given to the
+//! [`Parser`](crate::Parser). With `std`, [`Parser::from_read`](crate::Parser::from_read) and
+//! [`Parser::from_buf_read`](crate::Parser::from_buf_read) pin them in a box.
+//!
+//! [`embedded-io-async`]: https://docs.rs/embedded-io-async
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use embedded_io_async::{BufRead, ErrorType, Read};
+use futures::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use super::ReadError;
+#[cfg(feature = "std")]
+use crate::Parser;
+
+/// The reader and its buffer along with the number of bytes read.
+type Filled<R, B> = (R, B, Result<usize, <R as ErrorType>::Error>);
+
+/// Moves the reader and the buffer in and out of the future so that the future borrows neither.
+async fn read<R, B>(mut reader: R, mut buffer: B) -> Filled<R, B>
+where
+    R: Read,
+    B: AsMut<[u8]>,
+{
+    let res = reader.read(buffer.as_mut()).await;
+    (reader, buffer, res)
+}
+
+/// Takes the next byte of the reader's buffer out of the future, then consumes it.
+async fn buf_read<R: BufRead>(mut reader: R, mut byte: [u8; 1]) -> Filled<R, [u8; 1]> {
+    let res = match reader.fill_buf().await {
+        Ok(&[b, ..]) => {
+            byte[0] = b;
+            Ok(1)
+        }
+        Ok(_) => Ok(0),
+        Err(e) => Err(e),
+    };
+    if let Ok(n) = res {
+        reader.consume(n);
+    }
+    (reader, byte, res)
+}
+
+pin_project! {
+    /// The bytes of a reader, handed out of a buffer refilled by `step`.
+    struct ReadStream<R, B, F> {
+        // `None` while a read is pending.
+        idle: Option<(R, B)>,
+        start: usize,
+        end: usize,
+        #[pin]
+        future: Option<F>,
+        step: fn(R, B) -> F,
+    }
+}
+
+/// Reads chunks of up to `buffer.len()` bytes from `reader`.
+///
+/// The stream ends once the reader reports the end of its input by reading 0 bytes.
+///
+/// ```
+/// use async_gcode::{io, GCode, Parser};
+///
+/// let uart: &[u8] = b"G1 X2\n";
+/// let input = core::pin::pin!(io::read_stream(uart, [0; 4]));
+/// let mut parser = Parser::new(input);
+/// futures_executor::block_on(async {
+///     assert_eq!(parser.next().await, Some(Ok(GCode::Word('g', 1.into()))));
+///     assert_eq!(parser.next().await, Some(Ok(GCode::Word('x', 2.into()))));
+/// });
+/// ```
+pub fn read_stream<R, B>(
+    reader: R,
+    buffer: B,
+) -> impl Stream<Item = Result<u8, ReadError<R::Error>>>
+where
+    R: Read,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+{
+    ReadStream {
+        idle: Some((reader, buffer)),
+        start: 0,
+        end: 0,
+        future: None,
+        step: read::<R, B>,
+    }
+}
+
+/// Takes the bytes one at a time from the buffer of `reader`, which is only refilled once they
+/// are all consumed.
+///
+/// The stream ends once the reader reports the end of its input with an empty chunk.
+pub fn buf_read_stream<R>(reader: R) -> impl Stream<Item = Result<u8, ReadError<R::Error>>>
+where
+    R: BufRead,
+{
+    ReadStream {
+        idle: Some((reader, [0])),
+        start: 0,
+        end: 0,
+        future: None,
+        step: buf_read::<R>,
+    }
+}
+
+/// The stream of the bytes of a reader pinned in a box, for the parser to name its type.
+#[cfg(feature = "std")]
+pub type BoxedReadStream<'a, E> = Pin<Box<dyn Stream<Item = Result<u8, ReadError<E>>> + 'a>>;
+
+#[cfg(feature = "std")]
+impl<'a, E> Parser<BoxedReadStream<'a, E>, ReadError<E>> {
+    /// Builds a parser reading chunks of up to `buffer.len()` bytes from `reader` through
+    /// [`read_stream`].
+    ///
+    /// ```
+    /// use async_gcode::{GCode, Parser};
+    ///
+    /// let uart: &[u8] = b"G1 X2\n";
+    /// let mut parser = Parser::from_read(uart, [0; 4]);
+    /// futures_executor::block_on(async {
+    ///     assert_eq!(parser.next().await, Some(Ok(GCode::Word('g', 1.into()))));
+    /// });
+    /// ```
+    pub fn from_read<R, B>(reader: R, buffer: B) -> Self
+    where
+        R: Read + ErrorType<Error = E> + 'a,
+        B: AsRef<[u8]> + AsMut<[u8]> + 'a,
+    {
+        Self::new(Box::pin(read_stream(reader, buffer)))
+    }
+
+    /// Builds a parser taking the bytes from the buffer of `reader` through [`buf_read_stream`].
+    pub fn from_buf_read<R>(reader: R) -> Self
+    where
+        R: BufRead + ErrorType<Error = E> + 'a,
+    {
+        Self::new(Box::pin(buf_read_stream(reader)))
+    }
+}
+
+impl<R, B, F> Stream for ReadStream<R, B, F>
+where
+    R: ErrorType,
+    B: AsRef<[u8]>,
+    F: Future<Output = Filled<R, B>>,
+{
+    type Item = Result<u8, ReadError<R::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if let Some((_, buffer)) = this.idle {
+                if let Some(&b) = buffer.as_ref()[..*this.end].get(*this.start) {
+                    *this.start += 1;
+                    return Poll::Ready(Some(Ok(b)));
+                }
+            }
+            if let Some((reader, buffer)) = this.idle.take() {
+                this.future.set(Some((this.step)(reader, buffer)));
+            }
+            let future = match this.future.as_mut().as_pin_mut() {
+                Some(future) => future,
+                None => return Poll::Ready(None),
+            };
+            let (reader, buffer, res) = ready!(future.poll(cx));
+            this.future.set(None);
+            *this.idle = Some((reader, buffer));
+            *this.start = 0;
+            *this.end = 0;
+            match res {
+                Ok(0) => return Poll::Ready(None),
+                Ok(n) => *this.end = n,
+                Err(e) => return Poll::Ready(Some(Err(ReadError::Io(e)))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::pin::pin;
+
+    use embedded_io_async::{BufRead, ErrorKind, ErrorType, Read};
+    use futures::{Stream, StreamExt};
+
+    use super::{buf_read_stream, read_stream};
+    use crate::io::ReadError;
+    use crate::{Error, GCode, Parser};
+
+    /// Reads at most 3 bytes at a time, fails once at the end of its input.
+    struct Uart(&'static [u8], bool);
+    impl ErrorType for Uart {
+        type Error = ErrorKind;
+    }
+    impl Read for Uart {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+            if self.0.is_empty() && !core::mem::replace(&mut self.1, true) {
+                return Err(ErrorKind::BrokenPipe);
+            }
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    /// Counts the chunks it hands out.
+    struct Fifo(&'static [u8], usize);
+    impl ErrorType for Fifo {
+        type Error = ErrorKind;
+    }
+    impl BufRead for Fifo {
+        async fn fill_buf(&mut self) -> Result<&[u8], ErrorKind> {
+            self.1 += 1;
+            Ok(self.0)
+        }
+        fn consume(&mut self, amt: usize) {
+            self.0 = &self.0[amt..];
+        }
+    }
+
+    fn parse<S, E>(input: S) -> Vec<Result<GCode, E>>
+    where
+        S: Stream<Item = Result<u8, E>>,
+        E: From<Error>,
+    {
+        futures_executor::block_on(Parser::new(pin!(input)).into_stream().collect())
+    }
+
+    #[test]
+    fn chunks_are_parsed_as_a_single_input() {
+        let expected = [
+            Ok(GCode::Word('g', 1.into())),
+            Ok(GCode::Word('x', 2.5.into())),
+            Ok(GCode::Execute),
+        ];
+        assert_eq!(parse(read_stream(&b"G1 X2.5\n"[..], [0; 2])), expected);
+        assert_eq!(parse(buf_read_stream(&b"G1 X2.5\n"[..])), expected);
+    }
+
+    #[test]
+    fn read_errors_are_passed_through() {
+        assert_eq!(
+            parse(read_stream(Uart(b"G1 X2\n", false), [0; 8])),
+            [
+                Ok(GCode::Word('g', 1.into())),
+                Ok(GCode::Word('x', 2.into())),
+                Ok(GCode::Execute),
+                Err(ReadError::Io(ErrorKind::BrokenPipe)),
+            ]
+        );
+    }
+
+    #[test]
+    fn buffered_bytes_are_consumed_as_they_are_read() {
+        let mut fifo = Fifo(b"G1 X2.5\n", 0);
+        {
+            let mut input = pin!(buf_read_stream(&mut fifo));
+            let b = futures_executor::block_on(input.next());
+            assert_eq!(b, Some(Ok::<_, ReadError<ErrorKind>>(b'G')));
+        }
+        assert_eq!(fifo.0, b"1 X2.5\n");
+        assert_eq!(fifo.1, 1);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn parsers_may_be_built_from_readers() {
+        let expected = [
+            Ok(GCode::Word('g', 1.into())),
+            Ok(GCode::Word('x', 2.5.into())),
+            Ok(GCode::Execute),
+        ];
+        let parser = Parser::from_read(&b"G1 X2.5\n"[..], [0; 2]);
+        assert_eq!(
+            futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+            expected
+        );
+        let parser = Parser::from_buf_read(&b"G1 X2.5\n"[..]);
+        assert_eq!(
+            futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+            expected
+        );
+    }
+}
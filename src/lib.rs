@@ -36,6 +36,17 @@
 //! });
 //! ```
 //!
+//! `Parser::into_stream` turns the parser into a `futures::Stream` to use it with the stream
+//! combinators. `SyncParser` drives the same grammar from an `Iterator`, without an executor,
+//! and `SliceParser` from a slice, borrowing the comments and strings from it.
+//!
+//! Lines can also be assembled into a `Block` using `Parser::next_block`. The [`interpreter`]
+//! module then decodes them and keeps track of the modal state.
+//!
+//! A `Serializer` writes `GCode`s back as text to any `core::fmt::Write` implementation. On the
+//! host side, a `Sender` numbers and checksums the lines it writes to a firmware and sends them
+//! again on request.
+//!
 //! ## Error management
 //!
 //! On parsing error the `Parser` can no longer trust its input and enters an error recovery state.
@@ -46,15 +57,40 @@
 //! - `std` : Enabled by default. Allows for the use of dynamic allocation.
 //! - `parse-comments` : enables the parser to return `GCode::Comment(String)`; requires an allocator.
 //! - `parse-trailing-comment`: allows line to end with a `; comment`.
-//! - `parse-checksum` : Enables the use of xorsum.
+//! - `parse-checksum` : Enables the use of xorsum, or of a CRC selected with `Parser::with_checksum`.
+//!   The `reprap` module then provides the firmware side of the RepRap hosts' protocol (line
+//!   numbers, checksums and resend requests).
 //! - `parse-parameters` : Enables the use of `#` parameters ; requires an allocator.
+//!   A `ParameterTable` is provided to store and resolve them following RS274/NGC's semantics.
+//!   LinuxCNC's named parameters, local `#<name>` and global `#<_name>`, are supported as well as
+//!   `EXISTS[#<name>]` in expressions.
 //!   If `string-value` is enabled then parameters may use string index.
 //!   If `optional-value` is enabled then parameters value may be omitted but **NOT** the indices.
 //! - `parse-expressions` : Enables parsing infix expressions ; requires an allocator.
+//! - `parse-o-words` : Parses LinuxCNC's O-codes (subroutines, conditionals and loops) into
+//!   `GCode::OCode`. The `program` module executes them. Enables `parse-parameters` and
+//!   `parse-expressions`.
+//! - `parse-min-max` : Adds the `MIN[a, b]` and `MAX[a, b]` functions found in some dialects to the
+//!   expressions. Enables `parse-expressions`.
 //! - `optional-value` : Allows to omit in `RealValue` in word and parameter value positions.
 //!   Parameter indices cannot be omitted nor can be literals in expressions.
 //! - `string-value` : Allows `RealValue` to be a string. Any character preceded with `\` will be
 //!   used as is (useful for `"`, `)` or new line).
+//! - `source-position` : Tracks the byte offset, line and column of the input. `Parser::next_spanned`
+//!   then reports where each event was read from.
+//! - `decimal` : Stores real numbers as a `Decimal` (mantissa and scale) keeping the digits they were
+//!   written with instead of a floating point number. Expressions are still evaluated with `Real`s.
+//! - `embedded-io-async` : Provides the `io` module adapting `embedded_io_async::Read` and `BufRead`
+//!   readers into the parser's input, and with `std` `Parser::from_read` and
+//!   `Parser::from_buf_read`.
+//! - `futures-io` : Adds `Parser::from_async_read` reading from a buffered `futures::io::AsyncRead`;
+//!   requires `std`.
+//! - `tokio` : Adds `Parser::from_tokio_read` reading from a buffered `tokio::io::AsyncRead`;
+//!   requires `std`.
+//! - `f32` : Uses `f32` instead of `f64` as `Real`, the type of real numbers in `Literal` and during
+//!   expressions evaluation. This only meets RS274/NGC's resolution of 10^-4 up to 1024, numbers
+//!   around 10^6 may be off by 0.0625. The parameters, the `Serializer` and the `interpreter` use
+//!   `Real` as well, no `f64` arithmetics is left.
 //!
 //! ## ⚠ Warning
 //!
@@ -66,12 +102,14 @@
 //! It might be interesting to have a look at ISO 6983 and/or ISO 14649.
 //!
 //! During development fixed arithmetics was considered to be made available as an alternative to
-//! the floating point arithmetics especially for small target. After investigation, it does not
-//! seem to be a significant gain for the precision required by the gcode standard.
+//! the floating point arithmetics especially for small target. The `decimal` feature now keeps the
+//! parsed numbers exact, but expressions are still evaluated with floating points: computing them
+//! in fixed point did not seem to be a significant gain for the precision required by the gcode
+//! standard.
 //!
 //! Ideally all arithmetics triggered by a theoritically valid input should be caught and not
-//! trigger a panic. For an excessive  number of digit in a number may exceed the capacity of the
-//! variable used internally.
+//! trigger a panic. Numbers are parsed with checked arithmetics, excessive digits being reported
+//! as `Error::NumberOverflow` (see [`Limits`]).
 //!
 //! [RS274/NGC interpreter version 3]: https://www.nist.gov/publications/nist-rs274ngc-interpreter-version-3?pub_id=823374
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -90,27 +128,95 @@ extern crate alloc;
 #[cfg(all(not(feature = "std"), feature = "parse-comments"))]
 use alloc::string::String;
 
-#[macro_use]
-mod utils;
-
-mod stream;
 mod types;
+mod utils;
 
 mod parser;
 
-pub use parser::Parser;
+mod block;
+mod checksum;
+#[cfg(feature = "decimal")]
+mod decimal;
+#[cfg(feature = "source-position")]
+mod position;
+mod serializer;
+
+pub mod interpreter;
+
+#[cfg(feature = "parse-parameters")]
+mod parameters;
+
+#[cfg(any(
+    feature = "embedded-io-async",
+    feature = "futures-io",
+    feature = "tokio"
+))]
+pub mod io;
+#[cfg(feature = "parse-o-words")]
+pub mod program;
+#[cfg(feature = "parse-checksum")]
+pub mod reprap;
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+mod sender;
+
+pub use block::{Block, BlockStorage, Bounded, BoundedBuffer, Buffer};
+pub use checksum::{Checksum, Crc16, Crc8, XorSum};
+pub use parser::{Limits, Parser, ParserStream, SliceGCode, SliceParser, SyncParser};
+pub use serializer::{Format, Serializer};
+
+#[cfg(feature = "decimal")]
+pub use decimal::Decimal;
+#[cfg(feature = "source-position")]
+pub use position::{SourcePosition, Spanned};
 pub use types::Literal;
-pub use types::RealValue;
+pub use types::{Real, RealValue};
+
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+pub use block::Unbounded;
+#[cfg(any(
+    feature = "std",
+    feature = "parse-expressions",
+    feature = "parse-parameters",
+    feature = "parse-comments",
+    feature = "string-value"
+))]
+pub use sender::{Sender, SenderError};
 
 #[cfg(any(feature = "parse-expressions", feature = "parse-parameters"))]
 pub use types::expressions::Expression;
 
+#[cfg(feature = "parse-parameters")]
+pub use types::{expressions::ParameterResolver, ParameterName};
+#[cfg(feature = "parse-o-words")]
+pub use types::{OCode, OWord};
+
+#[cfg(feature = "parse-parameters")]
+pub use parameters::{
+    FixedParameterStorage, ParameterIndex, ParameterStorage, ParameterTable, PendingSetting,
+    MAX_NUMBERED_PARAMETERS,
+};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Error {
     /// Error no the gcode syntax
     UnexpectedByte(u8),
 
-    /// The parsed number excedded the expected range.
+    /// The parsed number exceeded the expected range or the digits allowed by [`Limits`].
+    ///
+    /// The error is raised on the offending digit, with `source-position` it is where the range
+    /// reported by `Parser::next_spanned` ends.
     NumberOverflow,
 
     /// Format error during number parsing. Typically a dot without digits (at least one is
@@ -122,12 +228,111 @@ pub enum Error {
     InvalidUTF8String,
 
     #[cfg(feature = "parse-checksum")]
-    /// Checksum verification failed. The error contains the computed value.
-    BadChecksum(u8),
+    /// Checksum verification failed.
+    BadChecksum {
+        /// The checksum received, following `*`.
+        received: u16,
+        /// The checksum of the bytes preceding `*`.
+        computed: u16,
+        /// The number of the line, or of the last numbered line if it has none.
+        line_number: Option<u32>,
+        /// Whether the line has an `N` word.
+        numbered: bool,
+    },
 
-    #[cfg(feature = "parse-expressions")]
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
     /// The expressions received was invalid.
-    InvalidExpression,
+    InvalidExpression(ExpressionError),
+
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+    /// The evaluation of an expression attempted a division (or a modulus) by zero.
+    DivisionByZero,
+
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+    /// The evaluation of an expression met an operand outside of its operator's domain (e.g.
+    /// `sqrt[-1]`, `ln[0]` or `acos[2]`) or an operand that is not a number.
+    DomainError,
+
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+    /// The evaluation of an expression ran out of operands.
+    StackUnderflow,
+
+    /// The block's storage cannot hold any more elements.
+    BlockOverflow,
+
+    /// Two codes of the same modal group were used in a block.
+    ModalGroupConflict(interpreter::ModalGroup),
+
+    /// A word other than `G` or `M` was used more than once in a block.
+    RepeatedWord(char),
+
+    /// The `G` or `M` code, or the keyword of an O-code, is not supported.
+    UnknownCode(char),
+
+    /// A word required by the block is missing.
+    MissingWord(char),
+
+    /// The value of the word is not valid for its letter.
+    InvalidValue(char),
+
+    /// The end point of the arc is not on the circle, or out of reach of the radius.
+    InvalidArc,
+
+    #[cfg(feature = "parse-parameters")]
+    /// The parameter index is not an integer in the valid range (1 to 5399).
+    InvalidParameterIndex,
+
+    #[cfg(feature = "parse-parameters")]
+    /// A parameter's or a subroutine's name is longer than [`Limits::name_length`].
+    NameTooLong,
+
+    #[cfg(feature = "parse-parameters")]
+    /// The parameter storage cannot hold any more values.
+    ParameterStorageFull,
+
+    #[cfg(feature = "optional-value")]
+    /// A value was required but has been omitted.
+    MissingValue,
+
+    #[cfg(feature = "parse-o-words")]
+    /// The called subroutine is not defined in the program.
+    UnknownSubroutine,
+
+    #[cfg(feature = "parse-o-words")]
+    /// The subroutine calls are nested too deep.
+    CallStackOverflow,
+
+    #[cfg(feature = "parse-o-words")]
+    /// More than 30 arguments were passed to a subroutine.
+    TooManyArguments,
+
+    #[cfg(feature = "parse-o-words")]
+    /// An O-code does not match the subroutine or the structure it is in, or is not in any (e.g.
+    /// `return` in the main program, a `sub` in a subroutine or `endif` without `if`).
+    MisplacedOWord,
+
+    #[cfg(feature = "parse-o-words")]
+    /// A loop ran more iterations than allowed.
+    TooManyIterations,
+}
+
+#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+/// Why an expression is invalid.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ExpressionError {
+    /// A bracket is closed without having been opened, or is never closed.
+    UnbalancedBrackets,
+    /// An operator or a bracket is missing its operands, or an operator or a function is unknown or
+    /// used where it does not apply.
+    MisplacedOperator,
+    /// Operands are left once every operator has been applied.
+    ExtraOperands,
+    #[cfg(feature = "parse-expressions")]
+    /// The brackets are nested deeper than [`Limits::expression_depth`].
+    TooDeep,
+    #[cfg(feature = "parse-expressions")]
+    /// The expression holds more items than [`Limits::expression_length`].
+    TooLong,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -140,5 +345,8 @@ pub enum GCode {
     #[cfg(feature = "parse-parameters")]
     /// When `optional-value` is enabled, the index cannot be `RealValue::None`.
     ParameterSet(RealValue, RealValue),
+    #[cfg(feature = "parse-o-words")]
+    /// A LinuxCNC O-code, e.g. `O100 call [1] [2]`.
+    OCode(OWord, OCode),
     Execute,
 }
@@ -0,0 +1,519 @@
+//! Parameter storage following RS274/NGC's numbered parameters semantics.
+//!
+//! Parameters are numbered from 1 to 5399 (inclusive). Reading a parameter that was never set
+//! returns 0. When `string-value` is enabled, parameters may also be indexed by a string.
+//!
+//! LinuxCNC's named parameters, `#<name>` and `#<_name>`, are respectively indexed as
+//! [`ParameterIndex::Local`] and [`ParameterIndex::Global`]. Local parameters are held by the
+//! table rather than by its storage, each subroutine having its own.
+//!
+//! All the parameter settings on a line take effect once the whole line has been read. This means
+//! that `#1=2 #2=#1` sets `#2` to the value `#1` had *before* that line. Until then they are held
+//! in a buffer selected by the [`ParameterStorage`].
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+
+use crate::{
+    BoundedBuffer, Buffer, Error, GCode, Literal, ParameterName, ParameterResolver, Real, RealValue,
+};
+
+#[cfg(not(feature = "f32"))]
+use crate::utils::math;
+#[cfg(feature = "f32")]
+use crate::utils::math::single as math;
+
+/// Highest (excluded) numbered parameter index.
+pub const MAX_NUMBERED_PARAMETERS: u16 = 5400;
+
+/// Number of parameters, from `#1`, receiving the arguments of a subroutine.
+#[cfg(feature = "parse-o-words")]
+const ARGUMENTS: u16 = 30;
+
+/// A validated parameter index.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub enum ParameterIndex {
+    /// A parameter in the range `1..5400`.
+    Numbered(u16),
+    #[cfg(feature = "string-value")]
+    /// A string indexed parameter.
+    Named(String),
+    /// A named parameter local to the subroutine setting it, `#<name>`.
+    Local(String),
+    /// A named parameter visible from everywhere, `#<_name>`. The name excludes the leading `_`.
+    Global(String),
+}
+
+impl TryFrom<Literal> for ParameterIndex {
+    type Error = Error;
+
+    /// Real numbers are rounded to the nearest integer but must be within 10^-4 of it.
+    #[allow(clippy::useless_conversion)] // not a `Decimal` by default
+    fn try_from(from: Literal) -> Result<Self, Error> {
+        match from {
+            Literal::RealNumber(rn) => {
+                let rn = Real::from(rn);
+                let index = (rn + 0.5) as i32;
+                if math::abs(rn - index as Real) > 1e-4
+                    || !(1..i32::from(MAX_NUMBERED_PARAMETERS)).contains(&index)
+                {
+                    Err(Error::InvalidParameterIndex)
+                } else {
+                    Ok(Self::Numbered(index as u16))
+                }
+            }
+            #[cfg(feature = "string-value")]
+            Literal::String(s) => Ok(Self::Named(s)),
+            Literal::Name(name) => Ok(name.into()),
+        }
+    }
+}
+impl From<ParameterName> for ParameterIndex {
+    fn from(from: ParameterName) -> Self {
+        match from {
+            ParameterName::Local(name) => Self::Local(name),
+            ParameterName::Global(name) => Self::Global(name),
+        }
+    }
+}
+impl From<u16> for ParameterIndex {
+    fn from(from: u16) -> Self {
+        Self::Numbered(from)
+    }
+}
+
+/// A parameter setting waiting for the end of its line, the slot is `None` when it holds none.
+pub type PendingSetting = Option<(ParameterIndex, Option<Literal>)>;
+
+/// Backend used by a [`ParameterTable`] to store its values.
+pub trait ParameterStorage {
+    /// Holds the settings of the line being read. When it is full, the settings are reported as
+    /// [`Error::ParameterStorageFull`].
+    type Pending: Buffer<PendingSetting> + core::fmt::Debug;
+
+    /// Returns the value of a parameter, `None` if it is not set.
+    fn get(&self, index: &ParameterIndex) -> Option<&Literal>;
+    /// Sets (or unsets when `value` is `None`) a parameter.
+    fn set(&mut self, index: ParameterIndex, value: Option<Literal>) -> Result<(), Error>;
+}
+
+impl ParameterStorage for BTreeMap<ParameterIndex, Literal> {
+    type Pending = Vec<PendingSetting>;
+
+    fn get(&self, index: &ParameterIndex) -> Option<&Literal> {
+        BTreeMap::get(self, index)
+    }
+    fn set(&mut self, index: ParameterIndex, value: Option<Literal>) -> Result<(), Error> {
+        match value {
+            Some(value) => {
+                self.insert(index, value);
+            }
+            None => {
+                self.remove(&index);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A storage holding at most `N` parameters at once, and at most `N` settings on a line.
+///
+/// Unset parameters do not take any room. It does not require any allocation.
+#[derive(Debug, Clone)]
+pub struct FixedParameterStorage<const N: usize> {
+    entries: [Option<(ParameterIndex, Literal)>; N],
+}
+impl<const N: usize> Default for FixedParameterStorage<N> {
+    fn default() -> Self {
+        Self {
+            entries: core::array::from_fn(|_| None),
+        }
+    }
+}
+impl<const N: usize> ParameterStorage for FixedParameterStorage<N> {
+    type Pending = BoundedBuffer<PendingSetting, N>;
+
+    fn get(&self, index: &ParameterIndex) -> Option<&Literal> {
+        self.entries
+            .iter()
+            .flatten()
+            .find_map(|(idx, value)| (idx == index).then_some(value))
+    }
+    fn set(&mut self, index: ParameterIndex, value: Option<Literal>) -> Result<(), Error> {
+        let slot = self
+            .entries
+            .iter()
+            .position(|entry| matches!(entry, Some((idx, _)) if *idx == index));
+        match (slot, value) {
+            (Some(slot), Some(value)) => self.entries[slot] = Some((index, value)),
+            (Some(slot), None) => self.entries[slot] = None,
+            (None, Some(value)) => {
+                *self
+                    .entries
+                    .iter_mut()
+                    .find(|entry| entry.is_none())
+                    .ok_or(Error::ParameterStorageFull)? = Some((index, value))
+            }
+            (None, None) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Parameter table applying `GCode::ParameterSet` with the RS274/NGC semantics.
+///
+/// ```
+/// use async_gcode::{GCode, Literal, ParameterIndex, ParameterTable};
+///
+/// let mut table = ParameterTable::new();
+/// table.apply(&GCode::ParameterSet(1.into(), 2.into())).unwrap();
+/// assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(0));
+/// table.apply(&GCode::Execute).unwrap();
+/// assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ParameterTable<S: ParameterStorage = BTreeMap<ParameterIndex, Literal>> {
+    storage: S,
+    pending: S::Pending,
+    /// The local parameters of the main program followed by those of each nested subroutine.
+    locals: Vec<(String, Literal)>,
+    #[cfg(feature = "parse-o-words")]
+    frames: Vec<Frame>,
+}
+
+/// What a subroutine call hides from the caller.
+#[cfg(feature = "parse-o-words")]
+#[derive(Debug, Clone)]
+struct Frame {
+    /// The values of the parameters receiving the arguments.
+    arguments: Vec<Option<Literal>>,
+    /// The number of local parameters of the callers.
+    locals: usize,
+}
+
+impl ParameterTable {
+    /// Builds an empty table backed by a `BTreeMap`.
+    pub fn new() -> Self {
+        Self::with_storage(BTreeMap::new())
+    }
+}
+impl Default for ParameterTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: ParameterStorage> ParameterTable<S> {
+    /// Builds a table backed by `storage`, which may already hold values.
+    pub fn with_storage(storage: S) -> Self {
+        Self {
+            storage,
+            pending: S::Pending::default(),
+            locals: Vec::new(),
+            #[cfg(feature = "parse-o-words")]
+            frames: Vec::new(),
+        }
+    }
+
+    /// The local parameters of the current subroutine.
+    fn scope(&self) -> &[(String, Literal)] {
+        #[cfg(feature = "parse-o-words")]
+        let start = self.frames.last().map_or(0, |frame| frame.locals);
+        #[cfg(not(feature = "parse-o-words"))]
+        let start = 0;
+        &self.locals[start..]
+    }
+
+    /// Returns the current value of a parameter. Unset parameters are worth 0.
+    pub fn get(&self, index: &ParameterIndex) -> Literal {
+        self.lookup(index).cloned().unwrap_or(Literal::from(0))
+    }
+
+    /// Whether a parameter is defined. Numbered parameters always are, the named ones once they
+    /// have been set.
+    pub fn is_defined(&self, index: &ParameterIndex) -> bool {
+        matches!(index, ParameterIndex::Numbered(_)) || self.lookup(index).is_some()
+    }
+
+    fn lookup(&self, index: &ParameterIndex) -> Option<&Literal> {
+        match index {
+            ParameterIndex::Local(name) => self
+                .scope()
+                .iter()
+                .find_map(|(local, value)| (local == name).then_some(value)),
+            index => self.storage.get(index),
+        }
+    }
+
+    /// Sets a parameter immediately, regardless of any pending settings.
+    ///
+    /// This is typically used by the machine to publish its state (e.g. the probed position).
+    pub fn set(&mut self, index: ParameterIndex, value: Literal) -> Result<(), Error> {
+        self.store(index, Some(value))
+    }
+
+    fn store(&mut self, index: ParameterIndex, value: Option<Literal>) -> Result<(), Error> {
+        let name = match index {
+            ParameterIndex::Local(name) => name,
+            index => return self.storage.set(index, value),
+        };
+        let start = self.locals.len() - self.scope().len();
+        let slot = self.locals[start..]
+            .iter()
+            .position(|(local, _)| *local == name)
+            .map(|slot| start + slot);
+        match (slot, value) {
+            (Some(slot), Some(value)) => self.locals[slot].1 = value,
+            (Some(slot), None) => {
+                self.locals.remove(slot);
+            }
+            (None, Some(value)) => self.locals.push((name, value)),
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    /// Enters a subroutine: `#1` to `#30` take the values of `arguments`, or are unset beyond
+    /// them, and the local parameters of the caller are hidden.
+    ///
+    /// They are all restored by [`ParameterTable::leave_subroutine`].
+    #[cfg(feature = "parse-o-words")]
+    pub fn enter_subroutine(&mut self, arguments: &[Literal]) -> Result<(), Error> {
+        if arguments.len() > usize::from(ARGUMENTS) {
+            return Err(Error::TooManyArguments);
+        }
+        let saved = (1..=ARGUMENTS)
+            .map(|index| self.storage.get(&index.into()).cloned())
+            .collect();
+        self.frames.push(Frame {
+            arguments: saved,
+            locals: self.locals.len(),
+        });
+        let mut res = Ok(());
+        for (index, value) in (1..=ARGUMENTS).zip(
+            arguments
+                .iter()
+                .cloned()
+                .map(Some)
+                .chain(core::iter::repeat(None)),
+        ) {
+            res = res.and(self.storage.set(index.into(), value));
+        }
+        res
+    }
+
+    /// Leaves the current subroutine, restoring the parameters of its caller.
+    #[cfg(feature = "parse-o-words")]
+    pub fn leave_subroutine(&mut self) -> Result<(), Error> {
+        let frame = self.frames.pop().ok_or(Error::MisplacedOWord)?;
+        self.locals.truncate(frame.locals);
+        let mut res = Ok(());
+        for (index, value) in (1..=ARGUMENTS).zip(frame.arguments) {
+            res = res.and(self.storage.set(index.into(), value));
+        }
+        res
+    }
+
+    /// Evaluates a value against the current parameters.
+    pub fn evaluate(&self, value: &RealValue) -> Result<Literal, Error> {
+        match value {
+            RealValue::Literal(literal) => Ok(literal.clone()),
+            RealValue::Expression(expr) => expr.evaluate(&mut &*self),
+            #[cfg(feature = "optional-value")]
+            RealValue::None => Err(Error::MissingValue),
+        }
+    }
+
+    /// Feeds a `GCode` to the table.
+    ///
+    /// `GCode::ParameterSet` are evaluated immediately but only take effect on the next
+    /// `GCode::Execute`. An omitted value (`optional-value`) unsets the parameter.
+    pub fn apply(&mut self, gcode: &GCode) -> Result<(), Error> {
+        match gcode {
+            GCode::ParameterSet(index, value) => {
+                let index = ParameterIndex::try_from(self.evaluate(index)?)?;
+                let value = match value {
+                    #[cfg(feature = "optional-value")]
+                    RealValue::None => None,
+                    value => Some(self.evaluate(value)?),
+                };
+                self.pending
+                    .try_push(Some((index, value)))
+                    .map_err(|_| Error::ParameterStorageFull)
+            }
+            GCode::Execute => self.commit(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies all pending settings in the order they were received.
+    ///
+    /// This is called by [`ParameterTable::apply`] on `GCode::Execute`.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let mut res = Ok(());
+        let pending = core::mem::take(&mut self.pending);
+        for (index, value) in pending.iter().flatten() {
+            if let Err(e) = self.store(index.clone(), value.clone()) {
+                res = res.and(Err(e));
+            }
+        }
+        res
+    }
+
+    /// Drops the pending settings. Useful when a line is discarded after a parsing error.
+    pub fn discard_pending(&mut self) {
+        self.pending.clear();
+    }
+}
+
+/// Resolves the parameters of an expression, reporting invalid indices.
+impl<S: ParameterStorage> ParameterResolver for &ParameterTable<S> {
+    fn resolve(&mut self, index: Literal) -> Result<Option<Literal>, Error> {
+        let index = ParameterIndex::try_from(index)?;
+        Ok(self.is_defined(&index).then(|| self.get(&index)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FixedParameterStorage, ParameterIndex, ParameterTable};
+    use crate::{Error, GCode, Literal, Parser};
+    use futures::stream::{self, StreamExt};
+
+    fn run<S: super::ParameterStorage>(table: &mut ParameterTable<S>, input: &str) -> Vec<Literal> {
+        let mut parser = Parser::new(stream::iter(input.bytes()).map(Result::<_, Error>::Ok));
+        let mut words = Vec::new();
+        futures_executor::block_on(async {
+            while let Some(gcode) = parser.next().await {
+                let gcode = gcode.unwrap();
+                if let GCode::Word(_, value) = &gcode {
+                    words.push(table.evaluate(value).unwrap());
+                }
+                table.apply(&gcode).unwrap();
+            }
+        });
+        words
+    }
+
+    #[test]
+    fn unset_parameters_are_worth_zero() {
+        let table = ParameterTable::new();
+        assert_eq!(table.get(&ParameterIndex::from(5)), Literal::from(0));
+    }
+
+    #[test]
+    fn settings_take_effect_after_all_the_reads_of_the_line() {
+        let mut table = ParameterTable::new();
+        assert_eq!(
+            run(&mut table, "#1=5 #2=#1 G#1\n#3=#1 G#1\n"),
+            [Literal::from(0), Literal::from(5)]
+        );
+        assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(5));
+        assert_eq!(table.get(&ParameterIndex::from(2)), Literal::from(0));
+        assert_eq!(table.get(&ParameterIndex::from(3)), Literal::from(5));
+    }
+
+    #[test]
+    fn the_last_setting_of_a_line_wins() {
+        let mut table = ParameterTable::new();
+        run(&mut table, "#1=5 #1=6\n");
+        assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(6));
+    }
+
+    #[test]
+    fn indices_must_be_integers_within_range() {
+        for (index, expected) in [
+            (0., Err(Error::InvalidParameterIndex)),
+            (5400., Err(Error::InvalidParameterIndex)),
+            (1.5, Err(Error::InvalidParameterIndex)),
+            (1., Ok(ParameterIndex::Numbered(1))),
+            (5399.00001, Ok(ParameterIndex::Numbered(5399))),
+        ] {
+            assert_eq!(ParameterIndex::try_from(Literal::from(index)), expected);
+        }
+
+        let mut table = ParameterTable::new();
+        assert_eq!(
+            table.apply(&GCode::ParameterSet(0.into(), 1.into())),
+            Err(Error::InvalidParameterIndex)
+        );
+    }
+
+    #[test]
+    fn fixed_storage_reports_when_full() {
+        let mut table = ParameterTable::with_storage(FixedParameterStorage::<2>::default());
+        run(&mut table, "#1=1 #2=2\n#1=3\n");
+        assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(3));
+
+        table
+            .apply(&GCode::ParameterSet(3.into(), 3.into()))
+            .unwrap();
+        assert_eq!(
+            table.apply(&GCode::Execute),
+            Err(Error::ParameterStorageFull)
+        );
+
+        // the settings of a line are bounded as well.
+        for index in 1..=2 {
+            table
+                .apply(&GCode::ParameterSet(index.into(), 1.into()))
+                .unwrap();
+        }
+        assert_eq!(
+            table.apply(&GCode::ParameterSet(1.into(), 1.into())),
+            Err(Error::ParameterStorageFull)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "optional-value")]
+    fn omitted_values_unset_the_parameter() {
+        let mut table = ParameterTable::with_storage(FixedParameterStorage::<1>::default());
+        run(&mut table, "#1=1\n#1=\n#2=2\n");
+        assert_eq!(table.get(&ParameterIndex::from(1)), Literal::from(0));
+        assert_eq!(table.get(&ParameterIndex::from(2)), Literal::from(2));
+    }
+
+    #[test]
+    #[cfg(feature = "parse-expressions")]
+    fn named_parameters_exist_once_set() {
+        let mut table = ParameterTable::new();
+        assert_eq!(
+            run(
+                &mut table,
+                "#<a>=1\nG[exists[#<a>] + exists[#<_a>]] G#<_a> Gexists[#5399]\n"
+            ),
+            [1, 0, 1].map(Literal::from)
+        );
+        assert!(table.is_defined(&ParameterIndex::Local("a".into())));
+        assert!(!table.is_defined(&ParameterIndex::Global("a".into())));
+    }
+
+    #[test]
+    fn named_parameters_keep_their_scope() {
+        let mut table = ParameterTable::new();
+        assert_eq!(
+            run(&mut table, "#<a>=1 #<_a>=2\nG#<A> G#<_A>\n"),
+            [Literal::from(1), Literal::from(2)]
+        );
+        assert_eq!(
+            table.get(&ParameterIndex::Global("a".into())),
+            Literal::from(2)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "string-value")]
+    fn parameters_may_be_indexed_by_strings() {
+        let mut table = ParameterTable::new();
+        assert_eq!(
+            run(&mut table, "#\"feed\"=1500\nG#\"feed\"\n"),
+            [Literal::from(1500)]
+        );
+    }
+}
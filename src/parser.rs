@@ -2,7 +2,7 @@
 //! ```ebnf
 //! line       ::= '/'? ( [Nn] [0-9]+ )?
 //!                ( [a-zA-Z] real_value? | '#' real_value '=' real_value? | '(' [^)] ')' )*
-//!                ( '*' [0-9]+ /* 0 to 255 */ )?
+//!                ( '*' [0-9]+ /* 0 to 255, or 65535 with a 16 bits checksum */ )?
 //!                ( ';' [^\n]* )? '\n'
 //! real_value ::= '#'* ( real_number
 //!                     | '"' [^"] '"'
@@ -12,380 +12,267 @@
 //! real_number ::= ( '+' | '-' )? ( [0-9]+ ( '.' [0-9]* )? | '.' [0-9]+ )
 //! ```
 //!
-#[cfg(all(not(feature = "std"), feature = "parse-comments"))]
-use alloc::string::String;
-#[cfg(all(not(feature = "std"), any(feature = "parse-comments")))]
-use alloc::vec::Vec;
+/// Unwraps the result of a completed [`Step`](grammar::Step) along with whether its byte was
+/// consumed, returning early from the enclosing `feed` otherwise.
+macro_rules! try_step {
+    ($step:expr) => {
+        match $step {
+            Step::More => return Step::More,
+            Step::Take(Ok(v)) => (v, true),
+            Step::Leave(Ok(v)) => (v, false),
+            Step::Take(Err(e)) => return Step::Take(Err(e)),
+            Step::Leave(Err(e)) => return Step::Leave(Err(e)),
+        }
+    };
+}
 
+mod grammar;
+mod limits;
+mod slice;
+mod stream;
+mod sync;
 mod values;
 
 #[cfg(feature = "parse-expressions")]
 mod expressions;
+#[cfg(feature = "parse-o-words")]
+mod o_words;
+
+pub use limits::Limits;
+pub use slice::{SliceGCode, SliceParser};
+pub use stream::ParserStream;
+pub use sync::SyncParser;
 
 #[cfg(test)]
 mod test;
 
-use futures::{Stream, StreamExt};
-
-use crate::{
-    stream::{MyTryStreamExt, PushBackable},
-    types::{Comment, ParseResult},
-    utils::skip_whitespaces,
-    Error, GCode,
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
 };
 
-use values::parse_number;
-
-#[cfg(not(feature = "parse-expressions"))]
-use values::parse_real_value;
+use futures::{ready, Stream};
 
-#[cfg(feature = "parse-expressions")]
-use expressions::parse_real_value;
+use crate::{
+    block::{Block, BlockStorage, Buffer},
+    Checksum, Error, GCode, XorSum,
+};
 
-#[derive(PartialEq, Debug, Clone, Copy)]
-enum AsyncParserState {
-    Start(bool),
-    LineNumberOrSegment,
-    Segment,
-    ErrorRecovery,
-    #[cfg(all(feature = "parse-trailing-comment", feature = "parse-checksum"))]
-    EoLOrTrailingComment,
-    #[cfg(any(feature = "parse-trailing-comment", feature = "parse-checksum"))]
-    EndOfLine,
-}
+use grammar::{Grammar, Step};
 
-#[cfg(all(feature = "parse-trailing-comment", not(feature = "parse-comments")))]
-async fn parse_eol_comment<S, E>(input: &mut S) -> Option<Result<Comment, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    loop {
-        let b = match input.next().await? {
-            Ok(o) => o,
-            Err(e) => return Some(Err(e)),
-        };
-        match b {
-            b'\r' | b'\n' => {
-                input.push_back(b);
-                break Some(Ok(()));
-            }
-            _ => {}
-        }
-    }
-}
+#[cfg(feature = "source-position")]
+use crate::position::{SourcePosition, Spanned};
 
-#[cfg(all(feature = "parse-trailing-comment", feature = "parse-comments"))]
-async fn parse_eol_comment<S, E>(input: &mut S) -> Option<ParseResult<Comment, E>>
+pub struct Parser<S, E, C = XorSum>
 where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
+    S: Stream<Item = Result<u8, E>> + Unpin,
 {
-    let mut v = Vec::new();
-    loop {
-        let b = try_result!(input.next());
-        match b {
-            b'\r' | b'\n' => {
-                input.push_back(b);
-                break Some(match String::from_utf8(v) {
-                    Ok(s) => ParseResult::Ok(s),
-                    Err(_) => Error::InvalidUTF8String.into(),
-                });
-            }
-            b => v.push(b),
-        }
-    }
+    input: S,
+    grammar: Grammar<C>,
+    /// The byte left by the last event, to be fed again before reading on.
+    pending: Option<u8>,
+    /// Whether the input has ended, it is not polled anymore.
+    ended: bool,
+    /// The position of the first byte of the event being read.
+    #[cfg(feature = "source-position")]
+    span_start: Option<SourcePosition>,
+    #[cfg(feature = "source-position")]
+    span: (SourcePosition, SourcePosition),
 }
 
-#[cfg(not(feature = "parse-comments"))]
-async fn parse_inline_comment<S, E>(input: &mut S) -> Option<ParseResult<Comment, E>>
+impl<S, E> Parser<S, E>
 where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
+    S: Stream<Item = Result<u8, E>> + Unpin,
+    E: From<Error>,
 {
-    loop {
-        match try_result!(input.next()) {
-            b'\\' => {
-                try_result!(input.next());
-            }
-            b'(' => break Some(Error::UnexpectedByte(b'(').into()),
-            b')' => break Some(ParseResult::Ok(())),
-            _ => {}
-        }
+    pub fn new(input: S) -> Self {
+        Self::with_limits(input, Limits::default())
     }
-}
 
-#[cfg(feature = "parse-comments")]
-async fn parse_inline_comment<S, E>(input: &mut S) -> Option<ParseResult<Comment, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    let mut v = Vec::new();
-    loop {
-        let b = try_result!(input.next());
-        match b {
-            b'\\' => {
-                v.push(try_result!(input.next()));
-            }
-            b'(' => break Some(Error::UnexpectedByte(b'(').into()),
-            b')' => {
-                break Some(match String::from_utf8(v) {
-                    Ok(s) => ParseResult::Ok(s),
-                    Err(_) => Error::InvalidUTF8String.into(),
-                })
-            }
-            b => v.push(b),
-        }
+    /// Builds a parser bounding the numbers it reads with `limits`.
+    pub fn with_limits(input: S, limits: Limits) -> Self {
+        Self::build(input, limits, XorSum::default())
     }
 }
 
-// use a different struct to compute checksum
-#[cfg(not(feature = "parse-checksum"))]
-use crate::stream::pushback::PushBack;
-#[cfg(feature = "parse-checksum")]
-type PushBack<T> = crate::stream::xorsum_pushback::XorSumPushBack<T>;
-
-async fn parse_eol<S, E>(
-    state: &mut AsyncParserState,
-    input: &mut PushBack<S>,
-) -> Option<ParseResult<GCode, E>>
+impl<S, E, C> Parser<S, E, C>
 where
     S: Stream<Item = Result<u8, E>> + Unpin,
+    E: From<Error>,
+    C: Checksum,
 {
-    Some(loop {
-        let b = try_result!(input.next());
-        match b {
-            b'\r' | b'\n' => {
-                *state = AsyncParserState::Start(true);
-                #[cfg(feature = "parse-checksum")]
-                {
-                    input.reset_sum(0);
-                }
-                break ParseResult::Ok(GCode::Execute);
-            }
-            b' ' => {}
-            b => break Error::UnexpectedByte(b).into(),
-        }
-    })
-}
+    /// Builds a parser verifying the lines' checksums with `C` rather than [`XorSum`].
+    ///
+    /// ```
+    /// use async_gcode::{Crc8, Error, GCode, Limits, Parser};
+    /// use futures::stream;
+    ///
+    /// let input = stream::iter("G0*160\n".bytes().map(Result::<_, Error>::Ok));
+    /// let mut parser = Parser::with_checksum(input, Limits::default(), Crc8::default());
+    /// futures_executor::block_on(async {
+    ///     assert_eq!(parser.next().await, Some(Ok(GCode::Word('g', 0.into()))));
+    ///     assert_eq!(parser.next().await, Some(Ok(GCode::Execute)));
+    /// });
+    /// ```
+    #[cfg(feature = "parse-checksum")]
+    pub fn with_checksum(input: S, limits: Limits, checksum: C) -> Self {
+        Self::build(input, limits, checksum)
+    }
 
-macro_rules! try_await {
-    ($input:expr) => {
-        match $input.await? {
-            ParseResult::Ok(ok) => ok,
-            ParseResult::Parsing(err) => break Err(err.into()),
-            ParseResult::Input(err) => break Err(err.into()),
+    fn build(input: S, limits: Limits, checksum: C) -> Self {
+        Self {
+            input,
+            grammar: Grammar::new(limits, checksum),
+            pending: None,
+            ended: false,
+            #[cfg(feature = "source-position")]
+            span_start: None,
+            #[cfg(feature = "source-position")]
+            span: Default::default(),
         }
-    };
-}
+    }
 
-macro_rules! try_await_result {
-    ($input:expr) => {
-        match $input.await? {
-            Ok(ok) => ok,
-            Err(err) => break Err(err.into()),
-        }
-    };
-}
+    /// The position of the next byte to be parsed.
+    #[cfg(feature = "source-position")]
+    pub fn position(&self) -> SourcePosition {
+        self.grammar.position()
+    }
 
-pub struct Parser<S, E>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin,
-{
-    input: PushBack<S>,
-    state: AsyncParserState,
-}
+    /// Whether the line last read starts with a line number or carries a checksum, even when they
+    /// could not be read.
+    #[cfg(feature = "parse-checksum")]
+    pub(crate) fn framed(&self) -> bool {
+        self.grammar.framed()
+    }
 
-impl<S, E> Parser<S, E>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin,
-    E: From<Error>,
-{
-    pub fn new(input: S) -> Self {
-        Self {
-            #[cfg(feature = "parse-checksum")]
-            input: input.xor_summed_push_backable(0),
-            #[cfg(not(feature = "parse-checksum"))]
-            input: input.push_backable(),
-            state: AsyncParserState::Start(true),
-        }
+    /// Same as [`Parser::next`] but also reports the range of the input the result was read from.
+    ///
+    /// The spaces preceding the event, and the bytes skipped while recovering from an error, are
+    /// not part of the range. Spaces following a number are.
+    #[cfg(feature = "source-position")]
+    pub async fn next_spanned(&mut self) -> Option<Spanned<Result<GCode, E>>> {
+        let value = self.next().await?;
+        let (start, end) = self.span;
+        Some(Spanned { start, end, value })
     }
+
     pub async fn next(&mut self) -> Option<Result<GCode, E>> {
-        let res = loop {
-            let b = match self.input.next().await? {
-                Ok(b) => b,
-                Err(err) => return Some(Err(err)),
-            };
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
 
-            // println!("{:?}: {:?}", self.state, char::from(b));
-            match self.state {
-                AsyncParserState::Start(ref mut first_byte) => match b {
-                    b'\n' => {
-                        self.input.push_back(b);
-                        break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
-                    }
-                    b'/' if *first_byte => {
-                        self.state = AsyncParserState::LineNumberOrSegment;
-                        break Ok(GCode::BlockDelete);
+    /// Polls for the next event, feeding the grammar with the bytes the input has ready.
+    ///
+    /// This is what [`Parser::next`] awaits, the parser keeps its state between two polls.
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<GCode, E>>> {
+        loop {
+            let b = match self.pending.take() {
+                Some(b) => b,
+                None if self.ended => return Poll::Ready(None),
+                None => match ready!(Pin::new(&mut self.input).poll_next(cx)) {
+                    Some(Ok(b)) => b,
+                    Some(Err(err)) => {
+                        self.grammar.input_failed();
+                        return self.produce(Err(err));
                     }
-                    b' ' => {
-                        *first_byte = false;
-                    }
-                    _ => {
-                        self.input.push_back(b);
-                        self.state = AsyncParserState::LineNumberOrSegment
-                    }
-                },
-                AsyncParserState::LineNumberOrSegment => match b.to_ascii_lowercase() {
-                    b'n' => {
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        let (n, ord) = try_await_result!(parse_number(&mut self.input));
-                        break if ord == 1 {
-                            let b = try_await_result!(self.input.next());
-                            Err(Error::UnexpectedByte(b).into())
-                        } else if ord > 10000 {
-                            Err(Error::NumberOverflow.into())
-                        } else {
-                            self.state = AsyncParserState::Segment;
-                            Ok(GCode::LineNumber(n))
-                        };
-                    }
-                    _ => {
-                        self.input.push_back(b);
-                        self.state = AsyncParserState::Segment;
+                    None => {
+                        self.ended = true;
+                        return Poll::Ready(None);
                     }
                 },
-                AsyncParserState::Segment => match b.to_ascii_lowercase() {
-                    b' ' => {}
-                    letter @ b'a'..=b'z' => {
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        let rv = try_await!(parse_real_value(&mut self.input));
-                        // println!("word({:?}, {:?})", letter as char, rv);
-                        break Ok(GCode::Word(letter.into(), rv));
-                    }
-                    b'\r' | b'\n' => {
-                        self.input.push_back(b);
-                        break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
-                    }
-                    // param support feature
-                    #[cfg(feature = "parse-parameters")]
-                    b'#' => {
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        #[allow(clippy::match_single_binding)]
-                        let param_id = match try_await!(parse_real_value(&mut self.input)) {
-                            #[cfg(feature = "optional-value")]
-                            crate::RealValue::None => {
-                                let b = try_await_result!(self.input.next());
-                                break Err(Error::UnexpectedByte(b).into());
-                            }
-                            id => id,
-                        };
-                        // println!("param_id: {:?}", param_id);
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        let b = try_await_result!(self.input.next());
-                        if b'=' != b {
-                            break Err(Error::UnexpectedByte(b).into());
-                        }
+            };
+            #[cfg(feature = "source-position")]
+            if self.span_start.is_none() && !self.grammar.skips(b) {
+                self.span_start = Some(self.grammar.position());
+            }
 
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        let value = try_await!(parse_real_value(&mut self.input));
-                        // println!("param_id: {:?}", value);
+            match self.grammar.feed(b) {
+                Step::More => {}
+                Step::Take(res) => return self.produce(res.map_err(E::from)),
+                Step::Leave(res) => {
+                    self.pending = Some(b);
+                    return self.produce(res.map_err(E::from));
+                }
+            }
+        }
+    }
 
-                        break Ok(GCode::ParameterSet(param_id, value));
-                    }
-                    // checksum support feature
-                    #[cfg(feature = "parse-checksum")]
-                    b'*' => {
-                        let sum = self.input.sum() ^ b'*';
-                        try_await_result!(skip_whitespaces(&mut self.input));
-                        let (n, _) = try_await_result!(parse_number(&mut self.input));
-                        // println!("{} {}", sum, n);
-                        if n >= 256 {
-                            break Err(Error::NumberOverflow.into());
-                        } else if (n as u8) != sum {
-                            break Err(Error::BadChecksum(sum).into());
-                        } else {
-                            try_await_result!(skip_whitespaces(&mut self.input));
-                            #[cfg(not(feature = "parse-trailing-comment"))]
-                            {
-                                self.state = AsyncParserState::EndOfLine;
-                            }
-                            #[cfg(feature = "parse-trailing-comment")]
-                            {
-                                self.state = AsyncParserState::EoLOrTrailingComment;
-                            }
-                        }
-                    }
-                    // comment support features
-                    #[cfg(not(feature = "parse-comments"))]
-                    b'(' => {
-                        try_await!(parse_inline_comment(&mut self.input));
-                    }
-                    #[cfg(feature = "parse-comments")]
-                    b'(' => {
-                        break Ok(GCode::Comment(try_await!(parse_inline_comment(
-                            &mut self.input
-                        ))));
-                    }
-                    #[cfg(all(
-                        feature = "parse-trailing-comment",
-                        not(feature = "parse-comments")
-                    ))]
-                    b';' => {
-                        try_await_result!(parse_eol_comment(&mut self.input));
-                        self.state = AsyncParserState::EndOfLine;
-                    }
-                    #[cfg(all(feature = "parse-trailing-comment", feature = "parse-comments"))]
-                    b';' => {
-                        let s = try_await!(parse_eol_comment(&mut self.input));
-                        self.state = AsyncParserState::EndOfLine;
-                        break Ok(GCode::Comment(s));
-                    }
-                    _ => break Err(Error::UnexpectedByte(b).into()),
-                },
-                #[cfg(all(
-                    feature = "parse-trailing-comment",
-                    not(feature = "parse-comments"),
-                    feature = "parse-checksum"
-                ))]
-                AsyncParserState::EoLOrTrailingComment => match b {
-                    b';' => try_await_result!(parse_eol_comment(&mut self.input)),
-                    _ => {
-                        self.input.push_back(b);
-                        break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
-                    }
-                },
-                #[cfg(all(
-                    feature = "parse-trailing-comment",
-                    feature = "parse-comments",
-                    feature = "parse-checksum"
-                ))]
-                AsyncParserState::EoLOrTrailingComment => match b {
-                    b';' => {
-                        let s = try_await!(parse_eol_comment(&mut self.input));
-                        self.state = AsyncParserState::EndOfLine;
-                        break Ok(GCode::Comment(s));
-                    }
-                    _ => {
-                        self.input.push_back(b);
-                        break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
-                    }
-                },
-                #[cfg(any(feature = "parse-trailing-comment", feature = "parse-checksum"))]
-                AsyncParserState::EndOfLine => {
-                    self.input.push_back(b);
-                    break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
+    fn produce(&mut self, res: Result<GCode, E>) -> Poll<Option<Result<GCode, E>>> {
+        #[cfg(feature = "source-position")]
+        {
+            let end = self.grammar.position();
+            self.span = (self.span_start.take().unwrap_or(end), end);
+        }
+        Poll::Ready(Some(res))
+    }
+
+    /// Reads a whole line into `block`.
+    ///
+    /// `block` is cleared first. On error, the rest of the line is skipped and the first error met
+    /// is returned. The next call then starts on the following line.
+    ///
+    /// Returns `None` once the input stream is exhausted. A line that is not terminated by a new
+    /// line character is still returned.
+    pub async fn next_block<B: BlockStorage>(
+        &mut self,
+        block: &mut Block<B>,
+    ) -> Option<Result<(), E>> {
+        block.clear();
+        #[cfg(feature = "parse-checksum")]
+        self.grammar.take_checksum();
+
+        let mut empty = true;
+        let mut error = None;
+        loop {
+            let gcode = match self.next().await {
+                Some(gcode) => gcode,
+                None if empty => return None,
+                None => break,
+            };
+            empty = false;
+
+            let overflow = match gcode {
+                Ok(GCode::Execute) => break,
+                Ok(_) if error.is_some() => continue,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    continue;
+                }
+                Ok(GCode::BlockDelete) => {
+                    block.block_delete = true;
+                    false
+                }
+                Ok(GCode::LineNumber(n)) => {
+                    block.line_number = Some(n);
+                    false
                 }
-                AsyncParserState::ErrorRecovery => match b {
-                    b'\r' | b'\n' => {
-                        self.input.push_back(b);
-                        break Ok(try_await!(parse_eol(&mut self.state, &mut self.input)));
+                Ok(GCode::Word(letter, value)) => block.words.try_push((letter, value)).is_err(),
+                #[cfg(feature = "parse-parameters")]
+                Ok(GCode::ParameterSet(index, value)) => {
+                    block.parameter_sets.try_push((index, value)).is_err()
+                }
+                #[cfg(feature = "parse-comments")]
+                Ok(GCode::Comment(comment)) => block.comments.try_push(comment).is_err(),
+                #[cfg(feature = "parse-o-words")]
+                Ok(GCode::OCode(label, code)) => {
+                    if block.o_code.is_some() {
+                        error.get_or_insert(Error::RepeatedWord('o').into());
                     }
-                    _ => {}
-                },
+                    block.o_code = Some((label, code));
+                    false
+                }
+            };
+            if overflow {
+                error = Some(Error::BlockOverflow.into());
             }
-        };
-        // eprintln!("{}:{} {:?}", file!(), line!(), res);
-        if res.is_err() {
-            self.state = AsyncParserState::ErrorRecovery;
         }
-        Some(res)
+
+        #[cfg(feature = "parse-checksum")]
+        {
+            block.checksum = self.grammar.take_checksum();
+        }
+        Some(match error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        })
     }
 }
@@ -1,5 +1,4 @@
 use either::Either;
-use futures::stream::{Stream, StreamExt};
 
 #[cfg(all(
     not(feature = "std"),
@@ -9,24 +8,81 @@ use alloc::vec::Vec;
 #[cfg(feature = "std")]
 use std::vec::Vec;
 
+#[cfg(feature = "parse-parameters")]
+use super::values::{parameter_name, NameParser};
+use super::{grammar::Step, values::LiteralParser, Limits};
 use crate::{
-    stream::PushBackable,
     types::{
         expressions::{Associativity, ExprItem, Expression, OpType, Operator},
-        Literal, ParseResult, RealValue,
+        Literal, RealValue,
     },
-    utils::skip_whitespaces,
-    Error,
+    Error, ExpressionError,
 };
 
+/// The functions expected before an operand.
+const FUNCTIONS: &[(&[u8], Operator)] = &[
+    (b"abs", Operator::Abs),
+    (b"acos", Operator::ACos),
+    (b"asin", Operator::ASin),
+    (b"atan", Operator::ATan),
+    (b"cos", Operator::Cos),
+    (b"exp", Operator::Exp),
+    #[cfg(feature = "parse-parameters")]
+    (b"exists", Operator::Exists),
+    (b"fix", Operator::Fix),
+    (b"fup", Operator::Fup),
+    (b"ln", Operator::Ln),
+    #[cfg(feature = "parse-min-max")]
+    (b"max", Operator::Max),
+    #[cfg(feature = "parse-min-max")]
+    (b"min", Operator::Min),
+    (b"round", Operator::Round),
+    (b"sin", Operator::Sin),
+    (b"sqrt", Operator::Sqrt),
+    (b"tan", Operator::Tan),
+];
+
+/// The operators spelled with letters, expected after an operand.
+const OPERATORS: &[(&[u8], Operator)] = &[
+    (b"and", Operator::And),
+    (b"or", Operator::Or),
+    (b"xor", Operator::Xor),
+    (b"mod", Operator::Modulus),
+    (b"eq", Operator::Equal),
+    (b"ne", Operator::NotEqual),
+    (b"gt", Operator::GreaterThan),
+    (b"ge", Operator::GreaterOrEqual),
+    (b"lt", Operator::LessThan),
+    (b"le", Operator::LessOrEqual),
+];
+
+/// The longest keyword, `exists`, has 6 letters.
+const MAX_KEYWORD_LEN: usize = 6;
+
 #[derive(PartialEq, Debug, Clone)]
 enum Token {
     OpenBracket, // stores what to expect on expr exit. BinOpOrEnd or ATanDiv
     CloseBracket,
+    /// Separates the arguments of `min` and `max`.
+    #[cfg(feature = "parse-min-max")]
+    Comma,
     Literal(Literal),
     Operator(Operator),
 }
 
+#[derive(Debug)]
+enum TokenState {
+    /// Spaces may precede the token.
+    Start,
+    /// The letters of a function or operator read so far.
+    Keyword([u8; MAX_KEYWORD_LEN], usize),
+    /// A `*` which may be followed by another one.
+    Star,
+    Literal(LiteralParser),
+    #[cfg(feature = "parse-parameters")]
+    Name(NameParser),
+}
+
 #[derive(PartialEq, Debug, Clone, Copy)]
 enum Expect {
     UnaryOrLiteralOrExpr,
@@ -39,219 +95,347 @@ enum Expect {
 enum Stacked {
     Operator(Operator),
     OpenBracket,
+    /// An open bracket whose first argument was separated from the second one.
+    #[cfg(feature = "parse-min-max")]
+    Comma,
     ATan,
 }
 
-macro_rules! match_operator {
-    (@ $input:expr, $e:block) => {{
-        $e
-    }};
-    (@ $input:expr, $p:expr) => {{
-        Token::Operator($p)
-    }};
-    (@ $input:expr, $c:literal => $op:path) => {{
-        let mut zipped = $input.zip(futures::stream::iter($c.iter()));
-        for _ in 0..$c.len() {
-            match zipped.next().await? {
-                (Ok(input_byte), &expected_byte) => if input_byte.to_ascii_lowercase() != expected_byte {
-                    return Some(ParseResult::Parsing(Error::UnexpectedByte(input_byte)))
-                }
-                (Err(e), _) => return Some(ParseResult::Input(e))
-            }
-        }
+/// Reads a word's value: a literal, a function or a bracketed expression.
+///
+/// The expression is turned into its postfix form as its tokens are read.
+#[derive(Debug)]
+pub(crate) struct ValueParser {
+    limits: Limits,
+    #[cfg(feature = "string-value")]
+    collect: bool,
+    token: TokenState,
+    expects: Expect,
+    stack: Vec<Stacked>,
+    postfix: Vec<ExprItem>,
+    depth: u8,
+}
 
-        Token::Operator($op)
-    }};
-    (@ $input:expr, $( $(#[$($m:tt)*])* $p:pat => { $($rest:tt)* } )*) => {{
-        let b = match $input.next().await? {
-            Ok(b) => b,
-            Err(e) => return Some(ParseResult::Input(e)),
-        };
-        match b.to_ascii_lowercase() {
-            $(
-                $p => {
-                    match_operator!(@ $input, $($rest)*)
-                }
-             )*
-            #[allow(unreachable_patterns)]
-            _ => return Some(ParseResult::Parsing(Error::UnexpectedByte(b))),
+impl ValueParser {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            #[cfg(feature = "string-value")]
+            collect: true,
+            token: TokenState::Start,
+            expects: Expect::UnaryOrLiteralOrExpr,
+            stack: Vec::new(),
+            postfix: Vec::new(),
+            depth: 0,
         }
-    }};
-    ($input:expr, $b:expr, $($(#[$($m:tt)*])* $p:pat => { $($rest:tt)* })* ) => {{
-        match $b.to_ascii_lowercase() {
-            $(
-                $(#[$($m)*])*
-                $p => {
-                    match_operator!(@ $input, $($rest)*)
-                }
-             )*
-            #[allow(unreachable_patterns)]
-            b => return Some(ParseResult::Parsing(Error::UnexpectedByte(b))),
+    }
+
+    /// Leaves a string value empty rather than collecting it, unless it is part of an expression.
+    #[cfg(feature = "string-value")]
+    pub fn borrowing(limits: Limits) -> Self {
+        Self {
+            collect: false,
+            ..Self::new(limits)
         }
-    }}
-}
+    }
 
-async fn tokenize<S, E>(input: &mut S, expect: Expect) -> Option<ParseResult<Token, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    let b = match input.next().await? {
-        Ok(b) => b,
-        Err(e) => return Some(ParseResult::Input(e)),
-    };
-    // println!("{:?}", b as char);
-    let token = match expect {
-        Expect::UnaryOrLiteralOrExpr => {
-            match_operator! { input, b,
-                #[cfg(feature = "parse-parameters")]
-                b'#' => { Operator::GetParameter }
-                b'[' => {{ Token::OpenBracket }}
-                b'a' => {
-                    b'b' => { b"s" => Operator::Abs }
-                    b'c' => { b"os" => Operator::ACos }
-                    b's' => { b"in" => Operator::ASin }
-                    b't' => { b"an" => Operator::ATan }
-                }
-                b'c' => { b"os" => Operator::Cos }
-                b'e' => { b"xp" => Operator::Exp }
-                b'f' => {
-                    b'i' => { b"x" => Operator::Fix }
-                    b'u' => { b"p" => Operator::Fup }
+    pub fn feed(&mut self, b: u8) -> Step<RealValue> {
+        /*
+        Begin
+           initially push some special character say # into the stack
+           for each character tok from infix expression, do
+              literal => add tok to postfix expression
+              openBracket => push ( into stack
+              closeBracket =>
+                while stack is not empty and stack top ≠ (,
+                  do pop and add item from stack to postfix expression
+                done
+
+                if stack.is_empty => error !
+
+                pop ( also from the stack
+              operator(op) =>
+                while !stack.is_empty AND
+                      precedence(op) < precedence(stack.top) OR
+                      (precedence(op) == precedence(stack.top) AND !op.is_right_associative) do
+                  pop and add into postfix expression
+                done
+
+                stack.push(op)
+           done
+
+           while the stack contains some remaining token, do
+              pop and add to the postfix expression
+           done
+           return postfix
+        End
+        */
+
+        loop {
+            // lexical analysis
+            let (token, consumed) = match self.tokenize(b) {
+                Step::More => return Step::More,
+                Step::Take(token) => (token, true),
+                Step::Leave(token) => (token, false),
+            };
+            self.token = TokenState::Start;
+            //println!("token: {:?}", token);
+            let token = match token {
+                Ok(token) => token,
+                #[cfg(feature = "optional-value")]
+                Err(Error::UnexpectedByte(_))
+                    if self.postfix.is_empty() && self.stack.is_empty() =>
+                {
+                    return Step::Leave(Ok(RealValue::None))
                 }
-                b'l' => { b"n" => Operator::Ln }
-                b'r' => { b"ound" => Operator::Round }
-                b's' => {
-                    b'i' => { b"n" => Operator::Sin }
-                    b'q' => { b"rt" => Operator::Sqrt }
+                // within an expression, a byte that fits no token makes it invalid.
+                Err(Error::UnexpectedByte(b)) if self.in_expression() => {
+                    let reason = self.reason(b);
+                    return Step::done(Err(Error::InvalidExpression(reason)), consumed);
                 }
-                b't' => { b"an" => Operator::Tan }
-                _ => {{
-                    input.push_back(b);
-                    let lit = try_parse!(super::values::parse_literal(input));
-                            Token::Literal(lit)
+                Err(e) => return Step::done(Err(e), consumed),
+            };
 
-                }}
+            // grammar analysis
+            match self.analyse(token) {
+                Ok(Some(value)) => return Step::done(Ok(value), consumed),
+                Ok(None) if consumed => return Step::More,
+                Ok(None) => {}
+                Err(e) => return Step::done(Err(e), consumed),
             }
         }
-        Expect::BinOpOrCloseBracket => {
-            match_operator! { input, b,
-                b'+' => { Operator::Add }
-                b'-' => { Operator::Substract }
-                b'*' => {
-                    b'*' => { Operator::Power }
-                    b => {{
-                        input.push_back(b);
-                        Token::Operator(Operator::Multiply)
-                    }}
+    }
+
+    fn tokenize(&mut self, b: u8) -> Step<Token> {
+        loop {
+            let keywords = match self.expects {
+                Expect::UnaryOrLiteralOrExpr => FUNCTIONS,
+                _ => OPERATORS,
+            };
+            match &mut self.token {
+                TokenState::Start => {
+                    if b == b' ' {
+                        return Step::More;
+                    }
+                    let lower = b.to_ascii_lowercase();
+                    let token = match self.expects {
+                        Expect::UnaryOrLiteralOrExpr => match lower {
+                            #[cfg(feature = "parse-parameters")]
+                            b'#' => Token::Operator(Operator::GetParameter),
+                            #[cfg(feature = "parse-parameters")]
+                            b'<' => {
+                                self.token = TokenState::Name(NameParser::new(self.limits));
+                                return Step::More;
+                            }
+                            b'[' => Token::OpenBracket,
+                            b']' => Token::CloseBracket,
+                            _ if keywords.iter().any(|(word, _)| word[0] == lower) => {
+                                self.token = TokenState::Keyword([lower, 0, 0, 0, 0, 0], 1);
+                                return Step::More;
+                            }
+                            _ => {
+                                // a string is the value itself unless it is part of an
+                                // expression.
+                                #[cfg(feature = "string-value")]
+                                let collect = self.collect || !self.stack.is_empty();
+                                #[cfg(not(feature = "string-value"))]
+                                let collect = true;
+                                self.token =
+                                    TokenState::Literal(LiteralParser::new(self.limits, collect));
+                                continue;
+                            }
+                        },
+                        Expect::BinOpOrCloseBracket => match lower {
+                            b'+' => Token::Operator(Operator::Add),
+                            b'-' => Token::Operator(Operator::Substract),
+                            b'/' => Token::Operator(Operator::Divide),
+                            b']' => Token::CloseBracket,
+                            #[cfg(feature = "parse-min-max")]
+                            b',' => Token::Comma,
+                            b'*' => {
+                                self.token = TokenState::Star;
+                                return Step::More;
+                            }
+                            _ if keywords.iter().any(|(word, _)| word[0] == lower) => {
+                                self.token = TokenState::Keyword([lower, 0, 0, 0, 0, 0], 1);
+                                return Step::More;
+                            }
+                            _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                        },
+                        Expect::Expr => match b {
+                            b'[' => Token::OpenBracket,
+                            _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                        },
+                        Expect::ATanDiv => match b {
+                            b'/' => Token::Operator(Operator::Divide),
+                            _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                        },
+                    };
+                    return Step::Take(Ok(token));
+                }
+                TokenState::Keyword(letters, len) => {
+                    // no keyword is the prefix of another one.
+                    if let Some(letter) = letters.get_mut(*len) {
+                        *letter = b.to_ascii_lowercase();
+                        *len += 1;
+                    }
+                    let read = &letters[..*len];
+                    return match keywords.iter().find(|(word, _)| word.starts_with(read)) {
+                        Some(&(word, op)) if word == read => Step::Take(Ok(Token::Operator(op))),
+                        Some(_) => Step::More,
+                        None => Step::Take(Err(Error::UnexpectedByte(b))),
+                    };
+                }
+                TokenState::Star => {
+                    return if b == b'*' {
+                        Step::Take(Ok(Token::Operator(Operator::Power)))
+                    } else {
+                        Step::Leave(Ok(Token::Operator(Operator::Multiply)))
+                    }
+                }
+                TokenState::Literal(literal) => return literal.feed(b).map(Token::Literal),
+                #[cfg(feature = "parse-parameters")]
+                TokenState::Name(name) => {
+                    return name
+                        .feed(b)
+                        .and_then(parameter_name)
+                        .map(|name| Token::Literal(name.into()))
                 }
-                b'/' => { Operator::Divide }
-                b'a' => { b"nd" => Operator::And }
-                b'o' => { b"r" => Operator::Or }
-                b'x' => { b"or" => Operator::Xor }
-                b'm' => { b"od" => Operator::Modulus }
-                b']' => {{ Token::CloseBracket }}
-                #[cfg(not(feature = "parse-parameters"))]
-                _ => {{ return Some(ParseResult::Parsing(Error::UnexpectedByte(b))) }}
             }
         }
-        Expect::Expr => match b {
-            b'[' => Token::OpenBracket,
-            _ => return Some(ParseResult::Parsing(Error::UnexpectedByte(b))),
-        },
-        Expect::ATanDiv => match b {
-            b'/' => Token::Operator(Operator::Divide),
-            _ => return Some(ParseResult::Parsing(Error::UnexpectedByte(b))),
-        },
-    };
-    Some(ParseResult::Ok(token))
-}
+    }
 
-pub(crate) async fn parse_real_value<S, E>(input: &mut S) -> Option<ParseResult<RealValue, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    /*
-    Begin
-       initially push some special character say # into the stack
-       for each character tok from infix expression, do
-          literal => add tok to postfix expression
-          openBracket => push ( into stack
-          closeBracket =>
-            while stack is not empty and stack top ≠ (,
-              do pop and add item from stack to postfix expression
-            done
-
-            if stack.is_empty => error !
-
-            pop ( also from the stack
-          operator(op) =>
-            while !stack.is_empty AND
-                  precedence(op) < precedence(stack.top) OR
-                  (precedence(op) == precedence(stack.top) AND !op.is_right_associative) do
-              pop and add into postfix expression
-            done
-
-            stack.push(op)
-       done
-
-       while the stack contains some remaining token, do
-          pop and add to the postfix expression
-       done
-       return postfix
-    End
-    */
-
-    let mut expects = Expect::UnaryOrLiteralOrExpr;
-    let mut stack: Vec<Stacked> = Vec::new();
-    let mut postfix: Vec<ExprItem> = Vec::new();
-    let mut depth = 0;
-
-    loop {
-        try_result!(skip_whitespaces(input));
-        //println!("{:?}: {:?} {:?}", expects, postfix, stack);
-
-        // lexical analysis
-        let token = match tokenize(input, expects).await? {
-            ParseResult::Ok(tok) => tok,
-            #[cfg(feature = "optional-value")]
-            ParseResult::Parsing(Error::UnexpectedByte(b))
-                if postfix.is_empty() && stack.is_empty() =>
+    /// Whether brackets or a function were read, a parameter reference such as `#1` alone is not
+    /// an expression.
+    fn in_expression(&self) -> bool {
+        self.depth != 0
+            || self.stack.iter().any(|stacked| match stacked {
+                #[cfg(feature = "parse-parameters")]
+                Stacked::Operator(Operator::GetParameter) => false,
+                _ => true,
+            })
+    }
+
+    /// Why the expression is invalid when `b` fits no token.
+    fn reason(&self, b: u8) -> ExpressionError {
+        let lower = b.to_ascii_lowercase();
+        match self.expects {
+            // the line ends before the brackets are closed.
+            _ if matches!(b, b'\r' | b'\n') && self.depth != 0 => {
+                ExpressionError::UnbalancedBrackets
+            }
+            // an operand follows another one.
+            Expect::BinOpOrCloseBracket
+                if matches!(lower, b'0'..=b'9' | b'.' | b'[' | b'#' | b'<')
+                    || FUNCTIONS.iter().any(|(word, _)| word[0] == lower) =>
             {
-                //println!("err: {:?}", b as char);
-                input.push_back(b);
-                return Some(ParseResult::Ok(RealValue::None));
+                ExpressionError::ExtraOperands
             }
-            ParseResult::Parsing(e) => return Some(ParseResult::Parsing(e)),
-            ParseResult::Input(e) => return Some(ParseResult::Input(e)),
-        };
-        //println!("token: {:?}", token);
+            // an unknown operator or function, or one missing its operands.
+            _ => ExpressionError::MisplacedOperator,
+        }
+    }
+
+    /// Adds `token` to the expression, returns its value once it is complete.
+    fn analyse(&mut self, token: Token) -> Result<Option<RealValue>, Error> {
+        let invalid = |reason| Err(Error::InvalidExpression(reason));
+        let (stack, postfix) = (&mut self.stack, &mut self.postfix);
+
+        // every token but a closing bracket adds an item to either the stack or the postfix
+        // expression.
+        if token != Token::CloseBracket
+            && stack.len() + postfix.len() >= usize::from(self.limits.expression_length)
+        {
+            return invalid(ExpressionError::TooLong);
+        }
 
-        // grammar analysis
         match token {
             Token::OpenBracket => {
-                depth += 1;
-                expects = Expect::UnaryOrLiteralOrExpr;
+                if self.depth >= self.limits.expression_depth {
+                    return invalid(ExpressionError::TooDeep);
+                }
+                self.depth += 1;
+                self.expects = Expect::UnaryOrLiteralOrExpr;
                 stack.push(Stacked::OpenBracket)
             }
             Token::Literal(l) => {
+                // names are only accepted as the operand of `#`.
+                #[cfg(feature = "parse-parameters")]
+                if matches!(l, Literal::Name(_))
+                    && stack.last() != Some(&Stacked::Operator(Operator::GetParameter))
+                {
+                    return if stack.is_empty() {
+                        Err(Error::UnexpectedByte(b'<'))
+                    } else {
+                        invalid(ExpressionError::MisplacedOperator)
+                    };
+                }
                 postfix.push(l.into());
-                if depth != 0 {
-                    expects = Expect::BinOpOrCloseBracket;
+                if self.depth != 0 {
+                    self.expects = Expect::BinOpOrCloseBracket;
                 } else {
-                    break;
+                    return self.finish().map(Some);
                 }
             }
+            #[cfg(feature = "parse-min-max")]
+            Token::Comma => {
+                loop {
+                    match stack.pop() {
+                        Some(Stacked::Operator(op)) => postfix.push(op.into()),
+                        Some(Stacked::OpenBracket) => break,
+                        _ => return invalid(ExpressionError::MisplacedOperator),
+                    };
+                }
+                if !matches!(
+                    stack.last(),
+                    Some(Stacked::Operator(Operator::Min | Operator::Max))
+                ) {
+                    return invalid(ExpressionError::MisplacedOperator);
+                }
+                stack.push(Stacked::Comma);
+                self.expects = Expect::UnaryOrLiteralOrExpr;
+            }
+            // the bracket or the operator before is missing its operand.
+            Token::CloseBracket
+                if self.depth != 0 && self.expects == Expect::UnaryOrLiteralOrExpr =>
+            {
+                return invalid(ExpressionError::MisplacedOperator)
+            }
             Token::CloseBracket => {
                 loop {
                     match stack.pop() {
                         Some(Stacked::Operator(op)) => postfix.push(op.into()),
+                        // `min` and `max` take two arguments.
+                        #[cfg(feature = "parse-min-max")]
+                        Some(Stacked::OpenBracket)
+                            if matches!(
+                                stack.last(),
+                                Some(Stacked::Operator(Operator::Min | Operator::Max))
+                            ) =>
+                        {
+                            return invalid(ExpressionError::MisplacedOperator)
+                        }
                         Some(Stacked::OpenBracket) => break,
-                        Some(_) | None => unreachable!(),
+                        #[cfg(feature = "parse-min-max")]
+                        Some(Stacked::Comma) => break,
+                        Some(Stacked::ATan) | None => {
+                            return invalid(ExpressionError::UnbalancedBrackets)
+                        }
                     };
                 }
                 // println!("CloseBracket: {:?} {:?}", postfix, stack);
-                // TODO: use checked arithmetic to prevent panic if depth == 0
-                depth -= 1;
+                self.depth = match self.depth.checked_sub(1) {
+                    Some(depth) => depth,
+                    None => return invalid(ExpressionError::UnbalancedBrackets),
+                };
+
+                // `exists` takes the parameter itself rather than its value.
+                #[cfg(feature = "parse-parameters")]
+                if stack.last() == Some(&Stacked::Operator(Operator::Exists))
+                    && postfix.pop() != Some(Operator::GetParameter.into())
+                {
+                    return invalid(ExpressionError::MisplacedOperator);
+                }
 
                 if stack
                     .last()
@@ -260,17 +444,19 @@ where
                 {
                     //TODO: this loop on expecting an ATan, let's use a stack specific Type and
                     //keep track there wether we already got our atandiv or not
-                    expects = Expect::ATanDiv;
-                } else if depth != 0 {
-                    expects = Expect::BinOpOrCloseBracket;
+                    self.expects = Expect::ATanDiv;
+                } else if self.depth != 0 {
+                    self.expects = Expect::BinOpOrCloseBracket;
                 } else {
-                    break;
+                    return self.finish().map(Some);
                 }
             }
-            Token::Operator(Operator::Divide) if expects == Expect::ATanDiv => {
-                stack.pop(); // this is known to be Stacked::ATan
+            Token::Operator(Operator::Divide) if self.expects == Expect::ATanDiv => {
+                if stack.pop() != Some(Stacked::ATan) {
+                    return invalid(ExpressionError::MisplacedOperator);
+                }
                 stack.push(Stacked::Operator(Operator::ATan));
-                expects = Expect::Expr
+                self.expects = Expect::Expr
             }
             Token::Operator(op) => {
                 /*
@@ -280,76 +466,69 @@ where
                  *   pop and add into postfix expression
                  * done
                  */
-                while stack
-                    .last()
-                    .map(|tok| match tok {
-                        Stacked::Operator(stacked_op) => {
-                            op.precedence() < stacked_op.precedence()
-                                || (op.precedence() == stacked_op.precedence()
-                                    && op.associativity() == Associativity::Left)
-                        }
-                        Stacked::OpenBracket => false,
-                        _ => unreachable!(),
-                    })
-                    .unwrap_or(false)
-                {
-                    postfix.push(
-                        match stack.pop().unwrap() {
-                            Stacked::Operator(op) => op,
-                            _ => unreachable!(),
-                        }
-                        .into(),
-                    )
+                // brackets, including the one following an atan, stop the unwinding.
+                while let Some(&Stacked::Operator(stacked_op)) = stack.last() {
+                    if op.precedence() < stacked_op.precedence()
+                        || (op.precedence() == stacked_op.precedence()
+                            && op.associativity() == Associativity::Left)
+                    {
+                        stack.pop();
+                        postfix.push(stacked_op.into());
+                    } else {
+                        break;
+                    }
                 }
 
                 match op {
                     #[cfg(feature = "parse-parameters")]
                     Operator::GetParameter => {
                         stack.push(Stacked::Operator(Operator::GetParameter));
-                        expects = Expect::UnaryOrLiteralOrExpr;
+                        self.expects = Expect::UnaryOrLiteralOrExpr;
                     }
                     Operator::ATan => {
                         stack.push(Stacked::ATan);
-                        expects = Expect::Expr;
+                        self.expects = Expect::Expr;
                     }
                     _ => {
                         stack.push(Stacked::Operator(op));
 
                         if op.op_type() == OpType::Unary {
-                            expects = Expect::Expr
+                            self.expects = Expect::Expr
                         } else {
-                            expects = Expect::UnaryOrLiteralOrExpr
+                            self.expects = Expect::UnaryOrLiteralOrExpr
                         }
                     }
                 }
             }
         }
+        Ok(None)
     }
 
-    /*
-      while the stack contains some remaining characters, do
-         pop and add to the postfix expression
-      done
-      return postfix
-    */
-    while let Some(stacked) = stack.pop() {
-        match stacked {
-            Stacked::Operator(op) => postfix.push(op.into()),
-            Stacked::OpenBracket => return Some(ParseResult::Parsing(Error::InvalidExpression)),
-            _ => unreachable!(),
-        }
-    }
+    fn finish(&mut self) -> Result<RealValue, Error> {
+        let invalid = |reason| Err(Error::InvalidExpression(reason));
+        let mut postfix = core::mem::take(&mut self.postfix);
 
-    debug_assert!(stack.is_empty());
-
-    // println!("end: {:?} {:?}", postfix, stack);
-    Some(ParseResult::Ok(if postfix.len() == 1 {
-        if let Some(Either::Right(rv)) = postfix.pop() {
-            rv.into()
-        } else {
-            unreachable!()
+        /*
+          while the stack contains some remaining characters, do
+             pop and add to the postfix expression
+          done
+          return postfix
+        */
+        while let Some(stacked) = self.stack.pop() {
+            match stacked {
+                Stacked::Operator(op) => postfix.push(op.into()),
+                Stacked::OpenBracket => return invalid(ExpressionError::UnbalancedBrackets),
+                #[cfg(feature = "parse-min-max")]
+                Stacked::Comma => return invalid(ExpressionError::UnbalancedBrackets),
+                Stacked::ATan => return invalid(ExpressionError::MisplacedOperator),
+            }
         }
-    } else {
-        Expression(postfix).into()
-    }))
+
+        // println!("end: {:?} {:?}", postfix, stack);
+        Ok(match postfix.as_slice() {
+            [Either::Right(literal)] => literal.clone().into(),
+            [Either::Left(_)] => return invalid(ExpressionError::MisplacedOperator),
+            _ => Expression(postfix).into(),
+        })
+    }
 }
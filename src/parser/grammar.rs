@@ -0,0 +1,593 @@
+//! The parser's state machine, fed one byte at a time.
+//!
+//! Every front-end drives the same [`Grammar`]: [`Parser`](super::Parser) polls its stream for
+//! the bytes and [`SliceParser`](super::SliceParser) reads them from its slice.
+
+#[cfg(all(not(feature = "std"), feature = "parse-comments"))]
+use alloc::{string::String, vec::Vec};
+
+use super::{values::NumberParser, Limits};
+use crate::{Checksum, Error, GCode, XorSum};
+
+#[cfg(feature = "parse-expressions")]
+use super::expressions::ValueParser;
+#[cfg(feature = "parse-o-words")]
+use super::o_words::OCodeParser;
+#[cfg(not(feature = "parse-expressions"))]
+use super::values::ValueParser;
+#[cfg(feature = "parse-parameters")]
+use super::values::{parameter_name, NameParser};
+#[cfg(feature = "parse-expressions")]
+use crate::ExpressionError;
+#[cfg(feature = "parse-parameters")]
+use crate::RealValue;
+
+#[cfg(feature = "source-position")]
+use crate::position::SourcePosition;
+
+/// What a state machine made of the byte it was fed.
+#[derive(Debug)]
+pub(crate) enum Step<T> {
+    /// The byte is consumed and more are needed.
+    More,
+    /// The byte is consumed and completes the result.
+    Take(Result<T, Error>),
+    /// The result was complete before the byte, which is left to whatever follows.
+    Leave(Result<T, Error>),
+}
+
+impl<T> Step<T> {
+    /// Completes with `res`, `consumed` telling whether the byte is part of it.
+    pub fn done(res: Result<T, Error>, consumed: bool) -> Self {
+        if consumed {
+            Self::Take(res)
+        } else {
+            Self::Leave(res)
+        }
+    }
+
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Step<U> {
+        self.and_then(|v| Ok(f(v)))
+    }
+
+    pub fn and_then<U>(self, f: impl FnOnce(T) -> Result<U, Error>) -> Step<U> {
+        match self {
+            Self::More => Step::More,
+            Self::Take(res) => Step::Take(res.and_then(f)),
+            Self::Leave(res) => Step::Leave(res.and_then(f)),
+        }
+    }
+}
+
+/// Reads an inline comment following its `(`.
+///
+/// With `parse-comments`, the comment is only kept when collecting, it is left empty otherwise.
+#[derive(Debug)]
+struct CommentParser {
+    #[cfg(feature = "parse-comments")]
+    text: Option<Vec<u8>>,
+    escaped: bool,
+}
+
+impl CommentParser {
+    #[cfg_attr(not(feature = "parse-comments"), allow(unused_variables))]
+    fn new(collect: bool) -> Self {
+        Self {
+            #[cfg(feature = "parse-comments")]
+            text: collect.then(Vec::new),
+            escaped: false,
+        }
+    }
+
+    fn feed(&mut self, b: u8) -> Step<Option<GCode>> {
+        if self.escaped {
+            self.escaped = false;
+        } else if b == b'\\' {
+            self.escaped = true;
+            return Step::More;
+        } else if b == b'(' {
+            return Step::Take(Err(Error::UnexpectedByte(b'(')));
+        } else if b == b')' {
+            return Step::Take(self.finish());
+        }
+        #[cfg(feature = "parse-comments")]
+        if let Some(text) = &mut self.text {
+            text.push(b);
+        }
+        Step::More
+    }
+
+    /// Comments are only produced with `parse-comments`.
+    fn finish(&mut self) -> Result<Option<GCode>, Error> {
+        #[cfg(feature = "parse-comments")]
+        {
+            let comment = match self.text.take() {
+                Some(text) => String::from_utf8(text).map_err(|_| Error::InvalidUTF8String)?,
+                None => String::new(),
+            };
+            Ok(Some(GCode::Comment(comment)))
+        }
+        #[cfg(not(feature = "parse-comments"))]
+        Ok(None)
+    }
+}
+
+/// Reads a trailing comment following its `;` up to the end of the line.
+#[cfg(feature = "parse-trailing-comment")]
+#[derive(Debug)]
+struct TrailingCommentParser(CommentParser);
+
+#[cfg(feature = "parse-trailing-comment")]
+impl TrailingCommentParser {
+    fn feed(&mut self, b: u8) -> Step<Option<GCode>> {
+        match b {
+            b'\r' | b'\n' => Step::Leave(self.0.finish()),
+            _b => {
+                #[cfg(feature = "parse-comments")]
+                if let Some(text) = &mut self.0.text {
+                    text.push(_b);
+                }
+                Step::More
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parse-parameters")]
+#[derive(Debug)]
+enum ParameterPhase {
+    /// Spaces may precede the index, a name starts with `<`.
+    Index,
+    Name(NameParser),
+    Number(ValueParser),
+    /// Spaces may precede the `=`.
+    Equal,
+    Value(ValueParser),
+}
+
+/// Reads a parameter setting following its `#`: `#index = value`.
+#[cfg(feature = "parse-parameters")]
+#[derive(Debug)]
+struct ParameterSetParser {
+    limits: Limits,
+    phase: ParameterPhase,
+    index: RealValue,
+}
+
+#[cfg(feature = "parse-parameters")]
+impl ParameterSetParser {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            phase: ParameterPhase::Index,
+            index: RealValue::default(),
+        }
+    }
+
+    fn feed(&mut self, b: u8) -> Step<GCode> {
+        loop {
+            match &mut self.phase {
+                ParameterPhase::Index => match b {
+                    b' ' => return Step::More,
+                    b'<' => {
+                        self.phase = ParameterPhase::Name(NameParser::new(self.limits));
+                        return Step::More;
+                    }
+                    _ => self.phase = ParameterPhase::Number(ValueParser::new(self.limits)),
+                },
+                ParameterPhase::Name(name) => {
+                    let (name, _) = try_step!(name.feed(b).and_then(parameter_name));
+                    self.index = RealValue::from(name);
+                    self.phase = ParameterPhase::Equal;
+                    return Step::More;
+                }
+                ParameterPhase::Number(number) => {
+                    let (index, consumed) = try_step!(number.feed(b));
+                    #[cfg(feature = "optional-value")]
+                    if index == RealValue::None {
+                        return Step::Take(Err(Error::UnexpectedByte(b)));
+                    }
+                    self.index = index;
+                    self.phase = ParameterPhase::Equal;
+                    if consumed {
+                        return Step::More;
+                    }
+                }
+                ParameterPhase::Equal => match b {
+                    b' ' => return Step::More,
+                    b'=' => {
+                        self.phase = ParameterPhase::Value(ValueParser::new(self.limits));
+                        return Step::More;
+                    }
+                    _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                },
+                ParameterPhase::Value(value) => {
+                    let index = &mut self.index;
+                    return value
+                        .feed(b)
+                        .map(|value| GCode::ParameterSet(core::mem::take(index), value));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Start(bool),
+    LineNumberOrSegment,
+    LineNumber(NumberParser),
+    Segment,
+    Word(char, ValueParser),
+    #[cfg(feature = "parse-o-words")]
+    OCode(OCodeParser),
+    #[cfg(feature = "parse-parameters")]
+    ParameterSet(ParameterSetParser),
+    /// The sum of the bytes preceding the `*` along with the checksum read so far.
+    #[cfg(feature = "parse-checksum")]
+    Checksum(u16, NumberParser),
+    Comment(CommentParser),
+    #[cfg(feature = "parse-trailing-comment")]
+    TrailingComment(TrailingCommentParser),
+    ErrorRecovery,
+    #[cfg(all(feature = "parse-trailing-comment", feature = "parse-checksum"))]
+    EoLOrTrailingComment,
+    #[cfg(any(feature = "parse-trailing-comment", feature = "parse-checksum"))]
+    EndOfLine,
+}
+
+/// The parser's state between two bytes.
+#[derive(Debug)]
+pub(crate) struct Grammar<C = XorSum> {
+    state: State,
+    limits: Limits,
+    #[cfg(feature = "parse-checksum")]
+    sum: C,
+    #[cfg(feature = "parse-checksum")]
+    checksum: Option<u16>,
+    /// The last line number read and whether it belongs to the current line.
+    #[cfg(feature = "parse-checksum")]
+    line_number: (Option<u32>, bool),
+    /// Whether the current line starts with a line number or carries a checksum, even when they
+    /// could not be read.
+    #[cfg(feature = "parse-checksum")]
+    framed: bool,
+    /// Whether the text of comments and strings is collected, it is left empty otherwise.
+    #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+    collect_text: bool,
+    #[cfg(not(feature = "parse-checksum"))]
+    algorithm: core::marker::PhantomData<C>,
+    #[cfg(feature = "source-position")]
+    position: SourcePosition,
+}
+
+impl<C: Checksum> Grammar<C> {
+    #[cfg_attr(not(feature = "parse-checksum"), allow(unused_variables))]
+    pub fn new(limits: Limits, checksum: C) -> Self {
+        Self {
+            state: State::Start(true),
+            limits,
+            #[cfg(feature = "parse-checksum")]
+            sum: checksum,
+            #[cfg(feature = "parse-checksum")]
+            checksum: None,
+            #[cfg(feature = "parse-checksum")]
+            line_number: (None, false),
+            #[cfg(feature = "parse-checksum")]
+            framed: false,
+            #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+            collect_text: true,
+            #[cfg(not(feature = "parse-checksum"))]
+            algorithm: core::marker::PhantomData,
+            #[cfg(feature = "source-position")]
+            position: SourcePosition::default(),
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Whether the comments and strings that are the value of a word are collected.
+    ///
+    /// When they are not, they are produced empty and are expected to be borrowed from the input.
+    #[cfg_attr(
+        not(any(feature = "parse-comments", feature = "string-value")),
+        allow(unused_variables)
+    )]
+    pub fn collect_text(&mut self, collect: bool) {
+        #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+        {
+            self.collect_text = collect;
+        }
+    }
+
+    /// The position of the next byte to be consumed.
+    #[cfg(feature = "source-position")]
+    pub fn position(&self) -> SourcePosition {
+        self.position
+    }
+
+    /// Takes the checksum of the current line, once it has been verified.
+    #[cfg(feature = "parse-checksum")]
+    pub fn take_checksum(&mut self) -> Option<u16> {
+        self.checksum.take()
+    }
+
+    /// Whether `b` is ignored in the current state.
+    pub fn skips(&self, b: u8) -> bool {
+        match self.state {
+            State::ErrorRecovery => !matches!(b, b'\r' | b'\n'),
+            _ => b == b' ',
+        }
+    }
+
+    /// Gives up on the current event as the input failed, unless it failed between two events.
+    pub fn input_failed(&mut self) {
+        if !matches!(
+            self.state,
+            State::Start(_) | State::LineNumberOrSegment | State::Segment | State::ErrorRecovery
+        ) {
+            self.state = State::ErrorRecovery;
+        }
+    }
+
+    /// Whether the current line, or the last one once it is executed, starts with a line number or
+    /// carries a checksum.
+    #[cfg(feature = "parse-checksum")]
+    pub fn framed(&self) -> bool {
+        self.framed
+    }
+
+    /// Skips the rest of the line as after an error, for errors met outside of the grammar.
+    pub fn recover(&mut self) {
+        self.state = State::ErrorRecovery;
+    }
+
+    /// Feeds `b`, which is to be fed again if it is left by the event produced.
+    pub fn feed(&mut self, b: u8) -> Step<GCode> {
+        // println!("{:?}: {:?}", self.state, char::from(b));
+        #[cfg(feature = "parse-checksum")]
+        if matches!(self.state, State::Start(true)) {
+            self.framed = false;
+        }
+        let step = match self.advance(b) {
+            // the end of the line is left to the error recovery, which executes it.
+            Step::Take(Err(e)) if b == b'\n' => Step::Leave(Err(e)),
+            step => step,
+        };
+        if !matches!(step, Step::Leave(_)) {
+            #[cfg(feature = "parse-checksum")]
+            self.sum.update(b);
+            #[cfg(feature = "source-position")]
+            {
+                self.position = self.position.after(b);
+            }
+        }
+        match step {
+            Step::Take(Err(_)) | Step::Leave(Err(_)) => self.state = State::ErrorRecovery,
+            #[cfg(feature = "parse-checksum")]
+            Step::Take(Ok(GCode::Execute)) => {
+                self.sum = C::default();
+                self.line_number.1 = false;
+            }
+            _ => {}
+        }
+        step
+    }
+
+    fn advance(&mut self, b: u8) -> Step<GCode> {
+        loop {
+            match &mut self.state {
+                State::Start(first_byte) => match b {
+                    b'\n' => return self.execute(),
+                    b'/' if *first_byte => {
+                        self.state = State::LineNumberOrSegment;
+                        return Step::Take(Ok(GCode::BlockDelete));
+                    }
+                    b' ' => {
+                        *first_byte = false;
+                        return Step::More;
+                    }
+                    _ => self.state = State::LineNumberOrSegment,
+                },
+                State::LineNumberOrSegment => match b.to_ascii_lowercase() {
+                    b'n' => {
+                        #[cfg(feature = "parse-checksum")]
+                        {
+                            self.framed = true;
+                        }
+                        let digits = self.limits.line_number_digits;
+                        self.state = State::LineNumber(NumberParser::new(digits));
+                        return Step::More;
+                    }
+                    _ => self.state = State::Segment,
+                },
+                State::LineNumber(number) => {
+                    if b == b' ' && number.is_empty() {
+                        return Step::More;
+                    }
+                    let ((n, digits), _) = try_step!(number.feed(b));
+                    if digits == 0 {
+                        return Step::Take(Err(Error::UnexpectedByte(b)));
+                    }
+                    self.state = State::Segment;
+                    #[cfg(feature = "parse-checksum")]
+                    {
+                        self.line_number = (Some(n), true);
+                    }
+                    return Step::Leave(Ok(GCode::LineNumber(n)));
+                }
+                State::Segment => match b.to_ascii_lowercase() {
+                    b' ' => return Step::More,
+                    #[cfg(feature = "parse-o-words")]
+                    b'o' => {
+                        self.state = State::OCode(OCodeParser::new(self.limits));
+                        return Step::More;
+                    }
+                    letter @ b'a'..=b'z' => {
+                        self.state = State::Word(char::from(letter), self.value());
+                        return Step::More;
+                    }
+                    b'\r' | b'\n' => return self.execute(),
+                    // param support feature
+                    #[cfg(feature = "parse-parameters")]
+                    b'#' => {
+                        self.state = State::ParameterSet(ParameterSetParser::new(self.limits));
+                        return Step::More;
+                    }
+                    // checksum support feature
+                    #[cfg(feature = "parse-checksum")]
+                    b'*' => {
+                        self.framed = true;
+                        // checksums have at most 3 or 5 digits
+                        let digits = if C::MAX > 999 { 5 } else { 3 };
+                        self.state = State::Checksum(self.sum.value(), NumberParser::new(digits));
+                        return Step::More;
+                    }
+                    // comment support features
+                    b'(' => {
+                        self.state = State::Comment(CommentParser::new(self.collects_text()));
+                        return Step::More;
+                    }
+                    #[cfg(feature = "parse-trailing-comment")]
+                    b';' => {
+                        self.state = self.trailing_comment();
+                        return Step::More;
+                    }
+                    // a bracket closed after the expression it belongs to.
+                    #[cfg(feature = "parse-expressions")]
+                    b']' => {
+                        return Step::Take(Err(Error::InvalidExpression(
+                            ExpressionError::UnbalancedBrackets,
+                        )))
+                    }
+                    _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                },
+                State::Word(letter, value) => {
+                    let letter = *letter;
+                    let step = value.feed(b);
+                    if !matches!(step, Step::More) {
+                        self.state = State::Segment;
+                    }
+                    return step.map(|value| GCode::Word(letter, value));
+                }
+                #[cfg(feature = "parse-o-words")]
+                State::OCode(code) => {
+                    let step = code.feed(b);
+                    if !matches!(step, Step::More) {
+                        self.state = State::Segment;
+                    }
+                    return step.map(|(label, code)| GCode::OCode(label, code));
+                }
+                #[cfg(feature = "parse-parameters")]
+                State::ParameterSet(set) => {
+                    let step = set.feed(b);
+                    if !matches!(step, Step::More) {
+                        self.state = State::Segment;
+                    }
+                    return step;
+                }
+                #[cfg(feature = "parse-checksum")]
+                State::Checksum(sum, number) => {
+                    if b == b' ' && number.is_empty() {
+                        return Step::More;
+                    }
+                    let sum = *sum;
+                    let ((n, _), _) = try_step!(number.feed(b));
+                    // println!("{} {}", sum, n);
+                    if n > u32::from(C::MAX) {
+                        return Step::Leave(Err(Error::NumberOverflow));
+                    } else if n != u32::from(sum) {
+                        let (line_number, numbered) = self.line_number;
+                        return Step::Leave(Err(Error::BadChecksum {
+                            received: n as u16,
+                            computed: sum,
+                            line_number,
+                            numbered,
+                        }));
+                    }
+                    self.checksum = Some(sum);
+                    #[cfg(not(feature = "parse-trailing-comment"))]
+                    {
+                        self.state = State::EndOfLine;
+                    }
+                    #[cfg(feature = "parse-trailing-comment")]
+                    {
+                        self.state = State::EoLOrTrailingComment;
+                    }
+                }
+                State::Comment(comment) => {
+                    let (gcode, consumed) = try_step!(comment.feed(b));
+                    self.state = State::Segment;
+                    match gcode {
+                        Some(gcode) => return Step::done(Ok(gcode), consumed),
+                        None if consumed => return Step::More,
+                        None => {}
+                    }
+                }
+                #[cfg(feature = "parse-trailing-comment")]
+                State::TrailingComment(comment) => {
+                    let (gcode, consumed) = try_step!(comment.feed(b));
+                    self.state = State::EndOfLine;
+                    match gcode {
+                        Some(gcode) => return Step::done(Ok(gcode), consumed),
+                        None if consumed => return Step::More,
+                        None => {}
+                    }
+                }
+                #[cfg(all(feature = "parse-trailing-comment", feature = "parse-checksum"))]
+                State::EoLOrTrailingComment => match b {
+                    b' ' => return Step::More,
+                    b';' => {
+                        self.state = self.trailing_comment();
+                        return Step::More;
+                    }
+                    _ => self.state = State::EndOfLine,
+                },
+                #[cfg(any(feature = "parse-trailing-comment", feature = "parse-checksum"))]
+                State::EndOfLine => match b {
+                    b'\r' | b'\n' => return self.execute(),
+                    b' ' => return Step::More,
+                    _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                },
+                State::ErrorRecovery => match b {
+                    b'\r' | b'\n' => return self.execute(),
+                    #[cfg(feature = "parse-checksum")]
+                    b'*' => {
+                        self.framed = true;
+                        return Step::More;
+                    }
+                    _ => return Step::More,
+                },
+            }
+        }
+    }
+
+    fn execute(&mut self) -> Step<GCode> {
+        self.state = State::Start(true);
+        Step::Take(Ok(GCode::Execute))
+    }
+
+    #[cfg(feature = "parse-trailing-comment")]
+    fn trailing_comment(&self) -> State {
+        State::TrailingComment(TrailingCommentParser(CommentParser::new(
+            self.collects_text(),
+        )))
+    }
+
+    fn collects_text(&self) -> bool {
+        #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+        return self.collect_text;
+        #[cfg(not(any(feature = "parse-comments", feature = "string-value")))]
+        true
+    }
+
+    fn value(&self) -> ValueParser {
+        #[cfg(feature = "string-value")]
+        if !self.collect_text {
+            return ValueParser::borrowing(self.limits);
+        }
+        ValueParser::new(self.limits)
+    }
+}
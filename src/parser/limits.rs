@@ -0,0 +1,57 @@
+/// Bounds on the numbers accepted by the parser.
+///
+/// ```
+/// use async_gcode::{Error, GCode, Limits, SyncParser};
+///
+/// let limits = Limits {
+///     integer_digits: 3,
+///     fraction_digits: 2,
+///     ..Limits::default()
+/// };
+/// let input = "X1.2345 Y1234\n".bytes().map(Result::<_, Error>::Ok);
+/// let mut parser = SyncParser::with_limits(input, limits);
+/// assert_eq!(parser.next(), Some(Ok(GCode::Word('x', 1.23.into()))));
+/// assert_eq!(parser.next(), Some(Err(Error::NumberOverflow)));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Limits {
+    /// Maximum number of digits before the decimal point, leading zeros included. Longer
+    /// integer parts are reported as [`Error::NumberOverflow`](crate::Error::NumberOverflow), so
+    /// are values exceeding `u32::MAX`.
+    pub integer_digits: u8,
+    /// Number of digits kept after the decimal point, the value is rounded half up on the next
+    /// one and the following ones are ignored. Values greater than 9 are treated as 9.
+    pub fraction_digits: u8,
+    /// Maximum number of digits of a line number, leading zeros included. RS274/NGC allows 5,
+    /// RepRap hosts number their lines past that and need up to 10. Longer line numbers are
+    /// reported as [`Error::NumberOverflow`](crate::Error::NumberOverflow), so are values
+    /// exceeding `u32::MAX`.
+    pub line_number_digits: u8,
+    /// Maximum nesting of the brackets of an expression, deeper ones are reported as
+    /// `ExpressionError::TooDeep`. Only used by `parse-expressions`.
+    pub expression_depth: u8,
+    /// Maximum number of operands, operators and pending brackets held while parsing an
+    /// expression, bounding its memory. Only used by `parse-expressions`.
+    pub expression_length: u16,
+    /// Maximum number of characters of a parameter's or a subroutine's name, whitespaces
+    /// excluded. Longer names are reported as `Error::NameTooLong`. Only used by
+    /// `parse-parameters`.
+    pub name_length: u8,
+}
+
+impl Limits {
+    pub(crate) const MAX_FRACTION_DIGITS: u8 = 9;
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            integer_digits: 10,
+            fraction_digits: Self::MAX_FRACTION_DIGITS,
+            line_number_digits: 5,
+            expression_depth: 16,
+            expression_length: 128,
+            name_length: 32,
+        }
+    }
+}
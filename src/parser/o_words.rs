@@ -0,0 +1,168 @@
+//! LinuxCNC's O-codes: a label, `O100` or `O<name>`, followed by a keyword and its arguments.
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use super::{
+    expressions::ValueParser,
+    grammar::Step,
+    values::{NameParser, NumberParser},
+    Limits,
+};
+use crate::{
+    types::{OCode, OWord, RealValue},
+    Error,
+};
+
+/// The longest keyword, `endrepeat`, has 9 letters.
+const MAX_KEYWORD_LEN: usize = 9;
+
+/// The keywords followed by bracketed expressions.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Keyword {
+    EndSub,
+    Call,
+    Return,
+    If,
+    ElseIf,
+    While,
+    Repeat,
+}
+
+#[derive(Debug)]
+enum Phase {
+    /// Spaces may precede the label.
+    Label,
+    Name(NameParser),
+    Number(NumberParser),
+    /// Spaces may precede the keyword.
+    Keyword([u8; MAX_KEYWORD_LEN], usize),
+    /// Spaces may precede each argument.
+    Argument(Keyword),
+    Value(Keyword, ValueParser),
+}
+
+/// Reads an O-code following its `O`.
+#[derive(Debug)]
+pub(crate) struct OCodeParser {
+    limits: Limits,
+    phase: Phase,
+    label: OWord,
+    arguments: Vec<RealValue>,
+}
+
+impl OCodeParser {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            phase: Phase::Label,
+            label: OWord::Number(0),
+            arguments: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Step<(OWord, OCode)> {
+        loop {
+            match &mut self.phase {
+                Phase::Label => match b {
+                    b' ' => return Step::More,
+                    b'<' => {
+                        self.phase = Phase::Name(NameParser::new(self.limits));
+                        return Step::More;
+                    }
+                    _ => self.phase = Phase::Number(NumberParser::new(self.limits.integer_digits)),
+                },
+                Phase::Name(name) => {
+                    let (name, _) = try_step!(name.feed(b));
+                    self.label = OWord::Name(name);
+                    self.phase = Phase::Keyword([0; MAX_KEYWORD_LEN], 0);
+                    return Step::More;
+                }
+                Phase::Number(number) => {
+                    let ((n, digits), _) = try_step!(number.feed(b));
+                    if digits == 0 {
+                        return Step::Take(Err(Error::UnexpectedByte(b)));
+                    }
+                    self.label = OWord::Number(n);
+                    self.phase = Phase::Keyword([0; MAX_KEYWORD_LEN], 0);
+                }
+                Phase::Keyword(letters, len) => {
+                    if b == b' ' && *len == 0 {
+                        return Step::More;
+                    } else if b.is_ascii_alphabetic() {
+                        match letters.get_mut(*len) {
+                            Some(letter) => *letter = b.to_ascii_lowercase(),
+                            None => return Step::Take(Err(Error::UnknownCode('o'))),
+                        }
+                        *len += 1;
+                        return Step::More;
+                    }
+                    let keyword = match &letters[..*len] {
+                        [] => return Step::Take(Err(Error::UnexpectedByte(b))),
+                        b"sub" => return Step::Leave(Ok(self.code(OCode::Sub))),
+                        b"endsub" => Keyword::EndSub,
+                        b"call" => Keyword::Call,
+                        b"return" => Keyword::Return,
+                        b"if" => Keyword::If,
+                        b"elseif" => Keyword::ElseIf,
+                        b"else" => return Step::Leave(Ok(self.code(OCode::Else))),
+                        b"endif" => return Step::Leave(Ok(self.code(OCode::EndIf))),
+                        b"while" => Keyword::While,
+                        b"endwhile" => return Step::Leave(Ok(self.code(OCode::EndWhile))),
+                        b"do" => return Step::Leave(Ok(self.code(OCode::Do))),
+                        b"repeat" => Keyword::Repeat,
+                        b"endrepeat" => return Step::Leave(Ok(self.code(OCode::EndRepeat))),
+                        b"break" => return Step::Leave(Ok(self.code(OCode::Break))),
+                        b"continue" => return Step::Leave(Ok(self.code(OCode::Continue))),
+                        _ => return Step::Leave(Err(Error::UnknownCode('o'))),
+                    };
+                    self.phase = Phase::Argument(keyword);
+                }
+                Phase::Argument(keyword) => match b {
+                    b' ' => return Step::More,
+                    b'[' => self.phase = Phase::Value(*keyword, ValueParser::new(self.limits)),
+                    // conditions are required.
+                    _ => {
+                        let keyword = *keyword;
+                        return match self.finish(keyword) {
+                            Some(code) => Step::Leave(Ok(code)),
+                            None => Step::Take(Err(Error::UnexpectedByte(b))),
+                        };
+                    }
+                },
+                Phase::Value(keyword, value) => {
+                    let keyword = *keyword;
+                    let (value, consumed) = try_step!(value.feed(b));
+                    self.arguments.push(value);
+                    if keyword == Keyword::Call {
+                        self.phase = Phase::Argument(keyword);
+                    } else if let Some(code) = self.finish(keyword) {
+                        return Step::done(Ok(code), consumed);
+                    }
+                    if consumed {
+                        return Step::More;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Builds the code of `keyword` from the arguments read, `None` if its condition is missing.
+    fn finish(&mut self, keyword: Keyword) -> Option<(OWord, OCode)> {
+        let mut arguments = core::mem::take(&mut self.arguments);
+        let code = match keyword {
+            Keyword::EndSub => OCode::EndSub(arguments.pop()),
+            Keyword::Call => OCode::Call(arguments),
+            Keyword::Return => OCode::Return(arguments.pop()),
+            Keyword::If => OCode::If(arguments.pop()?),
+            Keyword::ElseIf => OCode::ElseIf(arguments.pop()?),
+            Keyword::While => OCode::While(arguments.pop()?),
+            Keyword::Repeat => OCode::Repeat(arguments.pop()?),
+        };
+        Some(self.code(code))
+    }
+
+    fn code(&mut self, code: OCode) -> (OWord, OCode) {
+        (core::mem::replace(&mut self.label, OWord::Number(0)), code)
+    }
+}
@@ -0,0 +1,253 @@
+use core::ops::Range;
+
+use super::{
+    grammar::{Grammar, Step},
+    Limits,
+};
+use crate::{Error, GCode, RealValue, XorSum};
+
+#[cfg(feature = "string-value")]
+use crate::Literal;
+#[cfg(feature = "parse-o-words")]
+use crate::{OCode, OWord};
+
+/// A `GCode` borrowing its text from the slice it was read from.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SliceGCode<'a> {
+    BlockDelete,
+    LineNumber(u32),
+    /// Only produced when `parse-comments` is enabled.
+    Comment(&'a str),
+    Word(char, RealValue),
+    #[cfg(feature = "string-value")]
+    /// A word whose value is a string literal. The string is borrowed as written between the
+    /// quotes: escape sequences are left as is.
+    Text(char, &'a str),
+    #[cfg(feature = "parse-parameters")]
+    ParameterSet(RealValue, RealValue),
+    #[cfg(feature = "parse-o-words")]
+    OCode(OWord, OCode),
+    Execute,
+}
+
+/// A parser for programs held in memory.
+///
+/// It shares its grammar with [`Parser`](super::Parser) but reports, along with each event, the range of the
+/// slice it was read from. Comments and string literals are borrowed from the slice.
+///
+/// ```
+/// use async_gcode::{SliceGCode, SliceParser};
+///
+/// let mut parser = SliceParser::new(b"G1\n");
+/// assert_eq!(parser.next(), Some((0..2, Ok(SliceGCode::Word('g', 1.into())))));
+/// assert_eq!(parser.next(), Some((2..3, Ok(SliceGCode::Execute))));
+/// ```
+pub struct SliceParser<'a> {
+    bytes: &'a [u8],
+    /// Number of bytes consumed so far.
+    offset: usize,
+    grammar: Grammar,
+}
+
+impl<'a> SliceParser<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_limits(bytes, Limits::default())
+    }
+
+    /// See [`Parser::with_limits`](super::Parser::with_limits).
+    pub fn with_limits(bytes: &'a [u8], limits: Limits) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            grammar: Self::grammar(limits),
+        }
+    }
+
+    /// Number of bytes read so far. Following a `SliceGCode::Execute`, this is where the next
+    /// line starts.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Resumes parsing from `offset`, which should be the start of a line.
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset.min(self.bytes.len());
+        self.grammar = Self::grammar(self.grammar.limits());
+    }
+
+    /// Reads the next event without borrowing it from the slice.
+    #[cfg(feature = "parse-o-words")]
+    pub(crate) fn next_gcode(&mut self) -> Option<Result<GCode, Error>> {
+        self.grammar.collect_text(true);
+        let res = self.scan();
+        self.grammar.collect_text(false);
+        res.map(|(_, res)| res)
+    }
+
+    /// The text is sliced out of the input rather than collected.
+    fn grammar(limits: Limits) -> Grammar {
+        let mut grammar = Grammar::new(limits, XorSum::default());
+        grammar.collect_text(false);
+        grammar
+    }
+
+    /// Feeds the grammar up to its next event, which is read from the returned range.
+    fn scan(&mut self) -> Option<(Range<usize>, Result<GCode, Error>)> {
+        // the bytes skipped before the event are left out of its range.
+        let mut start = None;
+        loop {
+            let &b = self.bytes.get(self.offset)?;
+            if start.is_none() && !self.grammar.skips(b) {
+                start = Some(self.offset);
+            }
+            let res = match self.grammar.feed(b) {
+                Step::More => {
+                    self.offset += 1;
+                    continue;
+                }
+                Step::Take(res) => {
+                    self.offset += 1;
+                    res
+                }
+                Step::Leave(res) => res,
+            };
+            return Some((start.unwrap_or(self.offset)..self.offset, res));
+        }
+    }
+
+    #[cfg_attr(
+        not(any(feature = "parse-comments", feature = "string-value")),
+        allow(unused_variables)
+    )]
+    fn borrow(gcode: GCode, text: &'a [u8]) -> Result<SliceGCode<'a>, Error> {
+        #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+        let as_str = |bytes| core::str::from_utf8(bytes).map_err(|_| Error::InvalidUTF8String);
+        Ok(match gcode {
+            GCode::BlockDelete => SliceGCode::BlockDelete,
+            GCode::LineNumber(n) => SliceGCode::LineNumber(n),
+            // inline comments are enclosed in brackets, trailing comments start with `;`.
+            #[cfg(feature = "parse-comments")]
+            GCode::Comment(_) => SliceGCode::Comment(as_str(match text {
+                [b'(', comment @ .., b')'] => comment,
+                [_, comment @ ..] => comment,
+                [] => text,
+            })?),
+            #[cfg(feature = "string-value")]
+            GCode::Word(letter, RealValue::Literal(Literal::String(_))) => {
+                let first = text.iter().position(|&b| b == b'"').unwrap_or_default();
+                let last = text.iter().rposition(|&b| b == b'"').unwrap_or_default();
+                SliceGCode::Text(letter, as_str(&text[(first + 1).min(last)..last])?)
+            }
+            GCode::Word(letter, value) => SliceGCode::Word(letter, value),
+            #[cfg(feature = "parse-parameters")]
+            GCode::ParameterSet(index, value) => SliceGCode::ParameterSet(index, value),
+            #[cfg(feature = "parse-o-words")]
+            GCode::OCode(label, code) => SliceGCode::OCode(label, code),
+            GCode::Execute => SliceGCode::Execute,
+        })
+    }
+}
+
+impl<'a> Iterator for SliceParser<'a> {
+    type Item = (Range<usize>, Result<SliceGCode<'a>, Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (range, res) = self.scan()?;
+        let res = res.and_then(|gcode| Self::borrow(gcode, &self.bytes[range.clone()]));
+        if res.is_err() {
+            self.grammar.recover();
+        }
+        Some((range, res))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SliceGCode, SliceParser};
+    use crate::{Error, GCode, SyncParser};
+
+    #[test]
+    fn produces_the_same_events_as_the_parser() {
+        let input = "N3 G0  X1.5\n/G$ 2\nG1\n";
+        let expected: Vec<_> = SyncParser::new(input.bytes().map(Result::<_, Error>::Ok))
+            .map(|res| {
+                res.map(|gcode| match gcode {
+                    GCode::BlockDelete => SliceGCode::BlockDelete,
+                    GCode::LineNumber(n) => SliceGCode::LineNumber(n),
+                    GCode::Word(l, v) => SliceGCode::Word(l, v),
+                    GCode::Execute => SliceGCode::Execute,
+                    #[allow(unreachable_patterns)]
+                    gcode => panic!("unexpected {:?}", gcode),
+                })
+            })
+            .collect();
+        let events: Vec<_> = SliceParser::new(input.as_bytes())
+            .map(|(_, res)| res)
+            .collect();
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn reports_the_range_of_each_event() {
+        let input = b"N3 G0  X1.5\n/G1 $ 2\n";
+        let ranges: Vec<_> = SliceParser::new(input)
+            .map(|(range, _)| &input[range])
+            .collect();
+        assert_eq!(
+            ranges,
+            [
+                &b"N3"[..],
+                b"G0  ",
+                b"X1.5",
+                b"\n",
+                b"/",
+                b"G1 ",
+                b"$",
+                b"\n"
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "parse-comments")]
+    fn comments_are_borrowed() {
+        let input = "G0 (inline) X1 \n";
+        let mut parser = SliceParser::new(input.as_bytes());
+        assert_eq!(
+            parser.nth(1),
+            Some((3..11, Ok(SliceGCode::Comment("inline"))))
+        );
+        match parser.next() {
+            Some((_, Ok(SliceGCode::Word('x', _)))) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "parse-comments")]
+    fn borrowed_comments_must_be_utf8() {
+        let mut parser = SliceParser::new(b"(\xff) G1\n");
+        assert_eq!(parser.next(), Some((0..3, Err(Error::InvalidUTF8String))));
+        assert_eq!(parser.next(), Some((6..7, Ok(SliceGCode::Execute))));
+    }
+
+    #[test]
+    #[cfg(all(feature = "parse-comments", feature = "parse-trailing-comment"))]
+    fn trailing_comments_are_borrowed() {
+        let mut parser = SliceParser::new(b"G0 ;trailing\n");
+        assert_eq!(
+            parser.nth(1),
+            Some((3..12, Ok(SliceGCode::Comment("trailing"))))
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "string-value")]
+    fn strings_are_borrowed_as_written() {
+        let mut parser = SliceParser::new(br#"P "say \"hi\"" "#);
+        assert_eq!(
+            parser.next(),
+            Some((0..14, Ok(SliceGCode::Text('p', r#"say \"hi\""#))))
+        );
+    }
+}
@@ -0,0 +1,73 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use pin_project_lite::pin_project;
+
+use super::Parser;
+use crate::{Checksum, Error, GCode, XorSum};
+
+pin_project! {
+    /// A [`Parser`] implementing `Stream`, obtained from [`Parser::into_stream`].
+    ///
+    /// Polling the stream polls the parser, which keeps its state between two items.
+    pub struct ParserStream<S, E, C = XorSum>
+    where
+        S: Stream<Item = Result<u8, E>>,
+        S: Unpin,
+    {
+        parser: Option<Parser<S, E, C>>,
+    }
+}
+
+impl<S, E, C> Parser<S, E, C>
+where
+    S: Stream<Item = Result<u8, E>> + Unpin,
+    E: From<Error>,
+    C: Checksum,
+{
+    /// Turns the parser into a `Stream` of `GCode`s.
+    ///
+    /// ```
+    /// use futures::{pin_mut, stream, StreamExt};
+    /// use async_gcode::{Error, GCode, Parser};
+    ///
+    /// let input = stream::iter("G1 X2\n".bytes().map(Result::<_, Error>::Ok));
+    /// let gcodes = Parser::new(input).into_stream().filter_map(|res| async { res.ok() });
+    /// pin_mut!(gcodes);
+    /// futures_executor::block_on(async {
+    ///     assert_eq!(gcodes.next().await, Some(GCode::Word('g', 1.into())));
+    /// });
+    /// ```
+    pub fn into_stream(self) -> ParserStream<S, E, C> {
+        ParserStream { parser: Some(self) }
+    }
+}
+
+impl<S, E, C> ParserStream<S, E, C>
+where
+    S: Stream<Item = Result<u8, E>> + Unpin,
+{
+    /// Gives the parser back, the stream then ends.
+    pub fn take_parser(self: Pin<&mut Self>) -> Option<Parser<S, E, C>> {
+        self.project().parser.take()
+    }
+}
+
+impl<S, E, C> Stream for ParserStream<S, E, C>
+where
+    S: Stream<Item = Result<u8, E>> + Unpin,
+    E: From<Error>,
+    C: Checksum,
+{
+    type Item = Result<GCode, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.project().parser {
+            Some(parser) => parser.poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use core::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll},
+};
+
+use futures::{stream, task::noop_waker_ref};
+
+use super::{Limits, Parser};
+use crate::{
+    block::{Block, BlockStorage},
+    Error, GCode,
+};
+
+#[cfg(feature = "source-position")]
+use crate::position::{SourcePosition, Spanned};
+
+/// Runs a future polling an `Iterator` based stream.
+///
+/// Such streams are always ready so the future completes on its first poll and no executor is
+/// required.
+pub(super) fn now<F: Future>(future: F) -> F::Output {
+    let mut cx = Context::from_waker(noop_waker_ref());
+    match pin!(future).poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("an iterator based stream is never pending"),
+    }
+}
+
+/// A blocking front-end to [`Parser`] reading from an `Iterator`.
+///
+/// ```
+/// use async_gcode::{Error, GCode, SyncParser};
+///
+/// let mut parser = SyncParser::new("G1 X2\n".bytes().map(Result::<_, Error>::Ok));
+/// assert_eq!(parser.next(), Some(Ok(GCode::Word('g', 1.into()))));
+/// ```
+pub struct SyncParser<I, E>
+where
+    I: Iterator<Item = Result<u8, E>>,
+{
+    parser: Parser<stream::Iter<I>, E>,
+}
+
+impl<I, E> SyncParser<I, E>
+where
+    I: Iterator<Item = Result<u8, E>>,
+    E: From<Error>,
+{
+    pub fn new(input: I) -> Self {
+        Self::with_limits(input, Limits::default())
+    }
+
+    /// See [`Parser::with_limits`].
+    pub fn with_limits(input: I, limits: Limits) -> Self {
+        Self {
+            parser: Parser::with_limits(stream::iter(input), limits),
+        }
+    }
+
+    /// See [`Parser::next_block`].
+    pub fn next_block<B: BlockStorage>(&mut self, block: &mut Block<B>) -> Option<Result<(), E>> {
+        now(self.parser.next_block(block))
+    }
+
+    /// See [`Parser::position`].
+    #[cfg(feature = "source-position")]
+    pub fn position(&self) -> SourcePosition {
+        self.parser.position()
+    }
+
+    /// See [`Parser::next_spanned`].
+    #[cfg(feature = "source-position")]
+    pub fn next_spanned(&mut self) -> Option<Spanned<Result<GCode, E>>> {
+        now(self.parser.next_spanned())
+    }
+}
+
+impl<I, E> Iterator for SyncParser<I, E>
+where
+    I: Iterator<Item = Result<u8, E>>,
+    E: From<Error>,
+{
+    type Item = Result<GCode, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        now(self.parser.next())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SyncParser;
+    use crate::{Block, Bounded, Error, GCode};
+
+    #[test]
+    fn produces_the_same_events_as_the_async_parser() {
+        let input = "N3 G0 X1.5\n/G$\nG1\n";
+        let parser = crate::Parser::new(futures::stream::iter(
+            input.bytes().map(Result::<_, Error>::Ok),
+        ));
+        let expected: Vec<_> =
+            futures_executor::block_on(futures::StreamExt::collect(parser.into_stream()));
+
+        let events: Vec<_> = SyncParser::new(input.bytes().map(Result::<_, Error>::Ok)).collect();
+        assert_eq!(events, expected);
+        assert_eq!(events[1], Ok(GCode::Word('g', 0.into())));
+    }
+
+    #[test]
+    fn reads_blocks() {
+        let mut parser = SyncParser::new("G0 X1\nG1 Y2\n".bytes().map(Result::<_, Error>::Ok));
+        let mut block = Block::<Bounded<2>>::new();
+        assert_eq!(parser.next_block(&mut block), Some(Ok(())));
+        assert_eq!(parser.next_block(&mut block), Some(Ok(())));
+        assert_eq!(block.word('y'), Some(&2.into()));
+        assert_eq!(parser.next_block(&mut block), None);
+    }
+}
@@ -1,29 +1,32 @@
-use futures::stream;
+use futures::{stream, StreamExt};
 
-use super::{Error, GCode, Parser, StreamExt};
+use super::{Error, GCode, Limits, Parser};
 
 #[cfg(feature = "optional-value")]
 use crate::types::RealValue;
 
+mod parse_blocks;
 #[cfg(feature = "parse-checksum")]
 mod parse_checksum;
+#[cfg(feature = "decimal")]
+mod parse_decimal;
 #[cfg(feature = "parse-expressions")]
 mod parse_expressions;
+#[cfg(all(feature = "f32", not(feature = "decimal")))]
+mod parse_f32;
+#[cfg(feature = "parse-o-words")]
+mod parse_o_words;
 #[cfg(feature = "parse-parameters")]
 mod parse_parameters;
+#[cfg(feature = "source-position")]
+mod parse_positions;
 #[cfg(feature = "parse-trailing-comment")]
 mod parse_trailing_comment;
 
 fn block_on<T: Iterator<Item = u8>>(it: T) -> Vec<Result<GCode, Error>> {
-    let mut parser = Parser::new(stream::iter(it).map(Result::<_, Error>::Ok));
-
-    futures_executor::block_on(
-        stream::unfold(
-            &mut parser,
-            |p| async move { p.next().await.map(|w| (w, p)) },
-        )
-        .collect(),
-    )
+    let parser = Parser::new(stream::iter(it).map(Result::<_, Error>::Ok));
+
+    futures_executor::block_on(parser.into_stream().collect())
 }
 #[cfg(not(feature = "parse-comments"))]
 fn to_gcode_comment(_msg: &str) -> [Result<GCode, Error>; 0] {
@@ -151,7 +154,10 @@ fn line_number_can_only_be_intergers() {
 #[test]
 fn line_number_must_have_a_value() {
     let input = "N\n".bytes();
-    assert_eq!(block_on(input), &[Err(Error::UnexpectedByte(b'\n'))]);
+    assert_eq!(
+        block_on(input),
+        &[Err(Error::UnexpectedByte(b'\n')), Ok(GCode::Execute)]
+    );
 }
 
 #[test]
@@ -211,6 +217,76 @@ fn word_with_number() {
     );
 }
 
+#[test]
+fn numbers_exceeding_u32_overflow() {
+    let input = "X12345678901 Y4294967296\nX4294967295\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Err(Error::NumberOverflow),
+            Ok(GCode::Execute),
+            Ok(GCode::Word('x', 4294967295.0.into())),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn long_fractional_parts_are_rounded() {
+    let input = "X0.12345678949 Y1.99999999951 Z.000000000000000000000000000000001\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Ok(GCode::Word('x', 0.123456789.into())),
+            Ok(GCode::Word('y', 2.0.into())),
+            Ok(GCode::Word('z', 0.0.into())),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn digits_are_bounded_by_the_limits() {
+    let limits = Limits {
+        integer_digits: 2,
+        fraction_digits: 1,
+        ..Limits::default()
+    };
+    let input = "X-12.34 Y0.05\nZ123\n".bytes().map(Result::<_, Error>::Ok);
+    let parser = Parser::with_limits(stream::iter(input), limits);
+    assert_eq!(
+        futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+        &[
+            Ok(GCode::Word('x', (-12.3).into())),
+            Ok(GCode::Word('y', 0.1.into())),
+            Ok(GCode::Execute),
+            Err(Error::NumberOverflow),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn line_numbers_may_fit_in_a_u32_within_the_limits() {
+    let limits = Limits {
+        line_number_digits: 10,
+        ..Limits::default()
+    };
+    let input = "N4294967295\nN4294967296\n"
+        .bytes()
+        .map(Result::<_, Error>::Ok);
+    let parser = Parser::with_limits(stream::iter(input), limits);
+    assert_eq!(
+        futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+        &[
+            Ok(GCode::LineNumber(u32::MAX)),
+            Ok(GCode::Execute),
+            Err(Error::NumberOverflow),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
 #[test]
 #[cfg(all(feature = "optional-value", not(feature = "parse-expressions")))]
 fn word_may_not_have_a_value() {
@@ -254,3 +330,37 @@ fn value_may_be_strings() {
         &[Ok(GCode::Word('g', "Hello\"World\"".to_string().into()))]
     )
 }
+
+#[test]
+fn the_parser_composes_with_stream_combinators() {
+    let parser = Parser::new(stream::iter("G0 G1\nG2".bytes()).map(Result::<_, Error>::Ok));
+    let gcodes = parser.into_stream();
+    futures::pin_mut!(gcodes);
+    assert_eq!(
+        futures_executor::block_on(gcodes.as_mut().take(2).collect::<Vec<_>>()),
+        [
+            Ok(GCode::Word('g', 0.into())),
+            Ok(GCode::Word('g', 1.into()))
+        ]
+    );
+
+    let mut parser = gcodes.as_mut().take_parser().unwrap();
+    assert_eq!(
+        futures_executor::block_on(parser.next()),
+        Some(Ok(GCode::Execute))
+    );
+    assert_eq!(futures_executor::block_on(gcodes.next()), None);
+}
+
+#[test]
+fn the_input_is_not_polled_once_ended() {
+    let mut ended = false;
+    let input = stream::poll_fn(move |_| {
+        assert!(!ended, "polled after its end");
+        ended = true;
+        core::task::Poll::Ready(None::<Result<u8, Error>>)
+    });
+    let mut parser = Parser::new(input);
+    assert_eq!(futures_executor::block_on(parser.next()), None);
+    assert_eq!(futures_executor::block_on(parser.next()), None);
+}
@@ -0,0 +1,93 @@
+use futures::stream;
+
+use super::{Error, Parser, StreamExt};
+use crate::{Block, BlockStorage, Bounded, RealValue, Unbounded};
+
+fn blocks<B: BlockStorage>(input: &str) -> Vec<Result<Vec<(char, RealValue)>, Error>> {
+    let mut parser = Parser::new(stream::iter(input.bytes()).map(Result::<_, Error>::Ok));
+    let mut block = Block::<B>::new();
+    let mut res = Vec::new();
+    futures_executor::block_on(async {
+        while let Some(r) = parser.next_block(&mut block).await {
+            res.push(r.map(|_| block.words.to_vec()));
+        }
+    });
+    res
+}
+
+#[test]
+fn a_block_gathers_the_whole_line() {
+    let mut parser =
+        Parser::new(stream::iter("/N10 G1 X2 (comment) Y-3\n".bytes()).map(Result::<_, Error>::Ok));
+    let mut block = Block::<Unbounded>::new();
+    futures_executor::block_on(async {
+        assert_eq!(parser.next_block(&mut block).await, Some(Ok(())));
+        assert!(block.block_delete);
+        assert_eq!(block.line_number, Some(10));
+        assert_eq!(
+            &block.words[..],
+            &[('g', 1.into()), ('x', 2.into()), ('y', (-3).into())]
+        );
+        assert_eq!(block.word('x'), Some(&2.into()));
+        #[cfg(feature = "parse-comments")]
+        assert_eq!(&block.comments[..], &["comment".to_string()]);
+
+        assert_eq!(parser.next_block(&mut block).await, None);
+    });
+}
+
+#[test]
+fn errors_are_reported_per_line() {
+    assert_eq!(
+        blocks::<Unbounded>("G1 $ X2\nG0 X1\n\n"),
+        [
+            Err(Error::UnexpectedByte(b'$')),
+            Ok(vec![('g', 0.into()), ('x', 1.into())]),
+            Ok(vec![])
+        ]
+    );
+}
+
+#[test]
+fn unterminated_last_line_is_returned() {
+    assert_eq!(
+        blocks::<Unbounded>("G0\nG1.0 "),
+        [Ok(vec![('g', 0.into())]), Ok(vec![('g', 1.into())])]
+    );
+}
+
+#[test]
+fn bounded_storage_reports_overflows() {
+    assert_eq!(
+        blocks::<Bounded<2>>("G1 X1 Y1\nG0 X2\n"),
+        [
+            Err(Error::BlockOverflow),
+            Ok(vec![('g', 0.into()), ('x', 2.into())])
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "parse-checksum")]
+fn the_checksum_is_kept() {
+    let mut parser = Parser::new(stream::iter("G0*119\nG0\n".bytes()).map(Result::<_, Error>::Ok));
+    let mut block = Block::<Bounded<4>>::new();
+    futures_executor::block_on(async {
+        assert_eq!(parser.next_block(&mut block).await, Some(Ok(())));
+        assert_eq!(block.checksum, Some(119));
+        assert_eq!(parser.next_block(&mut block).await, Some(Ok(())));
+        assert_eq!(block.checksum, None);
+    });
+}
+
+#[test]
+#[cfg(feature = "parse-parameters")]
+fn parameter_settings_are_gathered() {
+    let mut parser = Parser::new(stream::iter("#1=2 G#1\n".bytes()).map(Result::<_, Error>::Ok));
+    let mut block = Block::<Unbounded>::new();
+    futures_executor::block_on(async {
+        assert_eq!(parser.next_block(&mut block).await, Some(Ok(())));
+        assert_eq!(&block.parameter_sets[..], &[(1.into(), 2.into())]);
+        assert_eq!(block.words.len(), 1);
+    });
+}
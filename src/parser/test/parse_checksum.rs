@@ -1,6 +1,13 @@
 #[cfg(feature = "parse-trailing-comment")]
 use super::to_gcode_comment;
-use super::{block_on, Error, GCode};
+use super::{block_on, stream, Error, GCode, Limits, Parser, StreamExt};
+use crate::{Checksum, Crc16, Crc8};
+
+fn block_on_with<C: Checksum>(input: &str, checksum: C) -> Vec<Result<GCode, Error>> {
+    let input = stream::iter(input.bytes().map(Result::<_, Error>::Ok));
+    let parser = Parser::with_checksum(input, Limits::default(), checksum);
+    futures_executor::block_on(parser.into_stream().collect())
+}
 
 #[test]
 fn parse_checksum_from_start_to_checksum() {
@@ -15,7 +22,12 @@ fn parse_checksum_from_start_to_checksum() {
         block_on(input),
         &[
             Ok(GCode::Word('g', (0.).into())),
-            Err(Error::BadChecksum(119)),
+            Err(Error::BadChecksum {
+                received: 118,
+                computed: 119,
+                line_number: None,
+                numbered: false
+            }),
             Ok(GCode::Execute)
         ]
     );
@@ -57,3 +69,67 @@ fn trailing_comment_are_not_covered_by_checksum() {
     expected_output.push(Ok(GCode::Execute));
     assert_eq!(block_on(input), expected_output)
 }
+
+#[test]
+fn crcs_can_be_used_instead_of_the_xorsum() {
+    assert_eq!(
+        block_on_with("N1 G28*14787\nG1 X2*10796\n", Crc16::default()),
+        &[
+            Ok(GCode::LineNumber(1)),
+            Ok(GCode::Word('g', (28.).into())),
+            Ok(GCode::Execute),
+            Ok(GCode::Word('g', (1.).into())),
+            Ok(GCode::Word('x', (2.).into())),
+            Ok(GCode::Execute),
+        ]
+    );
+    assert_eq!(
+        block_on_with("G0*161\n", Crc8::default()),
+        &[
+            Ok(GCode::Word('g', (0.).into())),
+            Err(Error::BadChecksum {
+                received: 161,
+                computed: 160,
+                line_number: None,
+                numbered: false
+            }),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn checksums_are_bounded_by_their_width() {
+    assert_eq!(block_on("G0*256\n".bytes())[1], Err(Error::NumberOverflow));
+    assert_eq!(
+        block_on_with("G0*65536\n", Crc16::default())[1],
+        Err(Error::NumberOverflow)
+    );
+    assert_eq!(
+        block_on_with("G0*123456\n", Crc16::default())[1],
+        Err(Error::NumberOverflow)
+    );
+}
+
+#[test]
+fn bad_checksums_report_the_line_number_in_effect() {
+    let res = block_on("N7 G0*1\nG1*1\n".bytes());
+    assert_eq!(
+        res[2],
+        Err(Error::BadChecksum {
+            received: 1,
+            computed: 46,
+            line_number: Some(7),
+            numbered: true
+        })
+    );
+    assert_eq!(
+        res[5],
+        Err(Error::BadChecksum {
+            received: 1,
+            computed: 118,
+            line_number: Some(7),
+            numbered: false
+        })
+    );
+}
@@ -0,0 +1,28 @@
+use super::{block_on, GCode};
+use crate::{Decimal, Literal, RealValue};
+
+fn decimals(input: &str) -> Vec<Decimal> {
+    block_on(input.bytes())
+        .into_iter()
+        .filter_map(|gcode| match gcode {
+            Ok(GCode::Word(_, RealValue::Literal(Literal::RealNumber(value)))) => Some(value),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn numbers_keep_their_digits() {
+    let values = decimals("X1.50 Y-.005 Z12 A0.1\n");
+    let digits: Vec<_> = values.iter().map(|d| (d.mantissa(), d.scale())).collect();
+    assert_eq!(digits, [(150, 2), (-5, 3), (12, 0), (1, 1)]);
+    assert_eq!(values[3].to_f64(), 0.1);
+}
+
+#[test]
+fn rounded_fractions_keep_the_digits_limit() {
+    let values = decimals("X1.99999999951 Y4294967295.123456789\n");
+    assert_eq!(values[0].mantissa(), 2_000_000_000);
+    assert_eq!(values[0].scale(), 9);
+    assert_eq!(values[1].rescale(9), Some(4_294_967_295_123_456_789));
+}
@@ -1,9 +1,18 @@
-use super::{block_on, GCode};
-use crate::types::{
-    expressions::{Expression, Operator},
-    Literal,
+use super::{block_on, stream, Error, GCode, Limits, Parser, StreamExt};
+use crate::{
+    types::{
+        expressions::{Expression, Operator},
+        Literal,
+    },
+    ExpressionError,
 };
 
+fn block_on_with_limits(input: &str, limits: Limits) -> Vec<Result<GCode, Error>> {
+    let input = stream::iter(input.bytes().map(Result::<_, Error>::Ok));
+    let parser = Parser::with_limits(input, limits);
+    futures_executor::block_on(parser.into_stream().collect())
+}
+
 #[test]
 fn parse_sngle_real_value_expressions() {
     let input = "G[3]\n".bytes();
@@ -14,6 +23,7 @@ fn parse_sngle_real_value_expressions() {
 }
 
 #[test]
+#[allow(clippy::approx_constant)]
 fn parse_unary_as_first_expressions() {
     let input = r"
 G Cos [ 90 ] G Sin [ 90 ] G Tan [ 90 ]
@@ -118,6 +128,47 @@ fn parse_binary_operator_with_appropriate_precedence() {
     )
 }
 
+#[test]
+fn parse_comparisons_below_additions_and_logical_operators() {
+    let input = "g [ 1 + 2 GT 3 and 4 le 5 ]".bytes();
+    assert_eq!(
+        block_on(input),
+        &[Ok(GCode::Word(
+            'g',
+            Expression(vec![
+                Literal::from(1).into(),
+                Literal::from(2).into(),
+                Operator::Add.into(),
+                Literal::from(3).into(),
+                Literal::from(4).into(),
+                Operator::And.into(),
+                Operator::GreaterThan.into(),
+                Literal::from(5).into(),
+                Operator::LessOrEqual.into(),
+            ])
+            .into()
+        ))]
+    )
+}
+
+#[test]
+fn logical_operators_bind_as_tightly_as_additions() {
+    let input = "g [ 1 and 0 + 2 ]".bytes();
+    let expected = Expression(vec![
+        Literal::from(1).into(),
+        Literal::from(0).into(),
+        Operator::And.into(),
+        Literal::from(2).into(),
+        Operator::Add.into(),
+    ]);
+    #[cfg(feature = "parse-parameters")]
+    let value = expected.evaluate(&mut |_| None);
+    #[cfg(not(feature = "parse-parameters"))]
+    let value = expected.evaluate();
+    assert_eq!(value.map(|value| value.as_real_number()), Ok(Some(2.0)));
+    assert_eq!(block_on(input), &[Ok(GCode::Word('g', expected.into()))])
+}
+
 #[test]
 fn parse_addition_of_negative_numbers() {
     let input = "g [ -2 + -9 ]".bytes();
@@ -252,3 +303,115 @@ fn parse_expressions_with_parameter_get() {
         ]
     );
 }
+
+#[test]
+fn expressions_are_bounded_by_the_limits() {
+    let limits = Limits {
+        expression_depth: 2,
+        expression_length: 5,
+        ..Limits::default()
+    };
+    assert_eq!(
+        block_on_with_limits("G[[1]+2]\n", limits)[0],
+        Ok(GCode::Word(
+            'g',
+            Expression(vec![
+                Literal::from(1).into(),
+                Literal::from(2).into(),
+                Operator::Add.into()
+            ])
+            .into()
+        ))
+    );
+    assert_eq!(
+        block_on_with_limits("G[[[1]]]\n", limits)[0],
+        Err(Error::InvalidExpression(ExpressionError::TooDeep))
+    );
+    assert_eq!(
+        block_on_with_limits("G[1+2*3]\n", limits)[0],
+        Err(Error::InvalidExpression(ExpressionError::TooLong))
+    );
+}
+
+#[test]
+#[cfg(feature = "parse-min-max")]
+fn parse_min_and_max() {
+    let input = "g min[1 + 2, max [3,4]]".bytes();
+    assert_eq!(
+        block_on(input),
+        &[Ok(GCode::Word(
+            'g',
+            Expression(vec![
+                Literal::from(1).into(),
+                Literal::from(2).into(),
+                Operator::Add.into(),
+                Literal::from(3).into(),
+                Literal::from(4).into(),
+                Operator::Max.into(),
+                Operator::Min.into(),
+            ])
+            .into()
+        ))]
+    )
+}
+
+#[test]
+fn malformed_expressions_are_invalid() {
+    use ExpressionError::*;
+    for (input, reason) in [
+        ("G]\n", UnbalancedBrackets),
+        ("G[1]]\n", UnbalancedBrackets),
+        ("G[]\n", MisplacedOperator),
+        ("G[1+]\n", MisplacedOperator),
+        ("G[1 $]\n", MisplacedOperator),
+        ("G[1 2]\n", ExtraOperands),
+        ("G[1 cos[2]]\n", ExtraOperands),
+        ("G[atan[1]]\n", MisplacedOperator),
+        ("Gsin 1\n", MisplacedOperator),
+        ("G[[1]\n", UnbalancedBrackets),
+        ("G[1, 2]\n", MisplacedOperator),
+        ("Gcos[1, 2]\n", MisplacedOperator),
+    ] {
+        let res = block_on(input.bytes());
+        assert_eq!(
+            res.iter().find(|res| res.is_err()),
+            Some(&Err(Error::InvalidExpression(reason))),
+            "{}",
+            input
+        );
+        assert_eq!(res.last(), Some(&Ok(GCode::Execute)), "{}", input);
+    }
+}
+
+#[test]
+#[cfg(feature = "parse-min-max")]
+fn malformed_min_and_max_are_invalid() {
+    use ExpressionError::*;
+    for (input, reason) in [
+        ("Gmin[1]\n", MisplacedOperator),
+        ("Gmax[1, 2, 3]\n", MisplacedOperator),
+        ("Gmin[1, 2\n", UnbalancedBrackets),
+    ] {
+        assert_eq!(
+            block_on(input.bytes())[0],
+            Err(Error::InvalidExpression(reason)),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn lines_after_an_unterminated_expression_are_kept() {
+    assert_eq!(
+        block_on("G[1+\nG2\n".bytes()),
+        &[
+            Err(Error::InvalidExpression(
+                ExpressionError::UnbalancedBrackets
+            )),
+            Ok(GCode::Execute),
+            Ok(GCode::Word('g', (2.).into())),
+            Ok(GCode::Execute),
+        ]
+    );
+}
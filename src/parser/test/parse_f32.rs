@@ -0,0 +1,35 @@
+//! RS274/NGC expects lengths of up to 10^6 to be handled with a resolution of 10^-4. `f32` only
+//! has 24 bits of mantissa: the resolution is met up to 2^10 and degrades past it.
+
+use super::{block_on, GCode};
+use crate::{Literal, RealValue};
+
+fn parse(value: &str) -> f64 {
+    match block_on(format!("X{} ", value).bytes()).as_slice() {
+        [Ok(GCode::Word('x', RealValue::Literal(Literal::RealNumber(v))))] => f64::from(*v),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn four_decimals_are_kept_up_to_1024() {
+    // every 10^-4 step would take too long, use a step co-prime with 10.
+    for scaled in (0..10_240_000u32).step_by(997) {
+        let text = format!("{}.{:04}", scaled / 10_000, scaled % 10_000);
+        let exact = f64::from(scaled) / 1e4;
+        let error = (parse(&text) - exact).abs();
+        // within half a step: the value rounds back to the digits it was written with.
+        assert!(error < 0.5e-4, "{} parsed with an error of {}", text, error);
+    }
+}
+
+#[test]
+fn the_resolution_falls_short_beyond_1024() {
+    // the spacing between two f32 is 2^-7 around 10^5 and 2^-4 around 10^6. The digits do not fit
+    // in the mantissa either, the value is within a spacing rather than half of it.
+    let error = (parse("123456.7851") - 123456.7851).abs();
+    assert!(error > 0.5e-4 && error <= 2f64.powi(-7));
+
+    let error = (parse("999999.9500") - 999999.95).abs();
+    assert!(error > 0.5e-4 && error <= 2f64.powi(-4));
+}
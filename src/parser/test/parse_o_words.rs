@@ -0,0 +1,115 @@
+use super::{block_on, Error, GCode};
+use crate::{
+    types::{
+        expressions::{Expression, Operator},
+        Literal,
+    },
+    OCode, OWord,
+};
+
+#[test]
+fn parse_subroutine_definitions() {
+    let input = "O100 sub\no100 ENDSUB\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Ok(GCode::OCode(OWord::Number(100), OCode::Sub)),
+            Ok(GCode::Execute),
+            Ok(GCode::OCode(OWord::Number(100), OCode::EndSub(None))),
+            Ok(GCode::Execute),
+        ]
+    );
+}
+
+#[test]
+fn parse_calls_and_returns() {
+    let input = "N1 o<Drill Hole> call [1] [#2 + 1]\no<drillhole> return [3]\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Ok(GCode::LineNumber(1)),
+            Ok(GCode::OCode(
+                OWord::Name("drillhole".into()),
+                OCode::Call(vec![
+                    (1).into(),
+                    Expression(vec![
+                        Literal::from(2).into(),
+                        Operator::GetParameter.into(),
+                        Literal::from(1).into(),
+                        Operator::Add.into(),
+                    ])
+                    .into()
+                ])
+            )),
+            Ok(GCode::Execute),
+            Ok(GCode::OCode(
+                OWord::Name("drillhole".into()),
+                OCode::Return(Some((3).into()))
+            )),
+            Ok(GCode::Execute),
+        ]
+    );
+}
+
+#[test]
+fn parse_control_flow() {
+    let input = "o1 IF [1]\no1 elseif [0]\no1 else\no1 endif\no2 while [#1]\no2 break\no2 endwhile\no3 do\no3 continue\no3 while [1]\no4 repeat[2]\no4 endrepeat\n".bytes();
+    let o_codes: Vec<_> = block_on(input)
+        .into_iter()
+        .filter_map(|gcode| match gcode {
+            Ok(GCode::OCode(label, code)) => Some((label, code)),
+            Ok(GCode::Execute) => None,
+            gcode => panic!("unexpected {:?}", gcode),
+        })
+        .collect();
+    let condition = Expression(vec![Literal::from(1).into(), Operator::GetParameter.into()]);
+    assert_eq!(
+        o_codes,
+        &[
+            (OWord::Number(1), OCode::If((1).into())),
+            (OWord::Number(1), OCode::ElseIf((0).into())),
+            (OWord::Number(1), OCode::Else),
+            (OWord::Number(1), OCode::EndIf),
+            (OWord::Number(2), OCode::While(condition.into())),
+            (OWord::Number(2), OCode::Break),
+            (OWord::Number(2), OCode::EndWhile),
+            (OWord::Number(3), OCode::Do),
+            (OWord::Number(3), OCode::Continue),
+            (OWord::Number(3), OCode::While((1).into())),
+            (OWord::Number(4), OCode::Repeat((2).into())),
+            (OWord::Number(4), OCode::EndRepeat),
+        ]
+    );
+}
+
+#[test]
+fn conditions_are_required() {
+    assert_eq!(
+        block_on("o1 if\n".bytes())[0],
+        Err(Error::UnexpectedByte(b'\n'))
+    );
+    assert_eq!(
+        block_on("o1 repeat 2\n".bytes())[0],
+        Err(Error::UnexpectedByte(b'2'))
+    );
+}
+
+#[test]
+fn invalid_o_codes_are_reported() {
+    assert_eq!(
+        block_on("o1 jump\n".bytes())[0],
+        Err(Error::UnknownCode('o'))
+    );
+    assert_eq!(
+        block_on("o1 endrepeated\n".bytes())[0],
+        Err(Error::UnknownCode('o'))
+    );
+    assert_eq!(
+        block_on("o1\n".bytes())[0],
+        Err(Error::UnexpectedByte(b'\n'))
+    );
+    assert_eq!(
+        block_on("o sub\n".bytes())[0],
+        Err(Error::UnexpectedByte(b's'))
+    );
+}
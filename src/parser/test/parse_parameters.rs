@@ -3,13 +3,13 @@ use crate::{
         expressions::{Expression, Operator},
         Literal,
     },
-    GCode,
+    Error, GCode, ParameterName,
 };
 
 #[cfg(feature = "optional-value")]
-use crate::{types::RealValue, Error};
+use crate::types::RealValue;
 
-use super::block_on;
+use super::{block_on, stream, Limits, Parser, StreamExt};
 
 #[test]
 fn parse_param_get() {
@@ -83,7 +83,10 @@ fn parse_param_set_with_nested_param_get() {
 #[cfg(feature = "optional-value")]
 fn values_are_always_required_for_parameters_id() {
     let input = "G#\n".bytes();
-    assert_eq!(block_on(input), &[Err(Error::UnexpectedByte(b'\n'))]);
+    assert_eq!(
+        block_on(input),
+        &[Err(Error::UnexpectedByte(b'\n')), Ok(GCode::Execute)]
+    );
 
     let input = "#=".bytes();
     assert_eq!(block_on(input), &[Err(Error::UnexpectedByte(b'='))]);
@@ -133,3 +136,99 @@ fn parse_param_with_string_index() {
         ]
     );
 }
+
+#[test]
+fn parse_named_parameters() {
+    let input = "#<_Tool Radius> = #<offset>\nG#<_tool_radius>\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Ok(GCode::ParameterSet(
+                Literal::from(ParameterName::Global("toolradius".into())).into(),
+                Expression(vec![
+                    Literal::from(ParameterName::Local("offset".into())).into(),
+                    Operator::GetParameter.into()
+                ])
+                .into()
+            )),
+            Ok(GCode::Execute),
+            Ok(GCode::Word(
+                'g',
+                Expression(vec![
+                    Literal::from(ParameterName::Global("tool_radius".into())).into(),
+                    Operator::GetParameter.into()
+                ])
+                .into()
+            )),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn names_are_bounded_by_the_limits() {
+    let limits = Limits {
+        name_length: 4,
+        ..Limits::default()
+    };
+    let input = stream::iter(
+        "#<a bcd> = 1\n#<abcde> = 1\n"
+            .bytes()
+            .map(Result::<_, Error>::Ok),
+    );
+    let parser = Parser::with_limits(input, limits);
+    assert_eq!(
+        futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+        &[
+            Ok(GCode::ParameterSet(
+                Literal::from(ParameterName::Local("abcd".into())).into(),
+                Literal::from(1).into()
+            )),
+            Ok(GCode::Execute),
+            Err(Error::NameTooLong),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn names_are_only_accepted_after_a_hash() {
+    // with `optional-value`, `G` is read without a value first.
+    assert!(block_on("G<a>\n".bytes()).contains(&Err(Error::UnexpectedByte(b'<'))));
+    assert_eq!(
+        block_on("G#<>\n".bytes())[0],
+        Err(Error::UnexpectedByte(b'>'))
+    );
+    assert_eq!(
+        block_on("G#<_>\n".bytes())[0],
+        Err(Error::UnexpectedByte(b'>'))
+    );
+}
+
+#[test]
+#[cfg(feature = "parse-expressions")]
+fn parse_exists() {
+    let input = "G exists[#<_a>]\n".bytes();
+    assert_eq!(
+        block_on(input),
+        &[
+            Ok(GCode::Word(
+                'g',
+                Expression(vec![
+                    Literal::from(ParameterName::Global("a".into())).into(),
+                    Operator::Exists.into()
+                ])
+                .into()
+            )),
+            Ok(GCode::Execute)
+        ]
+    );
+}
+
+#[test]
+fn exists_only_accepts_a_parameter() {
+    for input in ["G exists[1]\n", "G exists[#1 + 1]\n", "G exists[<a>]\n"] {
+        let res = block_on(input.bytes());
+        assert!(res.iter().any(Result::is_err), "{}: {:?}", input, res);
+    }
+}
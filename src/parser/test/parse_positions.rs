@@ -0,0 +1,69 @@
+use futures::stream;
+
+use super::{Error, GCode, Parser, StreamExt};
+use crate::{SourcePosition, Spanned};
+
+fn spanned(input: &str) -> Vec<Spanned<Result<GCode, Error>>> {
+    let mut parser = Parser::new(stream::iter(input.bytes()).map(Result::<_, Error>::Ok));
+    futures_executor::block_on(async {
+        let mut res = Vec::new();
+        while let Some(r) = parser.next_spanned().await {
+            res.push(r);
+        }
+        res
+    })
+}
+
+fn pos(offset: usize, line: u32, column: u32) -> SourcePosition {
+    SourcePosition {
+        offset,
+        line,
+        column,
+    }
+}
+
+#[test]
+fn events_span_from_their_first_byte() {
+    assert_eq!(
+        spanned("G0  X1.5 \n"),
+        [
+            Spanned {
+                start: pos(0, 1, 1),
+                // numbers may contain spaces, they are consumed while looking for a decimal point.
+                end: pos(4, 1, 5),
+                value: Ok(GCode::Word('g', 0.into()))
+            },
+            Spanned {
+                start: pos(4, 1, 5),
+                end: pos(8, 1, 9),
+                value: Ok(GCode::Word('x', 1.5.into()))
+            },
+            Spanned {
+                start: pos(9, 1, 10),
+                end: pos(10, 2, 1),
+                value: Ok(GCode::Execute)
+            },
+        ]
+    );
+}
+
+#[test]
+fn errors_report_their_line_and_column() {
+    let res = spanned("G0\nG1 $ Y2\n");
+    assert_eq!(res[2].value, Ok(GCode::Word('g', 1.into())));
+    assert_eq!(res[3].value, Err(Error::UnexpectedByte(b'$')));
+    assert_eq!(res[3].start, pos(6, 2, 4));
+    assert_eq!(res[3].end, pos(7, 2, 5));
+
+    // the bytes skipped during the recovery are not part of the execute's span.
+    assert_eq!(res[4].value, Ok(GCode::Execute));
+    assert_eq!(res[4].start, pos(10, 2, 8));
+}
+
+#[test]
+fn number_overflows_end_on_the_offending_digit() {
+    let res = spanned("G1 X12345678901\n");
+    assert_eq!(res[1].value, Err(Error::NumberOverflow));
+    assert_eq!(res[1].start, pos(3, 1, 4));
+    assert_eq!(res[1].end, pos(15, 1, 16));
+}
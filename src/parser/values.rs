@@ -7,200 +7,458 @@
 //! crate shows that there's no subtantial benefit in terms of flash size from using the fixed
 //! point arithmetics.
 
-use futures::{Stream, StreamExt};
-
-#[cfg(all(not(feature = "std"), feature = "string-value"))]
+#[cfg(all(
+    not(feature = "std"),
+    any(feature = "string-value", feature = "parse-parameters")
+))]
 use alloc::string::String;
+#[cfg(all(not(feature = "std"), feature = "string-value"))]
+use alloc::vec::Vec;
 
-use crate::{
-    stream::PushBackable,
-    types::{Literal, ParseResult},
-    utils::skip_whitespaces,
-    Error,
-};
+use super::{grammar::Step, Limits};
+use crate::{types::Literal, Error};
 
 #[cfg(not(feature = "parse-expressions"))]
 use crate::types::RealValue;
 
-#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
-pub use crate::types::expressions::{Expression, Operator};
-
-pub(crate) async fn parse_number<S, E>(input: &mut S) -> Option<Result<(u32, u32), E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    let mut n = 0;
-    let mut order = 1;
-    let res = loop {
-        let b = match input.next().await? {
-            Ok(b) => b,
-            Err(e) => return Some(Err(e)),
-        };
+#[cfg(all(feature = "parse-parameters", not(feature = "parse-expressions")))]
+use crate::types::expressions::{Expression, Operator};
+#[cfg(feature = "parse-parameters")]
+use crate::types::ParameterName;
+
+/// Reads up to `max_digits` digits, producing the value and the number of digits read.
+///
+/// Reading one more digit, or a value that does not fit in a `u32`, is an overflow. The offending
+/// digit is then the last byte consumed.
+#[derive(Debug)]
+pub(crate) struct NumberParser {
+    value: u32,
+    digits: u8,
+    max_digits: u8,
+}
+
+impl NumberParser {
+    pub fn new(max_digits: u8) -> Self {
+        Self {
+            value: 0,
+            digits: 0,
+            max_digits,
+        }
+    }
+
+    /// Whether no digit has been read yet.
+    pub fn is_empty(&self) -> bool {
+        self.digits == 0
+    }
+
+    pub fn feed(&mut self, b: u8) -> Step<(u32, u8)> {
         match b {
             b'0'..=b'9' => {
-                let digit = u32::from(b - b'0');
-                n = n * 10 + digit;
-                order *= 10;
-            }
-            _ => {
-                input.push_back(b);
-                break Ok((n, order));
+                self.digits = self.digits.saturating_add(1);
+                match self
+                    .value
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(u32::from(b - b'0')))
+                {
+                    Some(n) if self.digits <= self.max_digits => {
+                        self.value = n;
+                        Step::More
+                    }
+                    _ => Step::Take(Err(Error::NumberOverflow)),
+                }
             }
+            _ => Step::Leave(Ok((self.value, self.digits))),
         }
-    };
-    Some(res)
+    }
 }
 
-async fn parse_real_literal<S, E>(input: &mut S) -> Option<ParseResult<f64, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    // extract sign: default to positiv
-    let mut b = try_result!(input.next());
+#[cfg(not(feature = "decimal"))]
+type RealNumber = crate::Real;
+#[cfg(feature = "decimal")]
+type RealNumber = crate::Decimal;
+
+/// Builds `int.dec` where `dec` has `digits` digits.
+#[cfg(not(feature = "decimal"))]
+fn real_number(negativ: bool, int: u32, dec: u32, digits: u8) -> RealNumber {
+    // the scaled value is an integer and powers of 10 up to 10^9 are exact floats: the value is
+    // correctly rounded as long as the digits fit in the mantissa (2^24 for `f32`, 2^53 for
+    // `f64`).
+    let denominator = 10u64.pow(digits.into());
+    let scaled = u64::from(int) * denominator + u64::from(dec);
+    let value = scaled as RealNumber / denominator as RealNumber;
+    if negativ {
+        -value
+    } else {
+        value
+    }
+}
 
-    let mut negativ = false;
+/// Builds `int.dec` where `dec` has `digits` digits.
+#[cfg(feature = "decimal")]
+fn real_number(negativ: bool, int: u32, dec: u32, digits: u8) -> RealNumber {
+    // cannot overflow: `int` is a u32 and there are at most 9 digits.
+    let mantissa = i64::from(int) * 10i64.pow(digits.into()) + i64::from(dec);
+    crate::Decimal::new(if negativ { -mantissa } else { mantissa }, digits)
+}
 
-    if b == b'-' || b == b'+' {
-        negativ = b == b'-';
+#[derive(Debug)]
+enum RealPhase {
+    Sign,
+    /// Spaces may follow the sign, a decimal point may replace the integer part.
+    IntegerOrPoint,
+    Integer(NumberParser),
+    /// Spaces may follow the integer part.
+    Point,
+    /// Spaces may follow the decimal point.
+    FractionStart,
+    Fraction,
+}
 
-        // skip spaces after the sign
-        try_result!(skip_whitespaces(input));
-        b = try_result!(input.next());
+/// Reads a real number: its sign, its integer part and its fractional part, in that order.
+///
+/// Only the first `fraction_digits` digits of the fractional part (at most 9) are kept, the value
+/// is rounded half up on the next one.
+#[derive(Debug)]
+pub(crate) struct RealParser {
+    limits: Limits,
+    phase: RealPhase,
+    negativ: bool,
+    int: u32,
+    dec: u32,
+    digits: u8,
+    round_up: bool,
+}
+
+impl RealParser {
+    fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            phase: RealPhase::Sign,
+            negativ: false,
+            int: 0,
+            dec: 0,
+            digits: 0,
+            round_up: false,
+        }
     }
 
-    // parse integer part
-    let int = if b != b'.' {
-        input.push_back(b);
-        // if not a decimal point, there must be an integer
-        let (v, _) = try_result!(parse_number(input));
-        // skip spaces after integer part
-        try_result!(skip_whitespaces(input));
-        b = try_result!(input.next());
-        Some(v)
-    } else {
-        None
-    };
-
-    // parse decimal part: mandatory if integer part is abscent
-    let dec = if b == b'.' {
-        // skip spaces after decimal point
-        try_result!(skip_whitespaces(input));
-        Some(try_result!(parse_number(input)))
-    } else {
-        input.push_back(b);
-        None
-    };
-
-    //println!(
-    //"literal done: {} {:?} {:?}",
-    //if negativ { '-' } else { '+' },
-    //int,
-    //dec
-    //);
-
-    let res = if int.is_none() && dec.is_none() {
-        ParseResult::Parsing(Error::BadNumberFormat.into())
-    } else {
-        let int = int.map(f64::from).unwrap_or(0.);
-        let (dec, ord) = dec
-            .map(|(dec, ord)| (dec.into(), ord.into()))
-            .unwrap_or((0., 1.));
-        ParseResult::Ok((if negativ { -1. } else { 1. }) * (int + dec / ord))
-    };
-    Some(res)
+    fn feed(&mut self, b: u8) -> Step<RealNumber> {
+        loop {
+            match &mut self.phase {
+                RealPhase::Sign => {
+                    self.phase = RealPhase::IntegerOrPoint;
+                    if b == b'-' || b == b'+' {
+                        self.negativ = b == b'-';
+                        return Step::More;
+                    }
+                }
+                RealPhase::IntegerOrPoint => match b {
+                    b' ' => return Step::More,
+                    b'.' => {
+                        self.phase = RealPhase::FractionStart;
+                        return Step::More;
+                    }
+                    _ => {
+                        self.phase =
+                            RealPhase::Integer(NumberParser::new(self.limits.integer_digits))
+                    }
+                },
+                RealPhase::Integer(number) => {
+                    // a missing integer part is worth 0.
+                    let ((int, _), _) = try_step!(number.feed(b));
+                    self.int = int;
+                    self.phase = RealPhase::Point;
+                }
+                RealPhase::Point => match b {
+                    b' ' => return Step::More,
+                    b'.' => {
+                        self.phase = RealPhase::FractionStart;
+                        return Step::More;
+                    }
+                    _ => return Step::Leave(Ok(self.value())),
+                },
+                RealPhase::FractionStart => match b {
+                    b' ' => return Step::More,
+                    _ => self.phase = RealPhase::Fraction,
+                },
+                RealPhase::Fraction => match b {
+                    b'0'..=b'9' => {
+                        let digit = u32::from(b - b'0');
+                        let max_digits = self.max_fraction_digits();
+                        if self.digits < max_digits {
+                            // cannot overflow: there are at most 9 digits.
+                            self.dec = self.dec * 10 + digit;
+                        } else if self.digits == max_digits {
+                            self.round_up = digit >= 5;
+                        }
+                        self.digits = self.digits.saturating_add(1);
+                        return Step::More;
+                    }
+                    _ => return Step::Leave(Ok(self.value())),
+                },
+            }
+        }
+    }
+
+    fn max_fraction_digits(&self) -> u8 {
+        self.limits.fraction_digits.min(Limits::MAX_FRACTION_DIGITS)
+    }
+
+    /// The numerator of the fraction may be equal to its denominator once rounded.
+    fn value(&self) -> RealNumber {
+        real_number(
+            self.negativ,
+            self.int,
+            self.dec + u32::from(self.round_up),
+            self.digits.min(self.max_fraction_digits()),
+        )
+    }
 }
 
+/// Reads a string literal following its `"`, up to the closing one.
+///
+/// The string is only kept when collecting, it is left empty otherwise.
 #[cfg(feature = "string-value")]
-async fn parse_string_literal<S, E>(input: &mut S) -> Option<ParseResult<String, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    #[cfg(not(feature = "std"))]
-    use alloc::vec::Vec;
+#[derive(Debug)]
+pub(crate) struct StringParser {
+    bytes: Option<Vec<u8>>,
+    escaped: bool,
+}
 
-    // we cannot use take_until(…).collect() because we need to distinguish input's end of stream
-    // from take_until(…) end of stream
+#[cfg(feature = "string-value")]
+impl StringParser {
+    pub fn new(collect: bool) -> Self {
+        Self {
+            bytes: collect.then(Vec::new),
+            escaped: false,
+        }
+    }
 
-    let mut array = Vec::new();
-    loop {
-        match try_result!(input.next()) {
-            b'"' => break,
-            b'\\' => {
-                array.push(try_result!(input.next()));
+    pub fn feed(&mut self, b: u8) -> Step<String> {
+        if self.escaped {
+            self.escaped = false;
+        } else if b == b'"' {
+            return Step::Take(match self.bytes.take() {
+                Some(bytes) => String::from_utf8(bytes).map_err(|_| Error::InvalidUTF8String),
+                None => Ok(String::new()),
+            });
+        } else if b == b'\\' {
+            self.escaped = true;
+            return Step::More;
+        }
+        if let Some(bytes) = &mut self.bytes {
+            bytes.push(b);
+        }
+        Step::More
+    }
+}
+
+/// Reads a name following its `<` up to its `>`.
+///
+/// As in LinuxCNC, names are case insensitive and their whitespaces are ignored.
+#[cfg(feature = "parse-parameters")]
+#[derive(Debug)]
+pub(crate) struct NameParser {
+    name: String,
+    max_length: u8,
+}
+
+#[cfg(feature = "parse-parameters")]
+impl NameParser {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            name: String::new(),
+            max_length: limits.name_length,
+        }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Step<String> {
+        match b {
+            b'>' if self.name.is_empty() => Step::Take(Err(Error::UnexpectedByte(b'>'))),
+            b'>' => Step::Take(Ok(core::mem::take(&mut self.name))),
+            b' ' | b'\t' => Step::More,
+            b if b.is_ascii_graphic() => {
+                if self.name.len() >= usize::from(self.max_length) {
+                    return Step::Take(Err(Error::NameTooLong));
+                }
+                self.name.push(char::from(b.to_ascii_lowercase()));
+                Step::More
             }
-            b => array.push(b),
+            b => Step::Take(Err(Error::UnexpectedByte(b))),
         }
     }
+}
 
-    match String::from_utf8(array) {
-        Ok(string) => Some(ParseResult::Ok(string)),
-        Err(_) => Some(Error::InvalidUTF8String.into()),
+/// Names the parameter read by a [`NameParser`], those starting with `_` are global.
+#[cfg(feature = "parse-parameters")]
+pub(crate) fn parameter_name(name: String) -> Result<ParameterName, Error> {
+    match name.strip_prefix('_') {
+        Some("") => Err(Error::UnexpectedByte(b'>')),
+        Some(global) => Ok(ParameterName::Global(global.into())),
+        None => Ok(ParameterName::Local(name)),
     }
 }
 
-pub(crate) async fn parse_literal<S, E>(input: &mut S) -> Option<ParseResult<Literal, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    let b = try_result!(input.next());
-    Some(match b {
-        b'+' | b'-' | b'.' | b'0'..=b'9' => {
-            input.push_back(b);
-            ParseResult::Ok(Literal::from(try_parse!(parse_real_literal(input))))
-        }
+/// Reads a real number or, with `string-value`, a string.
+#[derive(Debug)]
+pub(crate) enum LiteralParser {
+    Start {
+        limits: Limits,
         #[cfg(feature = "string-value")]
-        b'"' => ParseResult::Ok(Literal::from(try_parse!(parse_string_literal(input)))),
-        _ => Error::UnexpectedByte(b).into(),
-    })
+        collect: bool,
+    },
+    Real(RealParser),
+    #[cfg(feature = "string-value")]
+    String(StringParser),
 }
 
-#[cfg(not(feature = "parse-expressions"))]
-pub(crate) async fn parse_real_value<S, E>(input: &mut S) -> Option<ParseResult<RealValue, E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    let b = try_result!(input.next());
-    // println!("real value: {:?}", b as char);
-
-    let res = match b {
-        b'+' | b'-' | b'.' | b'0'..=b'9' => {
-            input.push_back(b);
-            ParseResult::Ok(RealValue::from(try_parse!(parse_literal(input))))
+impl LiteralParser {
+    /// Strings are only kept when `collect` is set.
+    #[cfg_attr(not(feature = "string-value"), allow(unused_variables))]
+    pub fn new(limits: Limits, collect: bool) -> Self {
+        Self::Start {
+            limits,
+            #[cfg(feature = "string-value")]
+            collect,
         }
+    }
+
+    pub fn feed(&mut self, b: u8) -> Step<Literal> {
+        loop {
+            match self {
+                Self::Start { limits, .. } => match b {
+                    b'+' | b'-' | b'.' | b'0'..=b'9' => {
+                        *self = Self::Real(RealParser::new(*limits))
+                    }
+                    #[cfg(feature = "string-value")]
+                    b'"' => {
+                        if let Self::Start { collect, .. } = *self {
+                            *self = Self::String(StringParser::new(collect));
+                        }
+                        return Step::More;
+                    }
+                    _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                },
+                Self::Real(real) => return real.feed(b).map(Literal::from),
+                #[cfg(feature = "string-value")]
+                Self::String(string) => return string.feed(b).map(Literal::from),
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "parse-parameters", not(feature = "parse-expressions")))]
+#[derive(Debug)]
+pub(crate) enum ParameterPhase {
+    /// More `#` and spaces may follow the first one.
+    Hashes(Limits),
+    Name(NameParser),
+    Literal(LiteralParser),
+}
+
+/// Reads a word's value: a literal or, with `parse-parameters`, the value of a parameter.
+///
+/// Spaces may precede the value.
+#[cfg(not(feature = "parse-expressions"))]
+#[derive(Debug)]
+pub(crate) enum ValueParser {
+    Start {
+        limits: Limits,
         #[cfg(feature = "string-value")]
-        b'"' => {
-            input.push_back(b);
-            ParseResult::Ok(RealValue::from(try_parse!(parse_literal(input))))
+        collect: bool,
+    },
+    Literal(LiteralParser),
+    /// The number of `#` read along with the operand of the last one.
+    #[cfg(feature = "parse-parameters")]
+    Parameter(usize, ParameterPhase),
+}
+
+#[cfg(not(feature = "parse-expressions"))]
+impl ValueParser {
+    pub fn new(limits: Limits) -> Self {
+        Self::Start {
+            limits,
+            #[cfg(feature = "string-value")]
+            collect: true,
         }
-        #[cfg(feature = "parse-parameters")]
-        b'#' => {
-            #[cfg(not(feature = "std"))]
-            use alloc::vec::Vec;
-
-            let mut n = 1;
-            let literal = loop {
-                try_result!(skip_whitespaces(input));
-                let b = try_result!(input.next());
-                if b != b'#' {
-                    input.push_back(b);
-
-                    break try_parse!(parse_literal(input));
-                }
-                n += 1;
-            };
+    }
 
-            let vec: Vec<_> = core::iter::once(literal.into())
-                .chain(core::iter::repeat(Operator::GetParameter.into()).take(n))
-                .collect();
-            ParseResult::Ok(Expression(vec).into())
+    /// Leaves a string value empty rather than collecting it.
+    #[cfg(feature = "string-value")]
+    pub fn borrowing(limits: Limits) -> Self {
+        Self::Start {
+            limits,
+            collect: false,
         }
-        #[cfg(feature = "optional-value")]
-        b => {
-            input.push_back(b);
-            ParseResult::Ok(RealValue::None)
+    }
+
+    pub fn feed(&mut self, b: u8) -> Step<RealValue> {
+        loop {
+            match self {
+                Self::Start { limits, .. } => {
+                    let limits = *limits;
+                    match b {
+                        b' ' => return Step::More,
+                        b'+' | b'-' | b'.' | b'0'..=b'9' => {
+                            *self = Self::Literal(LiteralParser::new(limits, true))
+                        }
+                        #[cfg(feature = "string-value")]
+                        b'"' => {
+                            if let Self::Start { collect, .. } = *self {
+                                *self = Self::Literal(LiteralParser::new(limits, collect))
+                            }
+                        }
+                        #[cfg(feature = "parse-parameters")]
+                        b'#' => {
+                            *self = Self::Parameter(1, ParameterPhase::Hashes(limits));
+                            return Step::More;
+                        }
+                        #[cfg(feature = "optional-value")]
+                        _ => return Step::Leave(Ok(RealValue::None)),
+                        #[cfg(not(feature = "optional-value"))]
+                        _ => return Step::Take(Err(Error::UnexpectedByte(b))),
+                    }
+                }
+                Self::Literal(literal) => return literal.feed(b).map(RealValue::from),
+                #[cfg(feature = "parse-parameters")]
+                Self::Parameter(n, phase) => match phase {
+                    ParameterPhase::Hashes(limits) => match b {
+                        b' ' => return Step::More,
+                        b'#' => {
+                            *n += 1;
+                            return Step::More;
+                        }
+                        b'<' => {
+                            *phase = ParameterPhase::Name(NameParser::new(*limits));
+                            return Step::More;
+                        }
+                        _ => *phase = ParameterPhase::Literal(LiteralParser::new(*limits, true)),
+                    },
+                    ParameterPhase::Name(name) => {
+                        let n = *n;
+                        return name
+                            .feed(b)
+                            .and_then(parameter_name)
+                            .map(|name| parameter(Literal::from(name), n));
+                    }
+                    ParameterPhase::Literal(literal) => {
+                        let n = *n;
+                        return literal.feed(b).map(|literal| parameter(literal, n));
+                    }
+                },
+            }
         }
-        #[cfg(not(feature = "optional-value"))]
-        b => Error::UnexpectedByte(b).into(),
-    };
-    Some(res)
+    }
+}
+
+/// The value of `literal` read through `n` levels of `#`.
+#[cfg(all(feature = "parse-parameters", not(feature = "parse-expressions")))]
+fn parameter(literal: Literal, n: usize) -> RealValue {
+    #[cfg(not(feature = "std"))]
+    use alloc::vec::Vec;
+
+    let vec: Vec<_> = core::iter::once(literal.into())
+        .chain(core::iter::repeat(Operator::GetParameter.into()).take(n))
+        .collect();
+    Expression(vec).into()
 }
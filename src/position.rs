@@ -0,0 +1,48 @@
+//! Location of the parsed events in the input.
+
+/// A position in the input stream.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord, Hash)]
+pub struct SourcePosition {
+    /// Number of bytes read before this position.
+    pub offset: usize,
+    /// Line number, starting at 1.
+    pub line: u32,
+    /// Column in bytes, starting at 1.
+    pub column: u32,
+}
+impl Default for SourcePosition {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+impl SourcePosition {
+    pub(crate) fn after(self, b: u8) -> Self {
+        if b == b'\n' {
+            Self {
+                offset: self.offset + 1,
+                line: self.line + 1,
+                column: 1,
+            }
+        } else {
+            Self {
+                offset: self.offset + 1,
+                column: self.column + 1,
+                ..self
+            }
+        }
+    }
+}
+
+/// A value along with the range of the input it was read from.
+///
+/// `end` is the position following the last byte read.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Spanned<T> {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+    pub value: T,
+}
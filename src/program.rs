@@ -0,0 +1,704 @@
+//! Execution of LinuxCNC's O-word subroutines, conditionals and loops.
+//!
+//! A [`Program`] reads the `GCode`s of a [`ProgramSource`] and follows its control flow, jumping
+//! back and forth within the source. Calls bind their arguments to `#1` to `#30` (see
+//! [`ParameterTable::enter_subroutine`]) and conditions are evaluated against the parameters,
+//! which is why the program applies the parameter settings to its own [`ParameterTable`].
+//!
+//! ```
+//! use async_gcode::{program::Program, GCode, Literal, SliceParser};
+//!
+//! let source = SliceParser::new(b"o1 sub\nG#1\no1 endsub\no2 repeat [2]\no1 call [2]\no2 endrepeat\nG3\n");
+//! let mut program = Program::new(source);
+//! let mut values = Vec::new();
+//! while let Some(gcode) = program.next() {
+//!     if let GCode::Word('g', value) = gcode.unwrap() {
+//!         values.push(program.parameters().evaluate(&value).unwrap());
+//!     }
+//! }
+//! assert_eq!(values, [2, 2, 3].map(Literal::from));
+//! ```
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::BTreeMap, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::BTreeMap;
+
+use crate::{
+    Error, GCode, Literal, OCode, OWord, ParameterIndex, ParameterStorage, ParameterTable,
+    RealValue, SliceParser,
+};
+
+/// Default bound on the nesting of subroutine calls.
+pub const MAX_CALL_DEPTH: usize = 16;
+
+/// Default bound on the iterations of a loop.
+pub const MAX_ITERATIONS: u32 = 10_000;
+
+/// A program whose lines can be read again.
+pub trait ProgramSource {
+    type Error: From<Error>;
+
+    fn next_gcode(&mut self) -> Option<Result<GCode, Self::Error>>;
+
+    /// Where the next line starts, once a `GCode::Execute` has been read.
+    fn offset(&self) -> usize;
+
+    /// Resumes reading from an offset previously returned by [`ProgramSource::offset`].
+    fn seek(&mut self, offset: usize) -> Result<(), Self::Error>;
+}
+
+impl ProgramSource for SliceParser<'_> {
+    type Error = Error;
+
+    fn next_gcode(&mut self) -> Option<Result<GCode, Error>> {
+        SliceParser::next_gcode(self)
+    }
+
+    fn offset(&self) -> usize {
+        SliceParser::offset(self)
+    }
+
+    fn seek(&mut self, offset: usize) -> Result<(), Error> {
+        SliceParser::seek(self, offset);
+        Ok(())
+    }
+}
+
+/// A jump made once the line is complete.
+#[derive(Debug)]
+enum Jump {
+    Call(OWord, Vec<Literal>),
+    Return(Option<Literal>),
+    Seek(usize),
+}
+
+/// A subroutine being executed.
+#[derive(Debug)]
+struct Call {
+    label: OWord,
+    /// Where the line following the call starts.
+    resume: usize,
+    /// The number of structures opened by the callers.
+    structures: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    /// A conditional block and whether one of its branches was taken.
+    If {
+        taken: bool,
+    },
+    While,
+    Do,
+    Repeat {
+        count: u32,
+    },
+}
+
+/// A conditional block or a loop being executed.
+#[derive(Debug)]
+struct Structure {
+    label: OWord,
+    kind: Kind,
+    /// Where the line opening the structure starts.
+    head: usize,
+    /// The number of times the line opening a loop was executed again.
+    iterations: u32,
+}
+
+/// The O-code skipped lines lead to.
+#[derive(Debug, Clone, Copy)]
+enum Until {
+    /// The end of a subroutine definition.
+    EndSub,
+    /// The next branch of a conditional block, or its end.
+    Branch,
+    EndIf,
+    /// The end of a loop.
+    End(Kind),
+    /// The `while` ending a `do` loop.
+    DoWhile,
+}
+
+impl Until {
+    /// Whether the skipping stops on `code` and if so, whether its line is skipped as well.
+    fn stops(self, code: &OCode) -> Option<bool> {
+        match (self, code) {
+            (Self::EndSub, OCode::EndSub(_))
+            | (Self::End(Kind::While), OCode::EndWhile)
+            | (Self::End(Kind::Repeat { .. }), OCode::EndRepeat)
+            | (Self::End(Kind::Do), OCode::While(_)) => Some(true),
+            (Self::Branch, OCode::ElseIf(_) | OCode::Else | OCode::EndIf)
+            | (Self::EndIf, OCode::EndIf)
+            | (Self::DoWhile, OCode::While(_)) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Lines being skipped.
+#[derive(Debug)]
+struct Skip {
+    label: OWord,
+    until: Until,
+    /// Whether the skipping ends with the current line.
+    ends: bool,
+}
+
+/// Follows the control flow of a [`ProgramSource`].
+///
+/// The `GCode`s are handed out as they are executed: subroutine definitions are skipped until
+/// they are called, so are the branches not taken, and the O-codes are consumed. Their line's
+/// `GCode::Execute` is still produced, unless the jump fails in which case the error is produced
+/// instead.
+///
+/// Subroutines may be defined anywhere in the program, the source being searched for the ones not
+/// met yet. On return, `#<_value>` is set to the returned value, if any, and `#<_value_returned>`
+/// to 1 or 0.
+///
+/// Conditions are true when they are not 0. The iterations of each loop are bounded, see
+/// [`Program::set_max_iterations`].
+#[derive(Debug)]
+pub struct Program<P, S: ParameterStorage = BTreeMap<ParameterIndex, Literal>> {
+    source: P,
+    parameters: ParameterTable<S>,
+    /// Where the current line starts.
+    line: usize,
+    /// The subroutines met so far and where their body starts.
+    subroutines: Vec<(OWord, usize)>,
+    /// Whether the whole source has been searched for subroutines.
+    searched: bool,
+    calls: Vec<Call>,
+    structures: Vec<Structure>,
+    max_depth: usize,
+    max_iterations: u32,
+    jump: Option<Jump>,
+    /// The subroutine defined by the current line.
+    defining: Option<OWord>,
+    skip: Option<Skip>,
+}
+
+impl<P: ProgramSource> Program<P> {
+    pub fn new(source: P) -> Self {
+        Self::with_parameters(source, ParameterTable::new())
+    }
+}
+
+impl<P: ProgramSource, S: ParameterStorage> Program<P, S> {
+    pub fn with_parameters(source: P, parameters: ParameterTable<S>) -> Self {
+        Self {
+            line: source.offset(),
+            source,
+            parameters,
+            subroutines: Vec::new(),
+            searched: false,
+            calls: Vec::new(),
+            structures: Vec::new(),
+            max_depth: MAX_CALL_DEPTH,
+            max_iterations: MAX_ITERATIONS,
+            jump: None,
+            defining: None,
+            skip: None,
+        }
+    }
+
+    /// Bounds the nesting of subroutine calls, deeper ones are reported as
+    /// [`Error::CallStackOverflow`].
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// Bounds the iterations of each loop, further ones are reported as
+    /// [`Error::TooManyIterations`].
+    pub fn set_max_iterations(&mut self, iterations: u32) {
+        self.max_iterations = iterations;
+    }
+
+    /// The parameters, to evaluate the values of the words.
+    pub fn parameters(&self) -> &ParameterTable<S> {
+        &self.parameters
+    }
+
+    pub fn parameters_mut(&mut self) -> &mut ParameterTable<S> {
+        &mut self.parameters
+    }
+
+    pub fn into_inner(self) -> (P, ParameterTable<S>) {
+        (self.source, self.parameters)
+    }
+
+    fn evaluate(&self, value: &Option<RealValue>) -> Result<Option<Literal>, Error> {
+        value
+            .as_ref()
+            .map(|value| self.parameters.evaluate(value))
+            .transpose()
+    }
+
+    fn number(&self, value: &RealValue) -> Result<crate::Real, Error> {
+        self.parameters
+            .evaluate(value)?
+            .as_real_number()
+            .ok_or(Error::DomainError)
+    }
+
+    fn skip(&mut self, label: OWord, until: Until) {
+        self.skip = Some(Skip {
+            label,
+            until,
+            ends: false,
+        });
+    }
+
+    fn open(&mut self, label: OWord, kind: Kind) {
+        self.structures.push(Structure {
+            label,
+            kind,
+            head: self.line,
+            iterations: 0,
+        });
+    }
+
+    /// Counts an iteration of a loop.
+    fn iterate(&mut self, structure: usize) -> Result<(), Error> {
+        let iterations = &mut self.structures[structure].iterations;
+        *iterations += 1;
+        if *iterations > self.max_iterations {
+            return Err(Error::TooManyIterations);
+        }
+        Ok(())
+    }
+
+    fn o_code(&mut self, label: OWord, code: OCode) -> Result<(), Error> {
+        let call = self.calls.last();
+        let start = call.map_or(0, |call| call.structures);
+        let in_subroutine = call.map(|call| &call.label);
+        // the innermost structure of the subroutine, when the O-code belongs to it.
+        let top = (self.structures.len() > start)
+            .then(|| self.structures.len() - 1)
+            .filter(|&top| self.structures[top].label == label);
+        let current = top.map(|top| {
+            let structure = &self.structures[top];
+            (top, structure.kind, structure.head == self.line)
+        });
+
+        match code {
+            // nested definitions are skipped but reported.
+            OCode::Sub => {
+                let nested = in_subroutine.is_some();
+                if !nested {
+                    self.defining = Some(label.clone());
+                }
+                self.skip(label, Until::EndSub);
+                if nested {
+                    return Err(Error::MisplacedOWord);
+                }
+            }
+            OCode::EndSub(value) | OCode::Return(value) if in_subroutine == Some(&label) => {
+                self.jump = Some(Jump::Return(self.evaluate(&value)?));
+            }
+            OCode::Call(arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.parameters.evaluate(argument))
+                    .collect::<Result<_, _>>()?;
+                self.jump = Some(Jump::Call(label, arguments));
+            }
+            OCode::If(condition) => {
+                let taken = self.number(&condition)? != 0.;
+                self.open(label.clone(), Kind::If { taken });
+                if !taken {
+                    self.skip(label, Until::Branch);
+                }
+            }
+            OCode::ElseIf(_) | OCode::Else => match current {
+                Some((_, Kind::If { taken: true }, _)) => self.skip(label, Until::EndIf),
+                Some((top, Kind::If { taken: false }, _)) => {
+                    let taken = match &code {
+                        OCode::ElseIf(condition) => self.number(condition)? != 0.,
+                        _ => true,
+                    };
+                    self.structures[top].kind = Kind::If { taken };
+                    if !taken {
+                        self.skip(label, Until::Branch);
+                    }
+                }
+                _ => return Err(Error::MisplacedOWord),
+            },
+            OCode::EndIf => match current {
+                Some((_, Kind::If { .. }, _)) => {
+                    self.structures.pop();
+                }
+                _ => return Err(Error::MisplacedOWord),
+            },
+            OCode::While(condition) => {
+                let holds = self.number(&condition)? != 0.;
+                match current {
+                    Some((top, Kind::Do, _)) if holds => {
+                        self.jump = Some(Jump::Seek(self.structures[top].head));
+                    }
+                    Some((_, Kind::Do, _)) => {
+                        self.structures.pop();
+                    }
+                    Some((top, Kind::While, true)) if holds => self.iterate(top)?,
+                    Some((_, Kind::While, true)) => {
+                        self.structures.pop();
+                        self.skip(label, Until::End(Kind::While));
+                    }
+                    _ if holds => self.open(label, Kind::While),
+                    _ => self.skip(label, Until::End(Kind::While)),
+                }
+            }
+            OCode::Do => match current {
+                Some((top, Kind::Do, true)) => self.iterate(top)?,
+                _ => self.open(label, Kind::Do),
+            },
+            OCode::Repeat(count) => match current {
+                Some((top, Kind::Repeat { count }, true)) => {
+                    self.iterate(top)?;
+                    if self.structures[top].iterations >= count {
+                        self.structures.pop();
+                        self.skip(label, Until::End(Kind::Repeat { count }));
+                    }
+                }
+                _ => {
+                    // negative counts are treated as 0.
+                    let count = self.number(&count)? as u32;
+                    if count > 0 {
+                        self.open(label, Kind::Repeat { count });
+                    } else {
+                        self.skip(label, Until::End(Kind::Repeat { count }));
+                    }
+                }
+            },
+            OCode::EndWhile | OCode::EndRepeat => match (current, &code) {
+                (Some((top, Kind::While, _)), OCode::EndWhile)
+                | (Some((top, Kind::Repeat { .. }, _)), OCode::EndRepeat) => {
+                    self.jump = Some(Jump::Seek(self.structures[top].head));
+                }
+                _ => return Err(Error::MisplacedOWord),
+            },
+            OCode::Break | OCode::Continue => {
+                // the loop may hold other structures, they are left as well.
+                let structure = self.structures[start..]
+                    .iter()
+                    .rposition(|structure| {
+                        structure.label == label && !matches!(structure.kind, Kind::If { .. })
+                    })
+                    .ok_or(Error::MisplacedOWord)?
+                    + start;
+                let Structure { kind, head, .. } = self.structures[structure];
+                if let OCode::Break = code {
+                    self.structures.truncate(structure);
+                    self.skip(label, Until::End(kind));
+                } else {
+                    self.structures.truncate(structure + 1);
+                    match kind {
+                        Kind::Do => self.skip(label, Until::DoWhile),
+                        _ => self.jump = Some(Jump::Seek(head)),
+                    }
+                }
+            }
+            _ => return Err(Error::MisplacedOWord),
+        }
+        Ok(())
+    }
+
+    /// Records where the body of a subroutine starts.
+    fn define(&mut self, label: OWord) {
+        if !self.subroutines.iter().any(|(known, _)| *known == label) {
+            self.subroutines.push((label, self.source.offset()));
+        }
+    }
+
+    /// Finds a subroutine, searching the whole source if it was not met yet.
+    fn find(&mut self, label: &OWord) -> Result<usize, P::Error> {
+        let known = |subroutines: &[(OWord, usize)]| {
+            subroutines
+                .iter()
+                .find_map(|(known, offset)| (known == label).then_some(*offset))
+        };
+        if let Some(offset) = known(&self.subroutines) {
+            return Ok(offset);
+        }
+        if !self.searched {
+            self.searched = true;
+            let resume = self.source.offset();
+            self.source.seek(0)?;
+            let mut defining = None;
+            // errors are left to be reported when the faulty lines are executed.
+            while let Some(gcode) = self.source.next_gcode() {
+                match gcode {
+                    Ok(GCode::OCode(label, OCode::Sub)) => defining = Some(label),
+                    Ok(GCode::Execute) => {
+                        if let Some(label) = defining.take() {
+                            self.define(label);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            self.source.seek(resume)?;
+        }
+        known(&self.subroutines).ok_or_else(|| Error::UnknownSubroutine.into())
+    }
+
+    fn end_of_line(&mut self) -> Result<(), P::Error> {
+        self.parameters.commit()?;
+        match self.jump.take() {
+            Some(Jump::Call(label, arguments)) => {
+                if self.calls.len() >= self.max_depth {
+                    return Err(Error::CallStackOverflow.into());
+                }
+                let body = self.find(&label)?;
+                self.parameters.enter_subroutine(&arguments)?;
+                self.calls.push(Call {
+                    label,
+                    resume: self.source.offset(),
+                    structures: self.structures.len(),
+                });
+                self.source.seek(body)
+            }
+            Some(Jump::Return(value)) => {
+                let call = self.calls.pop().ok_or(Error::MisplacedOWord)?;
+                self.structures.truncate(call.structures);
+                self.parameters.leave_subroutine()?;
+                let returned = value.is_some();
+                if let Some(value) = value {
+                    self.parameters
+                        .set(ParameterIndex::Global("value".into()), value)?;
+                }
+                self.parameters.set(
+                    ParameterIndex::Global("value_returned".into()),
+                    Literal::from(i32::from(returned)),
+                )?;
+                self.source.seek(call.resume)
+            }
+            Some(Jump::Seek(offset)) => self.source.seek(offset),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<P: ProgramSource, S: ParameterStorage> Iterator for Program<P, S> {
+    type Item = Result<GCode, P::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let gcode = match self.source.next_gcode()? {
+                Ok(gcode) => gcode,
+                Err(e) => {
+                    // the line is discarded.
+                    self.parameters.discard_pending();
+                    self.jump = None;
+                    return Some(Err(e));
+                }
+            };
+
+            if let Some(skip) = &mut self.skip {
+                match &gcode {
+                    GCode::OCode(label, code) if *label == skip.label => {
+                        match skip.until.stops(code) {
+                            Some(true) => skip.ends = true,
+                            Some(false) => self.skip = None,
+                            None => {}
+                        }
+                    }
+                    GCode::Execute => {
+                        if skip.ends {
+                            self.skip = None;
+                        }
+                        if let Some(label) = self.defining.take() {
+                            self.define(label);
+                        }
+                        self.line = self.source.offset();
+                    }
+                    _ => {}
+                }
+                if self.skip.is_some() || gcode == GCode::Execute {
+                    continue;
+                }
+            }
+
+            let res = match gcode {
+                GCode::OCode(label, code) => match self.o_code(label, code) {
+                    Ok(()) => continue,
+                    Err(e) => Err(e.into()),
+                },
+                GCode::Execute => {
+                    let res = self.end_of_line().map(|_| GCode::Execute);
+                    self.line = self.source.offset();
+                    res
+                }
+                gcode => self
+                    .parameters
+                    .apply(&gcode)
+                    .map(|_| gcode)
+                    .map_err(Into::into),
+            };
+            return Some(res);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Program;
+    use crate::{Error, GCode, Literal, ParameterIndex, SliceParser};
+
+    /// Evaluates the words of a program, stopping on the first error.
+    fn run(input: &str) -> Result<Vec<Literal>, Error> {
+        let mut program = Program::new(SliceParser::new(input.as_bytes()));
+        let mut values = Vec::new();
+        while let Some(gcode) = program.next() {
+            if let GCode::Word(_, value) = gcode? {
+                values.push(program.parameters().evaluate(&value)?);
+            }
+        }
+        Ok(values)
+    }
+
+    #[test]
+    fn arguments_are_bound_for_the_duration_of_the_call() {
+        let input = "#1=5\no<square> call [3]\nG#1 X#<_value>\no<square> sub\n#<x>=[#1*#1]\nG#<x> Y#2\no<square> endsub [#<x>]\n";
+        let mut program = Program::new(SliceParser::new(input.as_bytes()));
+        let mut values = Vec::new();
+        while let Some(gcode) = program.next() {
+            if let GCode::Word(_, value) = gcode.unwrap() {
+                values.push(program.parameters().evaluate(&value).unwrap());
+            }
+        }
+        assert_eq!(values, [9, 0, 5, 9].map(Literal::from));
+        assert_eq!(
+            program
+                .parameters()
+                .get(&ParameterIndex::Global("value_returned".into())),
+            Literal::from(1)
+        );
+    }
+
+    #[test]
+    fn local_parameters_are_scoped_to_the_subroutine() {
+        assert_eq!(
+            run("#<a>=1\no1 call\nG#<a>\no1 sub\nG#<a>\n#<a>=2\nG#<a>\no1 return\no1 endsub\n"),
+            Ok([0, 2, 1].map(Literal::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn subroutines_may_call_each_other() {
+        assert_eq!(
+            run("o1 sub\nG1\no2 call\no1 endsub\no2 sub\nG2\no2 endsub\no1 call\nG3\n"),
+            Ok([1, 2, 3].map(Literal::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn the_first_branch_holding_is_taken() {
+        let input = "o1 if [#1 - 1]\nG1\no1 elseif [#2]\nG2\no1 else\nG3\no1 endif\nG4\n";
+        for (parameters, expected) in [
+            ("#1=0 #2=0", [1, 4]),
+            ("#1=1 #2=1", [2, 4]),
+            ("#1=1 #2=0", [3, 4]),
+        ] {
+            assert_eq!(
+                run(&format!("{}\n{}", parameters, input)),
+                Ok(expected.map(Literal::from).to_vec())
+            );
+        }
+    }
+
+    #[test]
+    fn loops_are_repeated() {
+        assert_eq!(
+            run("#1=0\no1 while [3 - #1]\nG#1\n#1=[#1 + 1]\no1 endwhile\nG9\n"),
+            Ok([0, 1, 2, 9].map(Literal::from).to_vec())
+        );
+        assert_eq!(
+            run("#1=0\no1 do\nG#1\n#1=[#1 + 1]\no1 while [2 - #1]\nG9\n"),
+            Ok([0, 1, 9].map(Literal::from).to_vec())
+        );
+        assert_eq!(
+            run("o1 repeat [3]\nG1\no1 endrepeat\no2 repeat [0]\nG2\no2 endrepeat\nG9\n"),
+            Ok([1, 1, 1, 9].map(Literal::from).to_vec())
+        );
+        assert_eq!(
+            run("o1 while [0]\nG1\no1 endwhile\nG9\n"),
+            Ok([9].map(Literal::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn loops_may_be_broken_or_continued() {
+        assert_eq!(
+            run("#1=0\no1 while [1]\n#1=[#1 + 1]\no2 if [#1 - 3]\no1 continue\no2 endif\no1 break\no1 endwhile\nG#1\n"),
+            Ok([3].map(Literal::from).to_vec())
+        );
+        assert_eq!(
+            run("#1=0\no1 do\n#1=[#1 + 1]\no1 continue\nG9\no1 while [2 - #1]\nG#1\n"),
+            Ok([2].map(Literal::from).to_vec())
+        );
+        assert_eq!(
+            run("o1 repeat [2]\nG1\no2 repeat [3]\nG2\no2 break\no2 endrepeat\no1 endrepeat\n"),
+            Ok([1, 2, 1, 2].map(Literal::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn subroutines_may_return_from_loops() {
+        assert_eq!(
+            run("o1 sub\no2 while [1]\no1 return [5]\no2 endwhile\no1 endsub\no1 call\no1 call\nG#<_value>\n"),
+            Ok([5].map(Literal::from).to_vec())
+        );
+    }
+
+    #[test]
+    fn iterations_are_bounded() {
+        let mut program = Program::new(SliceParser::new(b"o1 while [1]\no1 endwhile\n"));
+        program.set_max_iterations(10);
+        assert_eq!(
+            program.find_map(Result::err),
+            Some(Error::TooManyIterations)
+        );
+        // the count saturates to `u32::MAX`.
+        let mut program = Program::new(SliceParser::new(
+            b"o1 repeat [100000 * 100000]\no1 endrepeat\n",
+        ));
+        program.set_max_iterations(10);
+        assert_eq!(
+            program.find_map(Result::err),
+            Some(Error::TooManyIterations)
+        );
+    }
+
+    #[test]
+    fn misplaced_control_flow_is_reported() {
+        assert_eq!(run("o1 endwhile\n"), Err(Error::MisplacedOWord));
+        assert_eq!(run("o1 if [1]\no2 endif\n"), Err(Error::MisplacedOWord));
+        assert_eq!(run("o1 else\n"), Err(Error::MisplacedOWord));
+        assert_eq!(run("o1 break\n"), Err(Error::MisplacedOWord));
+        assert_eq!(
+            run("o1 while [1]\no2 if [1]\no1 endwhile\n"),
+            Err(Error::MisplacedOWord)
+        );
+    }
+
+    #[test]
+    fn invalid_calls_are_reported() {
+        assert_eq!(run("o1 call\n"), Err(Error::UnknownSubroutine));
+        assert_eq!(
+            run("o1 sub\no1 call\no1 endsub\no1 call\n"),
+            Err(Error::CallStackOverflow)
+        );
+        assert_eq!(run("o1 return\n"), Err(Error::MisplacedOWord));
+        assert_eq!(
+            run("o1 sub\no2 endsub\no1 endsub\no1 call\n"),
+            Err(Error::MisplacedOWord)
+        );
+        let arguments = "[0]".repeat(31);
+        assert_eq!(
+            run(&format!("o1 sub\no1 endsub\no1 call {}\n", arguments)),
+            Err(Error::TooManyArguments)
+        );
+    }
+}
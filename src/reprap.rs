@@ -0,0 +1,431 @@
+//! Firmware side of the line numbered protocol used by RepRap hosts (Marlin, Repetier, …).
+//!
+//! The host numbers its lines and appends their checksum: `N12 G1 X3*80`. The firmware answers
+//! `ok` once a line is processed and asks for a line to be sent again when it is corrupted or out
+//! of sequence:
+//!
+//! ```text
+//! Error:checksum mismatch, Last Line: 11
+//! Resend: 12
+//! ok
+//! ```
+//!
+//! `M110 N<n>` sets the number of the last line received to `n`, the next expected one being
+//! `n + 1`.
+//!
+//! Hosts number their lines past the 5 digits of RS274/NGC, the parser is built with
+//! [`Limits::line_number_digits`](crate::Limits::line_number_digits) raised to 10.
+//!
+//! ```
+//! use async_gcode::{reprap::{ReprapLink, Reply}, Block, Error, Limits, Parser, Unbounded};
+//! use futures::stream;
+//!
+//! let host = "N1 G28*18\nN3 G1 X1*98\n".bytes().map(Result::<_, Error>::Ok);
+//! let limits = Limits {
+//!     line_number_digits: 10,
+//!     ..Limits::default()
+//! };
+//! let mut link = ReprapLink::new(Parser::with_limits(stream::iter(host), limits));
+//! let mut block = Block::<Unbounded>::new();
+//! futures_executor::block_on(async {
+//!     assert_eq!(link.next_block(&mut block).await, Some(Ok(Ok(()))));
+//!     // … executes G28 then acknowledges it.
+//!     assert_eq!(Reply::Ok.to_string(), "ok\n");
+//!
+//!     // line 2 is missing.
+//!     let reply = link.next_block(&mut block).await.unwrap().unwrap().unwrap_err();
+//!     assert_eq!(
+//!         reply.to_string(),
+//!         "Error:Line Number is not Last Line Number+1, Last Line: 1\nResend: 2\nok\n"
+//!     );
+//! });
+//! ```
+
+use core::fmt;
+
+use futures::Stream;
+
+use crate::{Block, BlockStorage, Checksum, Error, Parser, Real, RealValue, XorSum};
+
+/// Why a line must be sent again.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ResendReason {
+    /// The line's checksum does not match its content.
+    Checksum,
+    /// The line is not numbered after the last line received.
+    LineNumber,
+    /// The line is numbered but has no checksum.
+    MissingChecksum,
+    /// The line has a checksum but no number.
+    MissingLineNumber,
+    /// The numbered or checksummed line could not be parsed.
+    Parsing(Error),
+}
+
+/// How the line to send again is requested.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ResendStyle {
+    /// `Resend: <n>` as sent by Marlin.
+    #[default]
+    Resend,
+    /// `rs <n>` as sent by Repetier.
+    Rs,
+}
+
+/// A request to send the lines again, starting from `last_line + 1`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Resend {
+    pub reason: ResendReason,
+    pub last_line: u32,
+    pub style: ResendStyle,
+}
+
+/// A response to send to the host.
+///
+/// Its `Display` implementation writes the whole response, including its final new line.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Reply {
+    /// The line has been processed.
+    Ok,
+    /// The line was rejected and must be sent again.
+    Resend(Resend),
+    /// A line with neither number nor checksum could not be parsed. It is acknowledged but not sent
+    /// again.
+    Error(Error),
+}
+
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reply::Ok => {}
+            Reply::Resend(resend) => {
+                f.write_str("Error:")?;
+                match resend.reason {
+                    ResendReason::Checksum => f.write_str("checksum mismatch")?,
+                    ResendReason::LineNumber => {
+                        f.write_str("Line Number is not Last Line Number+1")?
+                    }
+                    ResendReason::MissingChecksum => f.write_str("No Checksum with line number")?,
+                    ResendReason::MissingLineNumber => {
+                        f.write_str("No Line Number with checksum")?
+                    }
+                    ResendReason::Parsing(error) => describe(error, f)?,
+                }
+                writeln!(f, ", Last Line: {}", resend.last_line)?;
+                let line = resend.last_line.wrapping_add(1);
+                match resend.style {
+                    ResendStyle::Resend => writeln!(f, "Resend: {}", line)?,
+                    ResendStyle::Rs => writeln!(f, "rs {}", line)?,
+                }
+            }
+            Reply::Error(error) => {
+                f.write_str("Error:")?;
+                describe(*error, f)?;
+                f.write_str("\n")?;
+            }
+        }
+        f.write_str("ok\n")
+    }
+}
+
+/// Enforces the sequence of the host's lines on top of a [`Parser`].
+///
+/// The parser's errors that are not parsing ones, such as the failures of a reader, are returned
+/// apart from the replies: the line is lost and the host is not answered.
+pub struct ReprapLink<S, E = Error, C = XorSum>
+where
+    S: Stream<Item = Result<u8, E>> + Unpin,
+{
+    parser: Parser<S, E, C>,
+    last_line: u32,
+    style: ResendStyle,
+}
+
+impl<S, E, C> ReprapLink<S, E, C>
+where
+    S: Stream<Item = Result<u8, E>> + Unpin,
+    E: From<Error> + TryInto<Error>,
+    C: Checksum,
+{
+    pub fn new(parser: Parser<S, E, C>) -> Self {
+        Self::with_style(parser, ResendStyle::default())
+    }
+
+    pub fn with_style(parser: Parser<S, E, C>, style: ResendStyle) -> Self {
+        Self {
+            parser,
+            last_line: 0,
+            style,
+        }
+    }
+
+    /// Number of the last line accepted.
+    pub fn last_line(&self) -> u32 {
+        self.last_line
+    }
+
+    pub fn into_inner(self) -> Parser<S, E, C> {
+        self.parser
+    }
+
+    /// Reads the next line into `block`.
+    ///
+    /// A valid line must be executed then acknowledged with [`Reply::Ok`]. Otherwise the returned
+    /// reply must be sent as is, it includes the acknowledgement.
+    ///
+    /// Unnumbered lines are accepted as long as they have no checksum. `M110` lines are accepted
+    /// regardless of their number and are also returned.
+    ///
+    /// The parser's errors that do not convert to an [`Error`] are returned as the outer error.
+    pub async fn next_block<B: BlockStorage>(
+        &mut self,
+        block: &mut Block<B>,
+    ) -> Option<Result<Result<(), Reply>, <E as TryInto<Error>>::Error>> {
+        let res = match self.parser.next_block(block).await? {
+            Ok(()) => Ok(()),
+            Err(error) => match error.try_into() {
+                Ok(error) => Err(error),
+                Err(error) => return Some(Err(error)),
+            },
+        };
+        Some(Ok(self.check(block, res)))
+    }
+
+    /// Decides the reply to the line read into `block`.
+    fn check<B: BlockStorage>(
+        &mut self,
+        block: &Block<B>,
+        res: Result<(), Error>,
+    ) -> Result<(), Reply> {
+        let is_m110 = block
+            .words
+            .iter()
+            .any(|(letter, value)| *letter == 'm' && as_real(value) == Some(110.));
+        let reason = match (res, block.line_number, block.checksum) {
+            (Err(Error::BadChecksum { .. }), Some(_), _) => ResendReason::Checksum,
+            (Err(Error::BadChecksum { .. }), None, _) => ResendReason::MissingLineNumber,
+            // the line number itself may be what could not be read.
+            (Err(error), _, _) if self.parser.framed() => ResendReason::Parsing(error),
+            (Err(error), _, _) => return Err(Reply::Error(error)),
+            (Ok(()), Some(_), None) => ResendReason::MissingChecksum,
+            (Ok(()), None, Some(_)) => ResendReason::MissingLineNumber,
+            (Ok(()), Some(n), Some(_)) if !is_m110 && Some(n) != self.last_line.checked_add(1) => {
+                ResendReason::LineNumber
+            }
+            (Ok(()), line_number, _) => {
+                if is_m110 {
+                    // `M110 N<n>` takes precedence over the line's own number.
+                    if let Some(n) = block.word('n').and_then(as_line_number) {
+                        self.last_line = n;
+                    } else if let Some(n) = line_number {
+                        self.last_line = n;
+                    }
+                } else if let Some(n) = line_number {
+                    self.last_line = n;
+                }
+                return Ok(());
+            }
+        };
+        Err(Reply::Resend(Resend {
+            reason,
+            last_line: self.last_line,
+            style: self.style,
+        }))
+    }
+}
+
+/// Describes a parsing error in a host's log, the protocol's messages are plain text.
+fn describe(error: Error, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match error {
+        Error::UnexpectedByte(b) if b.is_ascii_graphic() => {
+            write!(f, "unexpected '{}'", char::from(b))
+        }
+        Error::UnexpectedByte(b) => write!(f, "unexpected byte {:#04x}", b),
+        Error::NumberOverflow => f.write_str("number overflow"),
+        Error::BadNumberFormat => f.write_str("bad number format"),
+        #[cfg(any(feature = "parse-comments", feature = "string-value"))]
+        Error::InvalidUTF8String => f.write_str("invalid UTF-8"),
+        #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+        Error::InvalidExpression(_) => f.write_str("invalid expression"),
+        Error::BlockOverflow => f.write_str("line too long"),
+        #[cfg(feature = "parse-parameters")]
+        Error::NameTooLong => f.write_str("name too long"),
+        _ => f.write_str("invalid line"),
+    }
+}
+
+/// Reads a word's value as a number.
+fn as_real(value: &RealValue) -> Option<Real> {
+    match value {
+        RealValue::Literal(literal) => literal.as_real_number(),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+/// Reads a word's value as a line number.
+fn as_line_number(value: &RealValue) -> Option<u32> {
+    let value = as_real(value)?;
+    // the conversion saturates, out of range values do not convert back.
+    let n = value as u32;
+    (value >= 0. && n as Real == value).then_some(n)
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+
+    use super::{Reply, ReprapLink, ResendStyle};
+    use crate::{Block, Checksum, Crc8, Error, Limits, Parser, Unbounded};
+
+    /// Plays `host` against a firmware acknowledging every valid line, returns the replies.
+    fn replies(host: &str, style: ResendStyle) -> String {
+        let input = stream::iter(host.bytes().map(Result::<_, Error>::Ok));
+        let limits = Limits {
+            line_number_digits: 10,
+            ..Limits::default()
+        };
+        let mut link = ReprapLink::with_style(Parser::with_limits(input, limits), style);
+        let mut block = Block::<Unbounded>::new();
+        let mut replies = String::new();
+        futures_executor::block_on(async {
+            while let Some(res) = link.next_block(&mut block).await {
+                let reply = res.unwrap_or_else(|never| match never {});
+                replies += &reply.err().unwrap_or(Reply::Ok).to_string();
+            }
+        });
+        replies
+    }
+
+    #[test]
+    fn corrupted_lines_are_sent_again() {
+        let host = "N1 G28*18\nN2 G1 X1*98\nN2 G1 X1*99\n";
+        assert_eq!(
+            replies(host, ResendStyle::Resend),
+            "ok\nError:checksum mismatch, Last Line: 1\nResend: 2\nok\nok\n"
+        );
+    }
+
+    #[test]
+    fn lines_must_follow_each_other() {
+        let host = "N1 G28*18\nN4 G0*45\nN1 G1*41\n";
+        assert_eq!(
+            replies(host, ResendStyle::Rs),
+            concat!(
+                "ok\n",
+                "Error:Line Number is not Last Line Number+1, Last Line: 1\nrs 2\nok\n",
+                "Error:Line Number is not Last Line Number+1, Last Line: 1\nrs 2\nok\n",
+            )
+        );
+    }
+
+    #[test]
+    fn numbers_and_checksums_go_together() {
+        let host = "N1 G28\nG28*18\n";
+        assert_eq!(
+            replies(host, ResendStyle::Resend),
+            concat!(
+                "Error:No Checksum with line number, Last Line: 0\nResend: 1\nok\n",
+                "Error:No Line Number with checksum, Last Line: 0\nResend: 1\nok\n",
+            )
+        );
+    }
+
+    #[test]
+    fn m110_resets_the_line_number() {
+        let host = "N5 M110*38\nN0 M110 N0*125\nN1 G28*18\n";
+        assert_eq!(replies(host, ResendStyle::Resend), "ok\nok\nok\n");
+
+        let host = "N1 G28*18\nN5 M110*38\nN11 G1 X2*82\n";
+        assert_eq!(
+            replies(host, ResendStyle::Resend),
+            "ok\nok\nError:Line Number is not Last Line Number+1, Last Line: 5\nResend: 6\nok\n"
+        );
+    }
+
+    #[test]
+    fn line_numbers_go_past_9999() {
+        let host = "M110 N9998\nN9999 G1 X1*81\nN10000 G1 X1*96\n";
+        assert_eq!(replies(host, ResendStyle::Resend), "ok\nok\nok\n");
+    }
+
+    #[test]
+    fn framed_lines_that_cannot_be_parsed_are_sent_again() {
+        let host = "N1 G28*18\nN2 G1 X$1*71\nN99999999999 G28*26\nG1 $*114\n";
+        assert_eq!(
+            replies(host, ResendStyle::Resend),
+            concat!(
+                "ok\n",
+                "Error:unexpected '$', Last Line: 1\nResend: 2\nok\n",
+                "Error:number overflow, Last Line: 1\nResend: 2\nok\n",
+                "Error:unexpected '$', Last Line: 1\nResend: 2\nok\n",
+            )
+        );
+    }
+
+    #[test]
+    fn unnumbered_lines_are_acknowledged() {
+        assert_eq!(
+            replies("G28\nG1 $\n", ResendStyle::Resend),
+            "ok\nError:unexpected '$'\nok\n"
+        );
+    }
+
+    #[test]
+    fn m110_may_be_written_with_a_fraction() {
+        let host = "M110.0 N9\nN10 G28*34\n";
+        assert_eq!(replies(host, ResendStyle::Resend), "ok\nok\n");
+    }
+
+    #[test]
+    fn lines_may_be_checked_with_another_checksum() {
+        let mut crc = Crc8::default();
+        "N1 G28".bytes().for_each(|b| crc.update(b));
+        let host = format!("N1 G28*{}\nN2 G28*18\n", crc.value());
+        let input = stream::iter(host.into_bytes().into_iter().map(Result::<_, Error>::Ok));
+        let parser = Parser::with_checksum(input, Limits::default(), Crc8::default());
+        let mut link = ReprapLink::new(parser);
+        let mut block = Block::<Unbounded>::new();
+        futures_executor::block_on(async {
+            assert_eq!(link.next_block(&mut block).await, Some(Ok(Ok(()))));
+            let reply = link.next_block(&mut block).await.unwrap().unwrap();
+            assert!(matches!(reply, Err(Reply::Resend(_))));
+        });
+    }
+
+    #[test]
+    fn input_failures_are_not_replied_to() {
+        #[derive(Debug, PartialEq)]
+        enum LinkError {
+            Input,
+            Parsing(Error),
+        }
+        impl From<Error> for LinkError {
+            fn from(from: Error) -> Self {
+                Self::Parsing(from)
+            }
+        }
+        impl TryFrom<LinkError> for Error {
+            type Error = ();
+
+            fn try_from(from: LinkError) -> Result<Self, ()> {
+                match from {
+                    LinkError::Input => Err(()),
+                    LinkError::Parsing(error) => Ok(error),
+                }
+            }
+        }
+
+        let input = "N1 G28*18\nN2 G1".bytes().map(Ok);
+        let input = input
+            .chain([Err(LinkError::Input)])
+            .chain(" X1*99\n".bytes().map(Ok));
+        let mut link = ReprapLink::new(Parser::new(stream::iter(input)));
+        let mut block = Block::<Unbounded>::new();
+        futures_executor::block_on(async {
+            assert_eq!(link.next_block(&mut block).await, Some(Ok(Ok(()))));
+            assert_eq!(link.next_block(&mut block).await, Some(Err(())));
+            assert_eq!(link.last_line(), 1);
+        });
+    }
+}
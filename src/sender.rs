@@ -0,0 +1,423 @@
+//! Host side of the line numbered protocol used by RepRap firmwares.
+//!
+//! Lines are numbered and checksummed before being written, then kept until the firmware
+//! acknowledges them so they can be sent again on request. This is the counterpart of the
+//! `reprap` module.
+
+#[cfg(not(feature = "std"))]
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use crate::{Format, GCode, Serializer};
+
+/// Longest response line considered, the remaining bytes are ignored.
+const RESPONSE_LEN: usize = 32;
+
+/// Errors met while sending.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SenderError<W, R> {
+    /// The output sink failed.
+    Output(W),
+    /// The input stream failed.
+    Input(R),
+    /// The input stream ended while responses were expected.
+    Disconnected,
+    /// The firmware asked for a line that was already acknowledged.
+    LineUnavailable(u32),
+    /// A `GCode` could not be written as text.
+    Format,
+}
+
+/// Sends `GCode`s to a firmware, numbering and checksumming each line.
+///
+/// Up to `window` lines are written ahead of the firmware's acknowledgements. `Resend: <n>` and
+/// `rs <n>` responses rewind the transmission to line `n`.
+///
+/// ```
+/// use async_gcode::{GCode, Sender};
+/// use futures::{sink, stream};
+///
+/// let mut written = Vec::new();
+/// let output = core::pin::pin!(sink::unfold(&mut written, |written, b| async move {
+///     written.push(b);
+///     Ok::<_, ()>(written)
+/// }));
+/// let responses = stream::iter(b"ok\n".iter().map(|&b| Ok::<_, ()>(b)));
+///
+/// let mut sender = Sender::new(output, responses, 4);
+/// let program = [GCode::Word('g', 28.into()), GCode::Execute];
+/// futures_executor::block_on(sender.send(stream::iter(program))).unwrap();
+/// drop(sender);
+/// assert_eq!(written, b"N1 G28*18\n");
+/// ```
+pub struct Sender<W, R> {
+    output: W,
+    input: R,
+    format: Format,
+    /// The lines written but not acknowledged yet, followed by the ones to write again.
+    window: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// Number of the first line of the window.
+    first_line: u32,
+    /// Index in the window of the next line to write.
+    next: usize,
+    /// Number of responses awaited.
+    in_flight: usize,
+    /// Number of resend requests to ignore, for the lines written before the last rewind.
+    skip_resends: usize,
+    /// Whether the next `ok` acknowledges a rejected line.
+    rejected: bool,
+}
+
+impl<W, R, WE, RE> Sender<W, R>
+where
+    W: Sink<u8, Error = WE> + Unpin,
+    R: Stream<Item = Result<u8, RE>> + Unpin,
+{
+    pub fn new(output: W, input: R, window: usize) -> Self {
+        Self::with_format(output, input, window, Format::default())
+    }
+
+    /// The checksum is always written, regardless of `format.checksum`.
+    pub fn with_format(output: W, input: R, window: usize, format: Format) -> Self {
+        Self {
+            output,
+            input,
+            format: Format {
+                checksum: true,
+                ..format
+            },
+            window: VecDeque::with_capacity(window),
+            capacity: window.max(1),
+            first_line: 1,
+            next: 0,
+            in_flight: 0,
+            skip_resends: 0,
+            rejected: false,
+        }
+    }
+
+    pub fn into_inner(self) -> (W, R) {
+        (self.output, self.input)
+    }
+
+    /// Number of the next new line.
+    pub fn line_number(&self) -> u32 {
+        self.first_line.wrapping_add(self.window.len() as u32)
+    }
+
+    /// Sends `M110` so that the firmware expects the next line to be numbered 1.
+    ///
+    /// Lines left unacknowledged by a failed `send` are forgotten.
+    pub async fn reset(&mut self) -> Result<(), SenderError<WE, RE>> {
+        self.window.clear();
+        self.next = 0;
+        self.first_line = 0;
+        let reset = [
+            GCode::Word('m', 110.into()),
+            GCode::Word('n', 0.into()),
+            GCode::Execute,
+        ];
+        self.send(futures::stream::iter(reset)).await
+    }
+
+    /// Sends the lines of `gcodes` and waits for all of them to be acknowledged.
+    ///
+    /// Original line numbers and comments are left out, empty lines are skipped.
+    pub async fn send<G>(&mut self, mut gcodes: G) -> Result<(), SenderError<WE, RE>>
+    where
+        G: Stream<Item = GCode> + Unpin,
+    {
+        let mut done = false;
+        loop {
+            if let Some(line) = self.window.get(self.next) {
+                for &b in line {
+                    self.output.feed(b).await.map_err(SenderError::Output)?;
+                }
+                self.next += 1;
+                self.in_flight += 1;
+            } else if !done && self.window.len() < self.capacity {
+                match self.next_line(&mut gcodes).await? {
+                    Some(line) => self.window.push_back(line),
+                    None => done = true,
+                }
+            } else if self.in_flight != 0 {
+                self.output.flush().await.map_err(SenderError::Output)?;
+                self.receive().await?;
+            } else {
+                break;
+            }
+        }
+        self.output.flush().await.map_err(SenderError::Output)
+    }
+
+    /// Serializes the next non empty line of `gcodes`.
+    async fn next_line<G>(&mut self, gcodes: &mut G) -> Result<Option<Vec<u8>>, SenderError<WE, RE>>
+    where
+        G: Stream<Item = GCode> + Unpin,
+    {
+        let mut serializer = Serializer::new(String::new(), self.format);
+        let mut empty = true;
+        loop {
+            let gcode = match gcodes.next().await {
+                None if empty => return Ok(None),
+                Some(GCode::Execute) if empty => {
+                    // skip the empty line, a block delete may have been written.
+                    serializer = Serializer::new(String::new(), self.format);
+                    continue;
+                }
+                Some(GCode::LineNumber(_)) => continue,
+                #[cfg(feature = "parse-comments")]
+                Some(GCode::Comment(_)) => continue,
+                Some(gcode) => gcode,
+                None => GCode::Execute,
+            };
+            if empty && !matches!(gcode, GCode::BlockDelete | GCode::Execute) {
+                empty = false;
+                serializer
+                    .write_gcode(&GCode::LineNumber(self.line_number()))
+                    .map_err(|_| SenderError::Format)?;
+            }
+            serializer
+                .write_gcode(&gcode)
+                .map_err(|_| SenderError::Format)?;
+            if gcode == GCode::Execute {
+                return Ok(Some(serializer.into_inner().into_bytes()));
+            }
+        }
+    }
+
+    /// Reads and handles a response line.
+    async fn receive(&mut self) -> Result<(), SenderError<WE, RE>> {
+        let mut response = [0; RESPONSE_LEN];
+        let mut len = 0;
+        loop {
+            match self.input.next().await {
+                Some(Ok(b'\n')) => break,
+                Some(Ok(b)) => {
+                    if let Some(slot) = response.get_mut(len) {
+                        *slot = b;
+                        len += 1;
+                    }
+                }
+                Some(Err(e)) => return Err(SenderError::Input(e)),
+                None => return Err(SenderError::Disconnected),
+            }
+        }
+        let response = trim(&response[..len]);
+
+        if response.starts_with(b"ok") {
+            self.in_flight = self.in_flight.saturating_sub(1);
+            if !core::mem::take(&mut self.rejected) && self.next != 0 {
+                self.window.pop_front();
+                self.first_line = self.first_line.wrapping_add(1);
+                self.next -= 1;
+            }
+        } else if let Some(line) = response
+            .strip_prefix(b"Resend:")
+            .or_else(|| response.strip_prefix(b"rs "))
+            .and_then(parse_line_number)
+        {
+            self.rejected = true;
+            if self.skip_resends != 0 {
+                self.skip_resends -= 1;
+                return Ok(());
+            }
+            // the lines before the requested one have been received.
+            while self.first_line < line && !self.window.is_empty() {
+                self.window.pop_front();
+                self.first_line = self.first_line.wrapping_add(1);
+            }
+            if self.first_line != line {
+                return Err(SenderError::LineUnavailable(line));
+            }
+            // the lines following the rejected one will be rejected too.
+            self.skip_resends = self.in_flight.saturating_sub(1);
+            self.next = 0;
+        }
+        // other responses (errors, echoes, temperatures, …) are informative.
+        Ok(())
+    }
+}
+
+/// Removes the leading and trailing ASCII whitespaces.
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |end| end + 1);
+    &bytes[start..end]
+}
+
+fn parse_line_number(digits: &[u8]) -> Option<u32> {
+    core::str::from_utf8(digits).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use core::{convert::Infallible, pin::pin};
+
+    use futures::{sink, stream};
+
+    use super::{Sender, SenderError};
+    use crate::{Error, GCode, SyncParser};
+
+    fn program(text: &str) -> Vec<GCode> {
+        SyncParser::new(text.bytes().map(Result::<_, Error>::Ok))
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    /// Sends `text` to a firmware answering `responses`, returns what was written.
+    fn send(
+        text: &str,
+        responses: &str,
+        window: usize,
+    ) -> Result<String, SenderError<Infallible, ()>> {
+        let mut written = Vec::new();
+        let output = pin!(sink::unfold(&mut written, |written, b| async move {
+            written.push(b);
+            Ok(written)
+        }));
+        let input = stream::iter(responses.bytes().map(Ok));
+        let mut sender = Sender::new(output, input, window);
+        futures_executor::block_on(sender.send(stream::iter(program(text))))?;
+        drop(sender);
+        Ok(String::from_utf8(written).unwrap())
+    }
+
+    #[test]
+    fn lines_are_numbered_and_checksummed() {
+        assert_eq!(
+            send("N10 G28 (home)\n\nG1 X2\n", "ok\nok\n", 1),
+            Ok("N1 G28*18\nN2 G1 X2*96\n".to_string())
+        );
+    }
+
+    #[test]
+    fn rejected_lines_are_sent_again() {
+        let responses = concat!(
+            "ok\n",
+            "Error:checksum mismatch, Last Line: 1\nResend: 2\nok\n",
+            // the line that was already in flight is rejected as well.
+            "Error:Line Number is not Last Line Number+1, Last Line: 1\nResend: 2\nok\n",
+            "ok\nok\n",
+        );
+        assert_eq!(
+            send("G28\nG1 X2\nG1 X3\n", responses, 2),
+            Ok("N1 G28*18\nN2 G1 X2*96\nN3 G1 X3*96\nN2 G1 X2*96\nN3 G1 X3*96\n".to_string())
+        );
+    }
+
+    #[test]
+    fn repetier_resend_requests_are_understood() {
+        assert_eq!(
+            send("G28\n", "rs 1\nok\nok\n", 1),
+            Ok("N1 G28*18\nN1 G28*18\n".to_string())
+        );
+    }
+
+    /// One direction of an in-memory serial link.
+    #[cfg(feature = "parse-checksum")]
+    #[derive(Default)]
+    struct Pipe {
+        bytes: std::collections::VecDeque<u8>,
+        closed: bool,
+        reader: Option<core::task::Waker>,
+    }
+
+    #[cfg(feature = "parse-checksum")]
+    type Link = std::rc::Rc<std::cell::RefCell<Pipe>>;
+
+    #[cfg(feature = "parse-checksum")]
+    fn write(pipe: &Link, bytes: impl IntoIterator<Item = u8>) {
+        let mut pipe = pipe.borrow_mut();
+        pipe.bytes.extend(bytes);
+        if let Some(reader) = pipe.reader.take() {
+            reader.wake();
+        }
+    }
+
+    #[cfg(feature = "parse-checksum")]
+    fn close(pipe: &Link) {
+        pipe.borrow_mut().closed = true;
+        write(pipe, []);
+    }
+
+    #[cfg(feature = "parse-checksum")]
+    fn reader<E>(pipe: Link) -> impl futures::Stream<Item = Result<u8, E>> + Unpin {
+        stream::poll_fn(move |cx| {
+            let mut pipe = pipe.borrow_mut();
+            match pipe.bytes.pop_front() {
+                Some(b) => core::task::Poll::Ready(Some(Ok(b))),
+                None if pipe.closed => core::task::Poll::Ready(None),
+                None => {
+                    pipe.reader = Some(cx.waker().clone());
+                    core::task::Poll::Pending
+                }
+            }
+        })
+    }
+
+    #[test]
+    #[cfg(feature = "parse-checksum")]
+    fn long_programs_are_received_by_the_firmware() {
+        use crate::{
+            reprap::{Reply, ReprapLink},
+            Block, Parser, Unbounded,
+        };
+
+        const LINES: u32 = 10_050;
+        let to_firmware = Link::default();
+        let to_host = Link::default();
+
+        let output = pin!(sink::unfold(to_firmware.clone(), |pipe, b| async move {
+            write(&pipe, [b]);
+            Ok::<_, Infallible>(pipe)
+        }));
+        let mut sender = Sender::new(output, reader::<()>(to_host.clone()), 8);
+        let program = (0..LINES).flat_map(|x| [GCode::Word('x', x.into()), GCode::Execute]);
+        let host = async {
+            let res = sender.send(stream::iter(program)).await;
+            close(&to_firmware);
+            res
+        };
+
+        let mut link = ReprapLink::new(Parser::new(reader::<Error>(to_firmware.clone())));
+        let firmware = async {
+            let mut block = Block::<Unbounded>::new();
+            let mut received = 0;
+            while let Some(res) = link.next_block(&mut block).await {
+                let reply = res.unwrap_or_else(|never| match never {});
+                let reply = reply.err().unwrap_or(Reply::Ok);
+                if reply == Reply::Ok {
+                    assert_eq!(block.word('x'), Some(&received.into()));
+                    received += 1;
+                }
+                write(&to_host, reply.to_string().into_bytes());
+            }
+            received
+        };
+
+        let (sent, received) = futures_executor::block_on(futures::future::join(host, firmware));
+        assert_eq!(sent, Ok(()));
+        assert_eq!(received, LINES);
+        assert_eq!(link.last_line(), LINES);
+    }
+
+    #[test]
+    fn acknowledged_lines_cannot_be_sent_again() {
+        assert_eq!(
+            send("G28\nG1 X2\n", "ok\nResend: 1\n", 1),
+            Err(SenderError::LineUnavailable(1))
+        );
+        assert_eq!(send("G28\n", "", 1), Err(SenderError::Disconnected));
+    }
+}
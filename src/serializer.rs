@@ -0,0 +1,711 @@
+//! Turns `GCode`s back into text.
+//!
+//! The [`Serializer`] writes to any [`core::fmt::Write`] implementation. Expressions are written
+//! back in their infix form with as few brackets as the operators' precedence allows.
+
+use core::fmt::{self, Write};
+
+#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+use crate::types::expressions::{Expression, OpType, Operator};
+#[cfg(all(not(feature = "decimal"), not(feature = "f32")))]
+use crate::utils::math;
+#[cfg(all(not(feature = "decimal"), feature = "f32"))]
+use crate::utils::math::single as math;
+#[cfg(feature = "parse-parameters")]
+use crate::ParameterName;
+#[cfg(not(feature = "decimal"))]
+use crate::Real;
+use crate::{types::Literal, Checksum, GCode, RealValue, XorSum};
+#[cfg(feature = "parse-o-words")]
+use crate::{OCode, OWord};
+
+/// Layout options of the [`Serializer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    /// Maximum number of decimals written. Trailing zeros are always omitted, unless the
+    /// `decimal` feature is enabled: numbers then keep the digits they were parsed with.
+    pub precision: usize,
+    /// Writes letters and operators in upper case.
+    pub uppercase: bool,
+    /// Separates words, and the operands of binary operators, with a space.
+    pub spacing: bool,
+    /// Appends the checksum (`*sum`) to every non empty line, the xor one unless the serializer
+    /// is built with [`Serializer::with_checksum`].
+    pub checksum: bool,
+}
+impl Default for Format {
+    /// `G1 X1.5 Y[#1 + 2]`: 4 decimals, upper case, spaced and without checksum.
+    fn default() -> Self {
+        Self {
+            precision: 4,
+            uppercase: true,
+            spacing: true,
+            checksum: false,
+        }
+    }
+}
+
+/// Keeps track of the checksum of the current line.
+struct Output<W, C> {
+    inner: W,
+    sum: C,
+}
+impl<W: Write, C: Checksum> Write for Output<W, C> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        s.bytes().for_each(|b| self.sum.update(b));
+        self.inner.write_str(s)
+    }
+}
+
+/// Holds back the trailing zeros of a decimal number until a non zero digit is written.
+#[cfg(not(feature = "decimal"))]
+struct TrimZeros<'a, W> {
+    output: &'a mut W,
+    decimals: bool,
+    point: bool,
+    zeros: usize,
+}
+#[cfg(not(feature = "decimal"))]
+impl<W: Write> Write for TrimZeros<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '.' => {
+                    self.decimals = true;
+                    self.point = true;
+                }
+                '0' if self.decimals => self.zeros += 1,
+                _ => {
+                    if core::mem::take(&mut self.point) {
+                        self.output.write_char('.')?;
+                    }
+                    for _ in 0..core::mem::take(&mut self.zeros) {
+                        self.output.write_char('0')?;
+                    }
+                    self.output.write_char(c)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes `GCode`s as text.
+///
+/// ```
+/// use async_gcode::{Format, GCode, Serializer};
+///
+/// let mut serializer = Serializer::new(String::new(), Format::default());
+/// for gcode in [
+///     GCode::LineNumber(10),
+///     GCode::Word('g', 1.into()),
+///     GCode::Word('x', (-2.5).into()),
+///     GCode::Execute,
+/// ] {
+///     serializer.write_gcode(&gcode).unwrap();
+/// }
+/// assert_eq!(serializer.into_inner(), "N10 G1 X-2.5\n");
+/// ```
+pub struct Serializer<W, C = XorSum> {
+    output: Output<W, C>,
+    format: Format,
+    line_empty: bool,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(output: W, format: Format) -> Self {
+        Self::with_checksum(output, format, XorSum::default())
+    }
+}
+
+impl<W: Write, C: Checksum> Serializer<W, C> {
+    /// Builds a serializer appending the checksum `C` rather than [`XorSum`] when
+    /// `format.checksum` is set.
+    ///
+    /// ```
+    /// use async_gcode::{Crc8, Format, GCode, Serializer};
+    ///
+    /// let format = Format {
+    ///     checksum: true,
+    ///     ..Format::default()
+    /// };
+    /// let mut serializer = Serializer::with_checksum(String::new(), format, Crc8::default());
+    /// serializer.write_gcode(&GCode::Word('g', 0.into())).unwrap();
+    /// serializer.write_gcode(&GCode::Execute).unwrap();
+    /// assert_eq!(serializer.into_inner(), "G0*160\n");
+    /// ```
+    pub fn with_checksum(output: W, format: Format, checksum: C) -> Self {
+        Self {
+            output: Output {
+                inner: output,
+                sum: checksum,
+            },
+            format,
+            line_empty: true,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output.inner
+    }
+
+    fn write_letter(&mut self, letter: char) -> fmt::Result {
+        self.output.write_char(if self.format.uppercase {
+            letter.to_ascii_uppercase()
+        } else {
+            letter.to_ascii_lowercase()
+        })
+    }
+
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+    fn write_keyword(&mut self, keyword: &str) -> fmt::Result {
+        keyword.chars().try_for_each(|c| self.write_letter(c))
+    }
+
+    /// Separates an element from the previous one on the same line.
+    fn start_element(&mut self) -> fmt::Result {
+        if !core::mem::replace(&mut self.line_empty, false) && self.format.spacing {
+            self.output.write_char(' ')?;
+        }
+        Ok(())
+    }
+
+    /// Writes a `GCode`. `GCode::Execute` terminates the line, appending the checksum if required.
+    ///
+    /// Brackets, backslashes and new lines are escaped with a `\` in comments.
+    pub fn write_gcode(&mut self, gcode: &GCode) -> fmt::Result {
+        match gcode {
+            // the line number must immediately follow the block delete.
+            GCode::BlockDelete => self.output.write_char('/'),
+            GCode::LineNumber(n) => {
+                self.start_element()?;
+                self.write_letter('n')?;
+                write!(self.output, "{}", n)
+            }
+            #[cfg(feature = "parse-comments")]
+            GCode::Comment(text) => {
+                self.start_element()?;
+                self.output.write_char('(')?;
+                for c in text.chars() {
+                    if matches!(c, '(' | ')' | '\\' | '\n') {
+                        self.output.write_char('\\')?;
+                    }
+                    self.output.write_char(c)?;
+                }
+                self.output.write_char(')')
+            }
+            GCode::Word(letter, value) => {
+                self.start_element()?;
+                self.write_letter(*letter)?;
+                self.write_value(value)
+            }
+            #[cfg(feature = "parse-parameters")]
+            GCode::ParameterSet(index, value) => {
+                self.start_element()?;
+                self.output.write_char('#')?;
+                self.write_value(index)?;
+                self.output.write_char('=')?;
+                self.write_value(value)
+            }
+            #[cfg(feature = "parse-o-words")]
+            GCode::OCode(label, code) => {
+                self.start_element()?;
+                self.write_letter('o')?;
+                match label {
+                    OWord::Number(n) => write!(self.output, "{}", n)?,
+                    OWord::Name(name) => write!(self.output, "<{}>", name)?,
+                }
+                let (keyword, arguments) = match code {
+                    OCode::Sub => ("sub", &[][..]),
+                    OCode::EndSub(value) => ("endsub", value.as_slice()),
+                    OCode::Call(arguments) => ("call", arguments.as_slice()),
+                    OCode::Return(value) => ("return", value.as_slice()),
+                    OCode::If(condition) => ("if", core::slice::from_ref(condition)),
+                    OCode::ElseIf(condition) => ("elseif", core::slice::from_ref(condition)),
+                    OCode::Else => ("else", &[][..]),
+                    OCode::EndIf => ("endif", &[][..]),
+                    OCode::While(condition) => ("while", core::slice::from_ref(condition)),
+                    OCode::EndWhile => ("endwhile", &[][..]),
+                    OCode::Do => ("do", &[][..]),
+                    OCode::Repeat(count) => ("repeat", core::slice::from_ref(count)),
+                    OCode::EndRepeat => ("endrepeat", &[][..]),
+                    OCode::Break => ("break", &[][..]),
+                    OCode::Continue => ("continue", &[][..]),
+                };
+                self.output.write_char(' ')?;
+                self.write_keyword(keyword)?;
+                arguments.iter().try_for_each(|argument| {
+                    self.output.write_char(' ')?;
+                    self.write_argument(argument)
+                })
+            }
+            GCode::Execute => {
+                if self.format.checksum && !self.line_empty {
+                    let sum = self.output.sum.value();
+                    write!(self.output, "*{}", sum)?;
+                }
+                self.output.write_char('\n')?;
+                self.output.sum = C::default();
+                self.line_empty = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes a value as it would follow a word's letter.
+    pub fn write_value(&mut self, value: &RealValue) -> fmt::Result {
+        match value {
+            RealValue::Literal(literal) => self.write_literal(literal),
+            #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+            RealValue::Expression(expression) => self.write_expression(expression),
+            #[cfg(feature = "optional-value")]
+            RealValue::None => Ok(()),
+        }
+    }
+
+    fn write_literal(&mut self, literal: &Literal) -> fmt::Result {
+        match literal {
+            #[cfg(not(feature = "decimal"))]
+            Literal::RealNumber(value) => self.write_number(*value),
+            #[cfg(feature = "decimal")]
+            Literal::RealNumber(value) => {
+                let precision = u8::try_from(self.format.precision).unwrap_or(u8::MAX);
+                write!(self.output, "{}", value.round(precision))
+            }
+            #[cfg(feature = "string-value")]
+            Literal::String(string) => {
+                self.output.write_char('"')?;
+                for c in string.chars() {
+                    if matches!(c, '"' | '\\') {
+                        self.output.write_char('\\')?;
+                    }
+                    self.output.write_char(c)?;
+                }
+                self.output.write_char('"')
+            }
+            #[cfg(feature = "parse-parameters")]
+            Literal::Name(ParameterName::Local(name)) => write!(self.output, "<{}>", name),
+            #[cfg(feature = "parse-parameters")]
+            Literal::Name(ParameterName::Global(name)) => write!(self.output, "<_{}>", name),
+        }
+    }
+
+    #[cfg(not(feature = "decimal"))]
+    fn write_number(&mut self, mut value: Real) -> fmt::Result {
+        if !value.is_finite() {
+            return Err(fmt::Error);
+        }
+        // avoid writing `-0` for values rounded to zero.
+        if math::abs(value) * math::pow(10., self.format.precision as Real) < 0.5 {
+            value = 0.;
+        }
+        let mut output = TrimZeros {
+            output: &mut self.output,
+            decimals: false,
+            point: false,
+            zeros: 0,
+        };
+        write!(output, "{:.*}", self.format.precision, value)
+    }
+
+    /// Writes a value in brackets, as the arguments of O-codes.
+    #[cfg(feature = "parse-o-words")]
+    fn write_argument(&mut self, value: &RealValue) -> fmt::Result {
+        match value {
+            // infix expressions are already written in brackets.
+            RealValue::Expression(expression)
+                if expression
+                    .0
+                    .len()
+                    .checked_sub(1)
+                    .is_some_and(|root| is_infix(&expression.0, root)) =>
+            {
+                self.write_value(value)
+            }
+            value => {
+                self.output.write_char('[')?;
+                self.write_value(value)?;
+                self.output.write_char(']')
+            }
+        }
+    }
+
+    #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+    fn write_expression(&mut self, expression: &Expression) -> fmt::Result {
+        #[cfg(not(feature = "std"))]
+        use alloc::vec::Vec;
+        use either::Either;
+
+        // `starts[i]` is the index of the first item of the sub-expression ending at `i`.
+        let items = &expression.0;
+        let mut starts = Vec::with_capacity(items.len());
+        let mut stack = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            let start = match item {
+                Either::Left(op) if op.op_type() == OpType::Binary => {
+                    stack.pop().ok_or(fmt::Error)?;
+                    stack.pop().ok_or(fmt::Error)?
+                }
+                Either::Left(_) => stack.pop().ok_or(fmt::Error)?,
+                Either::Right(_) => i,
+            };
+            stack.push(start);
+            starts.push(start);
+        }
+        if stack.len() != 1 {
+            return Err(fmt::Error);
+        }
+
+        // only bracketed expressions, parameters and functions may be used as a word's value.
+        let root = items.len() - 1;
+        let operand = |i, brackets| {
+            if brackets {
+                Step::Bracketed(i)
+            } else {
+                Step::Node(i)
+            }
+        };
+        let separator = if self.format.spacing { " " } else { "" };
+
+        // the tree is walked using an explicit stack of the steps left to write.
+        let mut steps = Vec::new();
+        steps.push(operand(root, is_infix(items, root)));
+        while let Some(step) = steps.pop() {
+            let end = match step {
+                Step::Text(text) => {
+                    self.output.write_str(text)?;
+                    continue;
+                }
+                Step::Keyword(keyword) => {
+                    self.write_keyword(keyword)?;
+                    continue;
+                }
+                Step::Bracketed(end) => {
+                    steps.extend([Step::Text("]"), Step::Node(end), Step::Text("[")]);
+                    continue;
+                }
+                Step::Node(end) => end,
+            };
+            let op = match &items[end] {
+                Either::Right(literal) => {
+                    self.write_literal(literal)?;
+                    continue;
+                }
+                Either::Left(op) => *op,
+            };
+            let rhs = end - 1;
+            match op {
+                #[cfg(feature = "parse-parameters")]
+                Operator::GetParameter => {
+                    steps.extend([operand(rhs, is_infix(items, rhs)), Step::Text("#")])
+                }
+                Operator::ATan => steps.extend([
+                    Step::Text("]"),
+                    Step::Node(rhs),
+                    Step::Text("]/["),
+                    Step::Node(starts[rhs] - 1),
+                    Step::Text("["),
+                    Step::Keyword(symbol(op)),
+                ]),
+                #[cfg(feature = "parse-min-max")]
+                Operator::Min | Operator::Max => steps.extend([
+                    Step::Text("]"),
+                    Step::Node(rhs),
+                    Step::Text(separator),
+                    Step::Text(","),
+                    Step::Node(starts[rhs] - 1),
+                    Step::Text("["),
+                    Step::Keyword(symbol(op)),
+                ]),
+                #[cfg(feature = "parse-parameters")]
+                Operator::Exists => steps.extend([
+                    Step::Text("]"),
+                    Step::Node(rhs),
+                    Step::Text("[#"),
+                    Step::Keyword(symbol(op)),
+                ]),
+                op if op.op_type() == OpType::Unary => steps.extend([
+                    Step::Text("]"),
+                    Step::Node(rhs),
+                    Step::Text("["),
+                    Step::Keyword(symbol(op)),
+                ]),
+                op => {
+                    // operators are all left associative: only the right hand side needs
+                    // brackets when its operator has the same precedence.
+                    let lhs = starts[rhs] - 1;
+                    let precedence = |i| match &items[i] {
+                        Either::Left(op) if is_infix(items, i) => Some(op.precedence()),
+                        _ => None,
+                    };
+                    steps.extend([
+                        operand(rhs, precedence(rhs).is_some_and(|p| p <= op.precedence())),
+                        Step::Text(separator),
+                        Step::Keyword(symbol(op)),
+                        Step::Text(separator),
+                        operand(lhs, precedence(lhs).is_some_and(|p| p < op.precedence())),
+                    ]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What is left to write of an expression.
+#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+enum Step {
+    /// The sub-expression ending at the given index.
+    Node(usize),
+    /// The sub-expression ending at the given index, in brackets.
+    Bracketed(usize),
+    Text(&'static str),
+    Keyword(&'static str),
+}
+
+/// Whether the item at `i` is a binary operator written between its operands.
+#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+fn is_infix(items: &[crate::types::expressions::ExprItem], i: usize) -> bool {
+    match &items[i] {
+        either::Either::Left(Operator::ATan) => false,
+        #[cfg(feature = "parse-min-max")]
+        either::Either::Left(Operator::Min | Operator::Max) => false,
+        either::Either::Left(op) => op.op_type() == OpType::Binary,
+        either::Either::Right(_) => false,
+    }
+}
+
+#[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
+fn symbol(op: Operator) -> &'static str {
+    match op {
+        Operator::Add => "+",
+        Operator::Substract => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Power => "**",
+        Operator::And => "and",
+        Operator::Or => "or",
+        Operator::Xor => "xor",
+        Operator::Modulus => "mod",
+        Operator::Equal => "eq",
+        Operator::NotEqual => "ne",
+        Operator::GreaterThan => "gt",
+        Operator::GreaterOrEqual => "ge",
+        Operator::LessThan => "lt",
+        Operator::LessOrEqual => "le",
+        Operator::Cos => "cos",
+        Operator::Sin => "sin",
+        Operator::Tan => "tan",
+        Operator::ACos => "acos",
+        Operator::ASin => "asin",
+        Operator::ATan => "atan",
+        Operator::Abs => "abs",
+        Operator::Exp => "exp",
+        Operator::Fix => "fix",
+        Operator::Fup => "fup",
+        Operator::Ln => "ln",
+        Operator::Round => "round",
+        Operator::Sqrt => "sqrt",
+        #[cfg(feature = "parse-min-max")]
+        Operator::Min => "min",
+        #[cfg(feature = "parse-min-max")]
+        Operator::Max => "max",
+        #[cfg(feature = "parse-parameters")]
+        Operator::GetParameter => "#",
+        #[cfg(feature = "parse-parameters")]
+        Operator::Exists => "exists",
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::new(f, Format::default()).write_literal(self)
+    }
+}
+
+impl fmt::Display for RealValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Serializer::new(f, Format::default()).write_value(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream::{self, StreamExt};
+
+    use super::{Format, Serializer};
+    #[cfg(feature = "parse-checksum")]
+    use crate::{Crc16, Limits};
+    use crate::{Error, GCode, Parser};
+
+    fn parse(input: &str) -> Vec<GCode> {
+        let mut parser = Parser::new(stream::iter(input.bytes()).map(Result::<_, Error>::Ok));
+        futures_executor::block_on(async {
+            let mut gcodes = Vec::new();
+            while let Some(gcode) = parser.next().await {
+                gcodes.push(gcode.expect("parsing failed"));
+            }
+            gcodes
+        })
+    }
+
+    fn serialize(gcodes: &[GCode], format: Format) -> String {
+        let mut serializer = Serializer::new(String::new(), format);
+        for gcode in gcodes {
+            serializer.write_gcode(gcode).unwrap();
+        }
+        serializer.into_inner()
+    }
+
+    fn assert_round_trip(input: &str, expected: &str) {
+        let gcodes = parse(input);
+        let output = serialize(&gcodes, Format::default());
+        assert_eq!(output, expected);
+        assert_eq!(parse(&output), gcodes);
+    }
+
+    #[test]
+    fn words_and_line_numbers() {
+        #[cfg(not(feature = "decimal"))]
+        assert_round_trip("/n10 g1x2.50y-.5 z3\n\n", "/N10 G1 X2.5 Y-0.5 Z3\n\n");
+        #[cfg(feature = "decimal")]
+        assert_round_trip("/n10 g1x2.50y-.5 z3\n\n", "/N10 G1 X2.50 Y-0.5 Z3\n\n");
+    }
+
+    #[test]
+    fn numbers_are_rounded_to_the_precision() {
+        let gcodes = [
+            GCode::Word('x', 1.23456.into()),
+            GCode::Word('y', (-0.00001).into()),
+            GCode::Word('z', 100.into()),
+            GCode::Execute,
+        ];
+        assert_eq!(serialize(&gcodes, Format::default()), "X1.2346 Y0 Z100\n");
+        let format = Format {
+            precision: 1,
+            uppercase: false,
+            spacing: false,
+            ..Format::default()
+        };
+        assert_eq!(serialize(&gcodes, format), "x1.2y0z100\n");
+    }
+
+    #[test]
+    fn checksum_covers_the_whole_line() {
+        let format = Format {
+            checksum: true,
+            ..Format::default()
+        };
+        let gcodes = [
+            GCode::Word('g', 0.into()),
+            GCode::Execute,
+            GCode::Execute,
+            GCode::LineNumber(2),
+            GCode::Word('g', 1.into()),
+            GCode::Execute,
+        ];
+        let output = serialize(&gcodes, format);
+        assert_eq!(output, "G0*119\n\nN2 G1*42\n");
+        #[cfg(feature = "parse-checksum")]
+        assert_eq!(parse(&output), gcodes);
+    }
+
+    #[test]
+    #[cfg(feature = "parse-checksum")]
+    fn other_checksums_round_trip() {
+        let format = Format {
+            checksum: true,
+            ..Format::default()
+        };
+        let gcodes = [
+            GCode::LineNumber(2),
+            GCode::Word('g', 1.into()),
+            GCode::Execute,
+        ];
+        let mut serializer = Serializer::with_checksum(String::new(), format, Crc16::default());
+        for gcode in &gcodes {
+            serializer.write_gcode(gcode).unwrap();
+        }
+        let output = serializer.into_inner();
+        assert_eq!(output, "N2 G1*15010\n");
+
+        let input = stream::iter(output.into_bytes()).map(Result::<_, Error>::Ok);
+        let parser = Parser::with_checksum(input, Limits::default(), Crc16::default());
+        assert_eq!(
+            futures_executor::block_on(parser.into_stream().collect::<Vec<_>>()),
+            gcodes.map(Ok)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "parse-comments")]
+    fn comments_are_escaped() {
+        assert_round_trip("G0 (hello) X1 \n", "G0 (hello) X1\n");
+        assert_round_trip("(a \\(b\\)\\\nc\\\\)\n", "(a \\(b\\)\\\nc\\\\)\n");
+        let gcodes = [GCode::Comment("a (b)\nc\\".into()), GCode::Execute];
+        assert_eq!(parse(&serialize(&gcodes, Format::default())), gcodes);
+    }
+
+    #[test]
+    #[cfg(feature = "string-value")]
+    fn strings_are_escaped() {
+        assert_round_trip(r#"P "say \"hi\\" "#, r#"P"say \"hi\\""#);
+    }
+
+    #[test]
+    #[cfg(feature = "parse-parameters")]
+    fn parameters() {
+        assert_round_trip("#1=2 G#1 X##2\n", "#1=2 G#1 X##2\n");
+        assert_round_trip("#<_a b>=#<c> G##<d>\n", "#<_ab>=#<c> G##<d>\n");
+    }
+
+    #[test]
+    #[cfg(feature = "parse-o-words")]
+    fn o_codes() {
+        assert_round_trip(
+            "o<a b> sub\nO1 call [2] [#1 + 1] [#2]\no1 return\nO1 ENDSUB [1]\n",
+            "O<ab> SUB\nO1 CALL [2] [#1 + 1] [#2]\nO1 RETURN\nO1 ENDSUB [1]\n",
+        );
+        assert_round_trip(
+            "o1 if [#1]\no1 elseif [#2 + 1]\no1 else\no1 endif\no2 do\no2 while [1]\n",
+            "O1 IF [#1]\nO1 ELSEIF [#2 + 1]\nO1 ELSE\nO1 ENDIF\nO2 DO\nO2 WHILE [1]\n",
+        );
+        assert_round_trip(
+            "o3 repeat [2]\no3 break\no3 continue\no3 endrepeat\no4 endwhile\n",
+            "O3 REPEAT [2]\nO3 BREAK\nO3 CONTINUE\nO3 ENDREPEAT\nO4 ENDWHILE\n",
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "parse-expressions")]
+    fn expressions_use_minimal_brackets() {
+        assert_round_trip(
+            "X[1 + [2 * 3]] Y[[1 - 2] - [3 - 4]] Z[2 ** [3 ** 2]]\n",
+            "X[1 + 2 * 3] Y[1 - 2 - [3 - 4]] Z[2 ** [3 ** 2]]\n",
+        );
+        assert_round_trip(
+            "X[[1 + 2] * 3 mod 4] Ycos[[1 + 2] / 3] Zatan[1]/[2 - 3]\n",
+            "X[[1 + 2] * 3 MOD 4] YCOS[[1 + 2] / 3] ZATAN[1]/[2 - 3]\n",
+        );
+        assert_round_trip("X[1 + -2 and 3] ", "X[1 + -2 AND 3]");
+        assert_round_trip(
+            "X[[1 + 2 gt 3] and [4 le 5]] Y[1 eq [2 ne 3]] Z[1 + 2 gt 3 and 4]\n",
+            "X[[1 + 2 GT 3] AND [4 LE 5]] Y[1 EQ [2 NE 3]] Z[1 + 2 GT 3 AND 4]\n",
+        );
+        #[cfg(feature = "parse-min-max")]
+        assert_round_trip("Xmin[1+2,max[3, 4]]", "XMIN[1 + 2, MAX[3, 4]]");
+    }
+
+    #[test]
+    #[cfg(all(feature = "parse-parameters", feature = "parse-expressions"))]
+    fn parameters_in_expressions() {
+        assert_round_trip(
+            "X[#1 + #[2 + 3]] Y#cos[1] #[1 + 1]=[#2 * 2]\n",
+            "X[#1 + #[2 + 3]] Y#COS[1] #[1 + 1]=[#2 * 2]\n",
+        );
+        assert_round_trip(
+            "X[exists[#<a>] + 1] Yexists[##2]\n",
+            "X[EXISTS[#<a>] + 1] YEXISTS[##2]\n",
+        );
+    }
+}
@@ -1,46 +1,77 @@
 #[cfg(all(
     not(feature = "std"),
-    any(feature = "parse-comments", feature = "string-value")
+    any(
+        feature = "parse-comments",
+        feature = "string-value",
+        feature = "parse-parameters"
+    )
 ))]
 use alloc::string::String;
 
-#[derive(Debug)]
-pub(crate) enum ParseResult<G, E> {
-    Input(E),
-    Parsing(crate::Error),
-    Ok(G),
-}
-impl<O, E> From<core::result::Result<O, E>> for ParseResult<O, E> {
-    fn from(f: core::result::Result<O, E>) -> Self {
-        match f {
-            Ok(o) => ParseResult::Ok(o),
-            Err(e) => ParseResult::Input(e),
-        }
-    }
-}
+#[cfg(all(not(feature = "std"), feature = "parse-o-words"))]
+use alloc::vec::Vec;
 
-impl<O, E> From<crate::Error> for ParseResult<O, E> {
-    fn from(f: crate::Error) -> Self {
-        ParseResult::Parsing(f)
-    }
-}
+#[cfg(feature = "decimal")]
+use crate::Decimal;
 
-#[cfg(not(feature = "parse-comments"))]
-pub type Comment = ();
 #[cfg(feature = "parse-comments")]
 pub type Comment = String;
 
+/// The floating point type used for real numbers: `f32` with the `f32` feature, `f64` otherwise.
+#[cfg(not(feature = "f32"))]
+pub type Real = f64;
+/// The floating point type used for real numbers: `f32` with the `f32` feature, `f64` otherwise.
+#[cfg(feature = "f32")]
+pub type Real = f32;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
-    RealNumber(f64),
+    #[cfg(not(feature = "decimal"))]
+    RealNumber(Real),
+    #[cfg(feature = "decimal")]
+    RealNumber(Decimal),
     #[cfg(feature = "string-value")]
     String(String),
+    #[cfg(feature = "parse-parameters")]
+    /// The name of a parameter, only found as the operand of `#`, e.g. `#<tool_radius>`.
+    Name(ParameterName),
+}
+
+/// The name of a LinuxCNC named parameter, lowercased and without its whitespaces.
+#[cfg(feature = "parse-parameters")]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub enum ParameterName {
+    /// A parameter local to the subroutine setting it, e.g. `#<tool_radius>`.
+    Local(String),
+    /// A parameter visible from everywhere, e.g. `#<_global>`. The name is stored without its
+    /// leading `_`.
+    Global(String),
+}
+#[cfg(feature = "parse-parameters")]
+impl ParameterName {
+    /// The name, without the leading `_` of global parameters.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Local(name) | Self::Global(name) => name,
+        }
+    }
 }
+// `RealNumber` holds a `Real` unless `decimal` is enabled.
+#[allow(clippy::useless_conversion)]
 impl Literal {
-    pub fn as_real_number(&self) -> Option<f64> {
+    pub fn as_real_number(&self) -> Option<Real> {
+        match self {
+            Literal::RealNumber(rn) => Some((*rn).into()),
+            #[cfg(any(feature = "string-value", feature = "parse-parameters"))]
+            _ => None,
+        }
+    }
+    /// The number as it was parsed.
+    #[cfg(feature = "decimal")]
+    pub fn as_decimal(&self) -> Option<Decimal> {
         match self {
             Literal::RealNumber(rn) => Some(*rn),
-            #[cfg(feature = "string-value")]
+            #[cfg(any(feature = "string-value", feature = "parse-parameters"))]
             _ => None,
         }
     }
@@ -54,16 +85,46 @@ impl Literal {
 }
 impl From<i32> for Literal {
     fn from(from: i32) -> Self {
-        Self::RealNumber(from as f64)
+        #[cfg(not(feature = "decimal"))]
+        let value = from as Real;
+        #[cfg(feature = "decimal")]
+        let value = from.into();
+        Self::RealNumber(value)
     }
 }
 impl From<u32> for Literal {
     fn from(from: u32) -> Self {
-        Self::RealNumber(from as f64)
+        #[cfg(not(feature = "decimal"))]
+        let value = from as Real;
+        #[cfg(feature = "decimal")]
+        let value = from.into();
+        Self::RealNumber(value)
+    }
+}
+#[cfg(any(not(feature = "f32"), feature = "decimal"))]
+#[allow(clippy::useless_conversion)]
+impl From<f64> for Literal {
+    fn from(from: f64) -> Self {
+        Self::RealNumber(from.into())
     }
 }
+/// The value is rounded to the nearest `f32`.
+#[cfg(all(feature = "f32", not(feature = "decimal")))]
 impl From<f64> for Literal {
     fn from(from: f64) -> Self {
+        Self::from(from as f32)
+    }
+}
+#[cfg(feature = "f32")]
+#[allow(clippy::useless_conversion)]
+impl From<f32> for Literal {
+    fn from(from: f32) -> Self {
+        Self::RealNumber(from.into())
+    }
+}
+#[cfg(feature = "decimal")]
+impl From<Decimal> for Literal {
+    fn from(from: Decimal) -> Self {
         Self::RealNumber(from)
     }
 }
@@ -75,6 +136,59 @@ impl From<String> for Literal {
     }
 }
 
+#[cfg(feature = "parse-parameters")]
+impl From<ParameterName> for Literal {
+    fn from(from: ParameterName) -> Self {
+        Self::Name(from)
+    }
+}
+
+/// The label of an O-code, `O100` or `O<name>`. Names are lowercased and without whitespaces.
+#[cfg(feature = "parse-o-words")]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum OWord {
+    Number(u32),
+    Name(String),
+}
+
+/// The statement following the label of an O-code.
+#[cfg(feature = "parse-o-words")]
+#[derive(Debug, PartialEq, Clone)]
+pub enum OCode {
+    /// `sub`: starts the definition of a subroutine.
+    Sub,
+    /// `endsub [value]`: ends the definition of a subroutine, returning from it when executed.
+    EndSub(Option<RealValue>),
+    /// `call [arg]…`: calls a subroutine, binding its arguments to `#1`, `#2`…
+    Call(Vec<RealValue>),
+    /// `return [value]`: returns from a subroutine.
+    Return(Option<RealValue>),
+    /// `if [condition]`: starts a conditional block.
+    If(RealValue),
+    /// `elseif [condition]`: the branch taken when the previous conditions are false and this
+    /// one is true.
+    ElseIf(RealValue),
+    /// `else`: the branch taken when all the conditions are false.
+    Else,
+    /// `endif`: ends a conditional block.
+    EndIf,
+    /// `while [condition]`: starts a loop running as long as the condition holds, or ends a `do`
+    /// loop with the same label.
+    While(RealValue),
+    /// `endwhile`: ends a `while` loop.
+    EndWhile,
+    /// `do`: starts a loop whose condition follows its body, in a `while`.
+    Do,
+    /// `repeat [count]`: starts a loop running `count` times.
+    Repeat(RealValue),
+    /// `endrepeat`: ends a `repeat` loop.
+    EndRepeat,
+    /// `break`: leaves the loop with the same label.
+    Break,
+    /// `continue`: starts the next iteration of the loop with the same label.
+    Continue,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum RealValue {
     Literal(Literal),
@@ -96,13 +210,21 @@ impl<T: Into<Literal>> From<T> for RealValue {
 
 #[cfg(any(feature = "parse-parameters", feature = "parse-expressions"))]
 pub(crate) mod expressions {
-    use super::{Literal, RealValue};
-    use crate::Error;
+    use super::{Literal, Real, RealValue};
+    use crate::{Error, ExpressionError};
+
+    #[cfg(not(feature = "f32"))]
+    use crate::utils::math;
+    #[cfg(feature = "f32")]
+    use crate::utils::math::single as math;
     use either::Either;
 
     #[cfg(not(feature = "std"))]
     use alloc::vec::Vec;
 
+    /// Values closer than this are equal for `EQ` and `NE`, as in LinuxCNC.
+    const TOLERANCE_EQUAL: Real = 1e-4;
+
     pub(crate) type ExprItem = Either<Operator, Literal>;
     pub(crate) type ExprInner = Vec<ExprItem>;
 
@@ -117,14 +239,17 @@ pub(crate) mod expressions {
         #[cfg(feature = "parse-parameters")]
         Right,
     }
+    /// From the lowest to the highest: comparisons, additions and logical operators as in
+    /// RS274/NGC, multiplications, power, parameters and functions.
     #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Eq)]
     pub enum Precedence {
         Group1,
         Group2,
         Group3,
-        #[cfg(feature = "parse-parameters")]
         Group4,
+        #[cfg(feature = "parse-parameters")]
         Group5,
+        Group6,
     }
 
     #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -142,6 +267,13 @@ pub(crate) mod expressions {
 
         Modulus,
 
+        Equal,
+        NotEqual,
+        GreaterThan,
+        GreaterOrEqual,
+        LessThan,
+        LessOrEqual,
+
         // Unary operators
         Cos,
         Sin,
@@ -158,8 +290,16 @@ pub(crate) mod expressions {
         Round,
         Sqrt,
 
+        // Binary functions
+        #[cfg(feature = "parse-min-max")]
+        Min,
+        #[cfg(feature = "parse-min-max")]
+        Max,
+
         #[cfg(feature = "parse-parameters")]
         GetParameter,
+        #[cfg(feature = "parse-parameters")]
+        Exists,
     }
     impl Operator {
         pub fn op_type(&self) -> OpType {
@@ -173,7 +313,15 @@ pub(crate) mod expressions {
                 | Self::And
                 | Self::Or
                 | Self::Xor
+                | Self::Equal
+                | Self::NotEqual
+                | Self::GreaterThan
+                | Self::GreaterOrEqual
+                | Self::LessThan
+                | Self::LessOrEqual
                 | Self::ATan => OpType::Binary,
+                #[cfg(feature = "parse-min-max")]
+                Self::Min | Self::Max => OpType::Binary,
                 Self::Cos
                 | Self::Sin
                 | Self::Tan
@@ -187,7 +335,7 @@ pub(crate) mod expressions {
                 | Self::Round
                 | Self::Sqrt => OpType::Unary,
                 #[cfg(feature = "parse-parameters")]
-                Self::GetParameter => OpType::Unary,
+                Self::GetParameter | Self::Exists => OpType::Unary,
             }
         }
 
@@ -202,13 +350,19 @@ pub(crate) mod expressions {
 
         pub fn precedence(&self) -> Precedence {
             match *self {
+                Self::Equal
+                | Self::NotEqual
+                | Self::GreaterThan
+                | Self::GreaterOrEqual
+                | Self::LessThan
+                | Self::LessOrEqual => Precedence::Group1,
                 Self::Add | Self::Substract | Self::And | Self::Or | Self::Xor => {
-                    Precedence::Group1
+                    Precedence::Group2
                 }
-                Self::Multiply | Self::Divide | Self::Modulus => Precedence::Group2,
-                Self::Power => Precedence::Group3,
+                Self::Multiply | Self::Divide | Self::Modulus => Precedence::Group3,
+                Self::Power => Precedence::Group4,
                 #[cfg(feature = "parse-parameters")]
-                Self::GetParameter => Precedence::Group4,
+                Self::GetParameter => Precedence::Group5,
                 Self::Cos
                 | Self::Sin
                 | Self::Tan
@@ -221,24 +375,148 @@ pub(crate) mod expressions {
                 | Self::Fup
                 | Self::Ln
                 | Self::Round
-                | Self::Sqrt => Precedence::Group5,
+                | Self::Sqrt => Precedence::Group6,
+                #[cfg(feature = "parse-min-max")]
+                Self::Min | Self::Max => Precedence::Group6,
+                #[cfg(feature = "parse-parameters")]
+                Self::Exists => Precedence::Group6,
             }
         }
     }
 
+    /// Resolves the parameters read by an expression.
+    ///
+    /// It is implemented by closures taking the raw index of a parameter and returning its value,
+    /// if it is defined.
+    #[cfg(feature = "parse-parameters")]
+    pub trait ParameterResolver {
+        /// Returns the value of the parameter at `index`, or `None` if it is not defined.
+        fn resolve(&mut self, index: Literal) -> Result<Option<Literal>, Error>;
+    }
+    #[cfg(feature = "parse-parameters")]
+    impl<F: FnMut(Literal) -> Option<Literal>> ParameterResolver for F {
+        fn resolve(&mut self, index: Literal) -> Result<Option<Literal>, Error> {
+            Ok(self(index))
+        }
+    }
+
     #[derive(Debug, PartialEq, Clone)]
     pub struct Expression(pub(crate) ExprInner);
     impl Expression {
         /// Evaluates the expressions to a single literal. Math error may occur during the
         /// expression's resolution (e.g. division by 0).
         ///
-        /// When `parse-parameters` is enabled, this method takes a closure as an argument.
-        /// This closure is used to resolve parameters get.
+        /// When `parse-parameters` is enabled, this method takes the resolver of the parameters
+        /// as an argument. Undefined parameters are worth 0, `EXISTS` evaluates to 1 or 0
+        /// depending on whether its parameter is defined.
         pub fn evaluate(
             &self,
-            #[cfg(feature = "parse-parameters")] _cbk: &mut dyn FnMut(Literal) -> Literal,
+            #[cfg(feature = "parse-parameters")] resolver: &mut dyn ParameterResolver,
         ) -> Result<Literal, Error> {
-            todo!()
+            let mut stack: Vec<Literal> = Vec::new();
+            for item in &self.0 {
+                let result = match item {
+                    Either::Right(literal) => literal.clone(),
+                    #[cfg(feature = "parse-parameters")]
+                    Either::Left(Operator::GetParameter) => resolver
+                        .resolve(stack.pop().ok_or(Error::StackUnderflow)?)?
+                        .unwrap_or(Literal::from(0)),
+                    #[cfg(feature = "parse-parameters")]
+                    Either::Left(Operator::Exists) => {
+                        let index = stack.pop().ok_or(Error::StackUnderflow)?;
+                        Literal::from(i32::from(resolver.resolve(index)?.is_some()))
+                    }
+                    Either::Left(op) if op.op_type() == OpType::Binary => {
+                        let rhs = pop_real_number(&mut stack)?;
+                        let lhs = pop_real_number(&mut stack)?;
+                        op.apply_binary(lhs, rhs)?.into()
+                    }
+                    Either::Left(op) => op.apply_unary(pop_real_number(&mut stack)?)?.into(),
+                };
+                stack.push(result);
+            }
+
+            let result = stack.pop().ok_or(Error::StackUnderflow)?;
+            if stack.is_empty() {
+                Ok(result)
+            } else {
+                Err(Error::InvalidExpression(ExpressionError::ExtraOperands))
+            }
+        }
+    }
+
+    fn pop_real_number(stack: &mut Vec<Literal>) -> Result<Real, Error> {
+        stack
+            .pop()
+            .ok_or(Error::StackUnderflow)?
+            .as_real_number()
+            .ok_or(Error::DomainError)
+    }
+
+    impl Operator {
+        /// Trigonometric functions work in degrees as specified by RS274/NGC.
+        fn apply_unary(&self, operand: Real) -> Result<Real, Error> {
+            Ok(match self {
+                Self::Cos => math::cos(operand.to_radians()),
+                Self::Sin => math::sin(operand.to_radians()),
+                Self::Tan => math::tan(operand.to_radians()),
+                Self::ACos | Self::ASin if !(-1. ..=1.).contains(&operand) => {
+                    return Err(Error::DomainError)
+                }
+                Self::ACos => math::acos(operand).to_degrees(),
+                Self::ASin => math::asin(operand).to_degrees(),
+                Self::Abs => math::abs(operand),
+                Self::Exp => math::exp(operand),
+                Self::Fix => math::floor(operand),
+                Self::Fup => math::ceil(operand),
+                Self::Ln if operand <= 0. => return Err(Error::DomainError),
+                Self::Ln => math::ln(operand),
+                Self::Round => math::round(operand),
+                Self::Sqrt if operand < 0. => return Err(Error::DomainError),
+                Self::Sqrt => math::sqrt(operand),
+                _ => return Err(Error::InvalidExpression(ExpressionError::MisplacedOperator)),
+            })
+        }
+
+        fn apply_binary(&self, lhs: Real, rhs: Real) -> Result<Real, Error> {
+            let as_bool = |v: Real| v != 0.;
+            let from_bool = |b: bool| if b { 1. } else { 0. };
+            Ok(match self {
+                Self::Add => lhs + rhs,
+                Self::Substract => lhs - rhs,
+                Self::Multiply => lhs * rhs,
+                Self::Divide | Self::Modulus if rhs == 0. => return Err(Error::DivisionByZero),
+                Self::Divide => lhs / rhs,
+                Self::Modulus => {
+                    // the result takes the sign of the divisor's absolute value (ie positive).
+                    let res = lhs % rhs;
+                    if res < 0. {
+                        res + math::abs(rhs)
+                    } else {
+                        res
+                    }
+                }
+                Self::Power if lhs == 0. && rhs < 0. => return Err(Error::DivisionByZero),
+                Self::Power if lhs < 0. && math::floor(rhs) != rhs => {
+                    return Err(Error::DomainError)
+                }
+                Self::Power => math::pow(lhs, rhs),
+                Self::And => from_bool(as_bool(lhs) && as_bool(rhs)),
+                Self::Or => from_bool(as_bool(lhs) || as_bool(rhs)),
+                Self::Xor => from_bool(as_bool(lhs) != as_bool(rhs)),
+                Self::Equal => from_bool(math::abs(lhs - rhs) < TOLERANCE_EQUAL),
+                Self::NotEqual => from_bool(math::abs(lhs - rhs) >= TOLERANCE_EQUAL),
+                Self::GreaterThan => from_bool(lhs > rhs),
+                Self::GreaterOrEqual => from_bool(lhs >= rhs),
+                Self::LessThan => from_bool(lhs < rhs),
+                Self::LessOrEqual => from_bool(lhs <= rhs),
+                Self::ATan => math::atan2(lhs, rhs).to_degrees(),
+                #[cfg(feature = "parse-min-max")]
+                Self::Min => lhs.min(rhs),
+                #[cfg(feature = "parse-min-max")]
+                Self::Max => lhs.max(rhs),
+                _ => return Err(Error::InvalidExpression(ExpressionError::MisplacedOperator)),
+            })
         }
     }
 
@@ -272,7 +550,233 @@ pub(crate) mod expressions {
 
     #[cfg(test)]
     mod test {
-        // test plan for expressions:
-        // TBD
+        use super::{Either, ExprItem, Expression, Literal, Operator};
+        use crate::{Error, ExpressionError};
+
+        fn expr(items: &[ExprItem]) -> Expression {
+            Expression(items.to_vec())
+        }
+        fn lit(v: f64) -> ExprItem {
+            Either::Right(Literal::from(v))
+        }
+        fn op(op: Operator) -> ExprItem {
+            Either::Left(op)
+        }
+
+        #[cfg(feature = "parse-parameters")]
+        fn evaluate(e: &Expression) -> Result<Literal, Error> {
+            e.evaluate(&mut |idx: Literal| {
+                Some(Literal::from(
+                    idx.as_real_number().map(|v| v * 10.).unwrap_or(-1.),
+                ))
+            })
+        }
+        #[cfg(not(feature = "parse-parameters"))]
+        fn evaluate(e: &Expression) -> Result<Literal, Error> {
+            e.evaluate()
+        }
+
+        /// Relative tolerance of the results.
+        #[cfg(not(feature = "f32"))]
+        const TOLERANCE: f64 = 1e-9;
+        #[cfg(feature = "f32")]
+        const TOLERANCE: f64 = 1e-5;
+
+        #[allow(clippy::useless_conversion)]
+        fn assert_evaluates_to(e: &[ExprItem], expected: f64) {
+            let res = f64::from(
+                evaluate(&expr(e))
+                    .expect("evaluation failed")
+                    .as_real_number()
+                    .unwrap(),
+            );
+            assert!(
+                (res - expected).abs() < TOLERANCE * expected.abs().max(1.),
+                "{:?} evaluated to {} instead of {}",
+                e,
+                res,
+                expected
+            );
+        }
+
+        #[test]
+        fn binary_operators_follow_postfix_order() {
+            // 2 - 9 * [7 * 5] ** 2 * 4 + 8 / 4
+            assert_evaluates_to(
+                &[
+                    lit(2.),
+                    lit(9.),
+                    lit(7.),
+                    lit(5.),
+                    op(Operator::Multiply),
+                    lit(2.),
+                    op(Operator::Power),
+                    op(Operator::Multiply),
+                    lit(4.),
+                    op(Operator::Multiply),
+                    op(Operator::Substract),
+                    lit(8.),
+                    lit(4.),
+                    op(Operator::Divide),
+                    op(Operator::Add),
+                ],
+                2. - 9. * 35. * 35. * 4. + 2.,
+            );
+        }
+
+        #[test]
+        fn modulus_result_is_always_positive() {
+            assert_evaluates_to(&[lit(7.), lit(3.), op(Operator::Modulus)], 1.);
+            assert_evaluates_to(&[lit(-7.), lit(3.), op(Operator::Modulus)], 2.);
+            assert_evaluates_to(&[lit(-7.), lit(-3.), op(Operator::Modulus)], 2.);
+        }
+
+        #[test]
+        fn logical_operators_evaluate_to_one_or_zero() {
+            assert_evaluates_to(&[lit(2.), lit(0.), op(Operator::And)], 0.);
+            assert_evaluates_to(&[lit(2.), lit(-3.), op(Operator::And)], 1.);
+            assert_evaluates_to(&[lit(0.), lit(0.), op(Operator::Or)], 0.);
+            assert_evaluates_to(&[lit(0.), lit(5.), op(Operator::Or)], 1.);
+            assert_evaluates_to(&[lit(1.), lit(5.), op(Operator::Xor)], 0.);
+            assert_evaluates_to(&[lit(0.), lit(5.), op(Operator::Xor)], 1.);
+        }
+
+        #[test]
+        fn comparisons_evaluate_to_one_or_zero() {
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::Equal)], 1.);
+            assert_evaluates_to(&[lit(2.), lit(3.), op(Operator::Equal)], 0.);
+            assert_evaluates_to(&[lit(2.), lit(3.), op(Operator::NotEqual)], 1.);
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::NotEqual)], 0.);
+            // `0.1 + 0.2` is not exactly `0.3`.
+            let sum = [lit(0.1), lit(0.2), op(Operator::Add), lit(0.3)];
+            assert_evaluates_to(&[&sum[..], &[op(Operator::Equal)]].concat(), 1.);
+            assert_evaluates_to(&[&sum[..], &[op(Operator::NotEqual)]].concat(), 0.);
+            assert_evaluates_to(&[lit(1.), lit(1.001), op(Operator::Equal)], 0.);
+            assert_evaluates_to(&[lit(3.), lit(2.), op(Operator::GreaterThan)], 1.);
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::GreaterThan)], 0.);
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::GreaterOrEqual)], 1.);
+            assert_evaluates_to(&[lit(1.), lit(2.), op(Operator::GreaterOrEqual)], 0.);
+            assert_evaluates_to(&[lit(-1.), lit(2.), op(Operator::LessThan)], 1.);
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::LessThan)], 0.);
+            assert_evaluates_to(&[lit(2.), lit(2.), op(Operator::LessOrEqual)], 1.);
+            assert_evaluates_to(&[lit(3.), lit(2.), op(Operator::LessOrEqual)], 0.);
+        }
+
+        #[test]
+        fn trigonometric_functions_work_in_degrees() {
+            assert_evaluates_to(&[lit(90.), op(Operator::Sin)], 1.);
+            assert_evaluates_to(&[lit(180.), op(Operator::Cos)], -1.);
+            assert_evaluates_to(&[lit(45.), op(Operator::Tan)], 1.);
+            assert_evaluates_to(&[lit(0.5), op(Operator::ACos)], 60.);
+            assert_evaluates_to(&[lit(-0.5), op(Operator::ASin)], -30.);
+            assert_evaluates_to(&[lit(1.), lit(-1.), op(Operator::ATan)], 135.);
+        }
+
+        #[test]
+        fn rounding_functions() {
+            assert_evaluates_to(&[lit(-2.5), op(Operator::Fix)], -3.);
+            assert_evaluates_to(&[lit(2.1), op(Operator::Fup)], 3.);
+            assert_evaluates_to(&[lit(2.5), op(Operator::Round)], 3.);
+            assert_evaluates_to(&[lit(-2.5), op(Operator::Round)], -3.);
+            assert_evaluates_to(&[lit(-2.5), op(Operator::Abs)], 2.5);
+        }
+
+        #[test]
+        fn math_errors_are_reported() {
+            for (e, err) in [
+                (
+                    vec![lit(1.), lit(0.), op(Operator::Divide)],
+                    Error::DivisionByZero,
+                ),
+                (
+                    vec![lit(1.), lit(0.), op(Operator::Modulus)],
+                    Error::DivisionByZero,
+                ),
+                (
+                    vec![lit(0.), lit(-1.), op(Operator::Power)],
+                    Error::DivisionByZero,
+                ),
+                (
+                    vec![lit(-8.), lit(0.5), op(Operator::Power)],
+                    Error::DomainError,
+                ),
+                (vec![lit(-1.), op(Operator::Sqrt)], Error::DomainError),
+                (vec![lit(0.), op(Operator::Ln)], Error::DomainError),
+                (vec![lit(1.1), op(Operator::ACos)], Error::DomainError),
+                (vec![lit(-1.1), op(Operator::ASin)], Error::DomainError),
+            ] {
+                assert_eq!(evaluate(&expr(&e)), Err(err), "{:?}", e);
+            }
+            assert_evaluates_to(&[lit(-2.), lit(3.), op(Operator::Power)], -8.);
+        }
+
+        #[test]
+        fn malformed_expressions_are_reported() {
+            assert_eq!(evaluate(&expr(&[])), Err(Error::StackUnderflow));
+            assert_eq!(
+                evaluate(&expr(&[lit(1.), op(Operator::Add)])),
+                Err(Error::StackUnderflow)
+            );
+            assert_eq!(
+                evaluate(&expr(&[lit(1.), lit(2.)])),
+                Err(Error::InvalidExpression(ExpressionError::ExtraOperands))
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "parse-parameters")]
+        fn parameters_are_resolved_through_the_callback() {
+            // ##2 + 1
+            assert_evaluates_to(
+                &[
+                    lit(2.),
+                    op(Operator::GetParameter),
+                    op(Operator::GetParameter),
+                    lit(1.),
+                    op(Operator::Add),
+                ],
+                201.,
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "parse-parameters")]
+        fn undefined_parameters_are_worth_zero_but_do_not_exist() {
+            let resolver = &mut |idx: Literal| (idx == Literal::from(1)).then(|| Literal::from(5));
+            let evaluate = |e: &[ExprItem], resolver: &mut dyn super::ParameterResolver| {
+                expr(e).evaluate(resolver)
+            };
+            assert_eq!(
+                evaluate(&[lit(2.), op(Operator::GetParameter)], resolver),
+                Ok(Literal::from(0))
+            );
+            assert_eq!(
+                evaluate(&[lit(1.), op(Operator::Exists)], resolver),
+                Ok(Literal::from(1))
+            );
+            assert_eq!(
+                evaluate(&[lit(2.), op(Operator::Exists)], resolver),
+                Ok(Literal::from(0))
+            );
+        }
+
+        #[test]
+        #[cfg(feature = "parse-min-max")]
+        fn min_and_max() {
+            assert_evaluates_to(&[lit(2.), lit(-3.), op(Operator::Min)], -3.);
+            assert_evaluates_to(&[lit(2.), lit(-3.), op(Operator::Max)], 2.);
+        }
+
+        #[test]
+        #[cfg(feature = "string-value")]
+        fn strings_are_not_valid_operands() {
+            assert_eq!(
+                evaluate(&expr(&[
+                    Either::Right(Literal::from("hello".to_string())),
+                    op(Operator::Cos)
+                ])),
+                Err(Error::DomainError)
+            );
+        }
     }
 }
@@ -1,66 +1,82 @@
-use crate::stream::PushBackable;
-use futures::{Stream, StreamExt};
+/// `f64`'s math methods are only available with `std`, `libm` provides them otherwise.
+#[allow(dead_code)] // not all of them are used by every feature set
+pub(crate) mod math {
+    macro_rules! forward {
+        ($t:ident: $($name:ident($($arg:ident),*) => $libm:ident;)*) => {
+            $(
+                #[inline]
+                pub fn $name($($arg: $t),*) -> $t {
+                    #[cfg(feature = "std")]
+                    {
+                        $t::$name($($arg),*)
+                    }
+                    #[cfg(not(feature = "std"))]
+                    {
+                        libm::$libm($($arg),*)
+                    }
+                }
+            )*
+        };
+    }
+    forward! { f64:
+        abs(x) => fabs;
+        acos(x) => acos;
+        asin(x) => asin;
+        atan2(y, x) => atan2;
+        ceil(x) => ceil;
+        cos(x) => cos;
+        exp(x) => exp;
+        floor(x) => floor;
+        hypot(x, y) => hypot;
+        ln(x) => log;
+        round(x) => round;
+        sin(x) => sin;
+        sqrt(x) => sqrt;
+        tan(x) => tan;
+    }
 
-#[doc(hidden)]
-#[macro_export]
-macro_rules! try_parse {
-    ($input:expr) => {
-        match $input.await? {
-            ParseResult::Ok(b) => b,
-            ParseResult::Input(e) => return Some(ParseResult::Input(e)),
-            ParseResult::Parsing(e) => return Some(ParseResult::Parsing(e)),
+    #[inline]
+    pub fn pow(x: f64, n: f64) -> f64 {
+        #[cfg(feature = "std")]
+        {
+            x.powf(n)
         }
-    };
-}
-
-#[doc(hidden)]
-#[macro_export]
-macro_rules! try_result {
-    ($input:expr) => {
-        match $input.await? {
-            Ok(b) => b,
-            Err(e) => return Some(ParseResult::Input(e)),
-        }
-    };
-}
-
-pub(crate) async fn skip_whitespaces<S, E>(input: &mut S) -> Option<Result<(), E>>
-where
-    S: Stream<Item = Result<u8, E>> + Unpin + PushBackable<Item = u8>,
-{
-    loop {
-        let b = match input.next().await? {
-            Ok(b) => b,
-            Err(e) => return Some(Err(e)),
-        };
-        if b != b' ' {
-            input.push_back(b);
-            break;
+        #[cfg(not(feature = "std"))]
+        {
+            libm::pow(x, n)
         }
     }
-    Some(Ok(()))
-}
 
-#[cfg(itest)]
-mod test {
-    use super::{skip_whitespaces, stream, StreamExt};
-
-    #[cfg(not(feature = "parse-checksum"))]
-    use crate::stream::pushback::PushBack;
-    #[cfg(feature = "parse-checksum")]
-    use crate::stream::xorsum_pushback::XorSumPushBack;
+    /// The same functions for `f32`.
+    #[cfg(feature = "f32")]
+    pub(crate) mod single {
+        forward! { f32:
+            abs(x) => fabsf;
+            acos(x) => acosf;
+            asin(x) => asinf;
+            atan2(y, x) => atan2f;
+            ceil(x) => ceilf;
+            cos(x) => cosf;
+            exp(x) => expf;
+            floor(x) => floorf;
+            hypot(x, y) => hypotf;
+            ln(x) => logf;
+            round(x) => roundf;
+            sin(x) => sinf;
+            sqrt(x) => sqrtf;
+            tan(x) => tanf;
+        }
 
-    #[test]
-    fn skips_white_spaces_and_pushes_back_the_first_non_space_byte() {
-        let mut data = PushBack::new(stream::iter(
-            b"      d"
-                .iter()
-                .copied()
-                .map(Result::<_, core::convert::Infallible>::Ok),
-        ));
-        futures_executor::block_on(async move {
-            skip_whitespaces(&mut data).await;
-            assert_eq!(Some(Ok(b'd')), data.next().await);
-        });
+        #[inline]
+        pub fn pow(x: f32, n: f32) -> f32 {
+            #[cfg(feature = "std")]
+            {
+                x.powf(n)
+            }
+            #[cfg(not(feature = "std"))]
+            {
+                libm::powf(x, n)
+            }
+        }
     }
 }
@@ -13,6 +13,9 @@ function run_test() {
 
 cargo clippy
 cargo test --color=always
+# these features require std, they cannot be built for the embedded target.
+cargo clippy --all-targets --features futures-io,tokio -- -Dwarnings
+cargo test --color=always --features futures-io,tokio
 
 run_test "Parse comment" parse-comments
 run_test "Parse trailing comment" parse-trailing-comment
@@ -31,5 +34,19 @@ run_test "Parse parameters & string-value" parse-parameters,string-value
 
 run_test "Parse expressions" parse-expressions
 run_test "Parse expressions & parameters" parse-expressions,parse-parameters
+run_test "Parse O-words" parse-o-words
+run_test "Parse MIN/MAX" parse-min-max
 
-run_test "All features" parse-comments,parse-trailing-comment,parse-checksum,parse-parameters,parse-expressions,optional-value,string-value
+run_test "Source position" source-position
+run_test "Source position & checksum" parse-checksum,source-position
+
+run_test "Decimal" decimal
+run_test "Decimal & expressions" parse-expressions,parse-parameters,decimal
+
+run_test "f32" f32
+run_test "f32 & expressions" parse-expressions,parse-parameters,f32
+run_test "f32 & decimal" f32,decimal
+
+run_test "embedded-io-async" embedded-io-async
+
+run_test "All features" parse-comments,parse-trailing-comment,parse-checksum,parse-parameters,parse-expressions,optional-value,string-value,source-position,decimal,f32,embedded-io-async,parse-o-words,parse-min-max